@@ -0,0 +1,48 @@
+-- Per-field write clocks for sync conflict detection. A row records the
+-- task's updated_ts at the last write that changed that field, so a push
+-- carrying `base_updated_ts` can tell which fields moved on the server since
+-- the client's base. Maintained by triggers so every write path (REST, sync,
+-- API token, backup restore) stamps it.
+--
+-- Logically reversible via:
+--   drop trigger task_field_clock_on_delete;
+--   drop trigger task_field_clock_on_update;
+--   drop table task_field_clock;
+create table if not exists task_field_clock (
+    task_id text not null,
+    space_id text not null references space(id) on delete cascade,
+    field text not null,
+    updated_ts integer not null,
+    primary key (task_id, space_id, field)
+);
+
+create trigger if not exists task_field_clock_on_update
+after update on task
+begin
+    insert or replace into task_field_clock (task_id, space_id, field, updated_ts)
+    select new.id, new.space_id, changed.field, new.updated_ts
+    from (
+        select 'title' as field where old.title is not new.title
+        union all select 'status' where old.status is not new.status
+        union all select 'list_id' where old.list_id is not new.list_id
+        union all select 'my_day' where old.my_day is not new.my_day
+        union all select 'priority' where old.priority is not new.priority
+        union all select 'url' where old.url is not new.url
+        union all select 'recur_rule' where old.recur_rule is not new.recur_rule
+        union all select 'due_date' where old.due_date is not new.due_date
+        union all select 'punted_from_due_date'
+            where old.punted_from_due_date is not new.punted_from_due_date
+        union all select 'punted_on_date' where old.punted_on_date is not new.punted_on_date
+        union all select 'notes' where old.notes is not new.notes
+        union all select 'occurrences_completed'
+            where old.occurrences_completed is not new.occurrences_completed
+        union all select 'completed_ts' where old.completed_ts is not new.completed_ts
+        union all select 'assignee_user_id' where old.assignee_user_id is not new.assignee_user_id
+    ) as changed;
+end;
+
+create trigger if not exists task_field_clock_on_delete
+after delete on task
+begin
+    delete from task_field_clock where task_id = old.id and space_id = old.space_id;
+end;
@@ -0,0 +1,99 @@
+-- Per-space monotonic change sequence for the `delta-v2` sync cursor.
+-- `sync_counter.seq` is bumped by triggers on every task, list and task
+-- tombstone write, and the written row is stamped with the new value in its
+-- `change_seq` column, so the counter increment and the row stamp commit
+-- atomically. Unlike `updated_ts`, the sequence never repeats and never steps
+-- backward with the wall clock.
+--
+-- Logically reversible via:
+--   drop trigger task_change_seq_on_insert;   drop trigger task_change_seq_on_update;
+--   drop trigger list_change_seq_on_insert;   drop trigger list_change_seq_on_update;
+--   drop trigger task_tombstone_change_seq_on_insert;
+--   drop trigger task_tombstone_change_seq_on_update;
+--   drop index idx_task_space_change_seq; drop index idx_list_space_change_seq;
+--   drop index idx_task_tombstone_space_change_seq;
+--   alter table task drop column change_seq; alter table list drop column change_seq;
+--   alter table task_tombstone drop column change_seq; drop table sync_counter;
+create table if not exists sync_counter (
+    space_id text primary key references space(id) on delete cascade,
+    seq integer not null default 0
+);
+
+alter table task add column change_seq integer not null default 0;
+alter table list add column change_seq integer not null default 0;
+alter table task_tombstone add column change_seq integer not null default 0;
+
+-- Pre-existing rows all land at seq 1: a delta-v2 client starting from 0
+-- receives them once, and every later write sorts after them.
+update task set change_seq = 1;
+update list set change_seq = 1;
+update task_tombstone set change_seq = 1;
+insert into sync_counter (space_id, seq)
+select id, 1 from space
+where exists (select 1 from task where task.space_id = space.id)
+   or exists (select 1 from list where list.space_id = space.id)
+   or exists (select 1 from task_tombstone where task_tombstone.space_id = space.id);
+
+create index if not exists idx_task_space_change_seq on task(space_id, change_seq);
+create index if not exists idx_list_space_change_seq on list(space_id, change_seq);
+create index if not exists idx_task_tombstone_space_change_seq
+    on task_tombstone(space_id, change_seq);
+
+create trigger if not exists task_change_seq_on_insert
+after insert on task
+begin
+    insert or ignore into sync_counter (space_id, seq) values (new.space_id, 0);
+    update sync_counter set seq = seq + 1 where space_id = new.space_id;
+    update task set change_seq = (select seq from sync_counter where space_id = new.space_id)
+    where id = new.id;
+end;
+
+create trigger if not exists task_change_seq_on_update
+after update on task
+when new.change_seq = old.change_seq
+begin
+    insert or ignore into sync_counter (space_id, seq) values (new.space_id, 0);
+    update sync_counter set seq = seq + 1 where space_id = new.space_id;
+    update task set change_seq = (select seq from sync_counter where space_id = new.space_id)
+    where id = new.id;
+end;
+
+create trigger if not exists list_change_seq_on_insert
+after insert on list
+begin
+    insert or ignore into sync_counter (space_id, seq) values (new.space_id, 0);
+    update sync_counter set seq = seq + 1 where space_id = new.space_id;
+    update list set change_seq = (select seq from sync_counter where space_id = new.space_id)
+    where id = new.id;
+end;
+
+create trigger if not exists list_change_seq_on_update
+after update on list
+when new.change_seq = old.change_seq
+begin
+    insert or ignore into sync_counter (space_id, seq) values (new.space_id, 0);
+    update sync_counter set seq = seq + 1 where space_id = new.space_id;
+    update list set change_seq = (select seq from sync_counter where space_id = new.space_id)
+    where id = new.id;
+end;
+
+create trigger if not exists task_tombstone_change_seq_on_insert
+after insert on task_tombstone
+begin
+    insert or ignore into sync_counter (space_id, seq) values (new.space_id, 0);
+    update sync_counter set seq = seq + 1 where space_id = new.space_id;
+    update task_tombstone
+    set change_seq = (select seq from sync_counter where space_id = new.space_id)
+    where task_id = new.task_id and space_id = new.space_id;
+end;
+
+create trigger if not exists task_tombstone_change_seq_on_update
+after update on task_tombstone
+when new.change_seq = old.change_seq
+begin
+    insert or ignore into sync_counter (space_id, seq) values (new.space_id, 0);
+    update sync_counter set seq = seq + 1 where space_id = new.space_id;
+    update task_tombstone
+    set change_seq = (select seq from sync_counter where space_id = new.space_id)
+    where task_id = new.task_id and space_id = new.space_id;
+end;
@@ -0,0 +1,4 @@
+-- Backs the SQL-filtered `delta-v1` pull, which selects changed tasks with an
+-- `updated_ts` range and walks them in (updated_ts, id) order.
+-- Logically reversible via: drop index idx_task_space_updated;
+create index if not exists idx_task_space_updated on task(space_id, updated_ts, id);
@@ -0,0 +1,47 @@
+-- List change tracking for delta sync: `list.updated_ts` is stamped by every
+-- list write, and `list_tombstone` records deleted lists so clients can drop
+-- them from their cache without a full reload. Tombstones are stamped with
+-- the space `change_seq` like task tombstones (see 0019). Pre-existing lists
+-- keep `updated_ts = 0`.
+--
+-- Logically reversible via:
+--   drop trigger list_tombstone_change_seq_on_insert;
+--   drop trigger list_tombstone_change_seq_on_update;
+--   drop index idx_list_tombstone_space_deleted;
+--   drop index idx_list_tombstone_space_change_seq;
+--   drop table list_tombstone; alter table list drop column updated_ts;
+alter table list add column updated_ts integer not null default 0;
+
+create table if not exists list_tombstone (
+    list_id text not null,
+    space_id text not null references space(id) on delete cascade,
+    deleted_ts integer not null,
+    change_seq integer not null default 0,
+    primary key (list_id, space_id)
+);
+
+create index if not exists idx_list_tombstone_space_deleted
+    on list_tombstone(space_id, deleted_ts);
+create index if not exists idx_list_tombstone_space_change_seq
+    on list_tombstone(space_id, change_seq);
+
+create trigger if not exists list_tombstone_change_seq_on_insert
+after insert on list_tombstone
+begin
+    insert or ignore into sync_counter (space_id, seq) values (new.space_id, 0);
+    update sync_counter set seq = seq + 1 where space_id = new.space_id;
+    update list_tombstone
+    set change_seq = (select seq from sync_counter where space_id = new.space_id)
+    where list_id = new.list_id and space_id = new.space_id;
+end;
+
+create trigger if not exists list_tombstone_change_seq_on_update
+after update on list_tombstone
+when new.change_seq = old.change_seq
+begin
+    insert or ignore into sync_counter (space_id, seq) values (new.space_id, 0);
+    update sync_counter set seq = seq + 1 where space_id = new.space_id;
+    update list_tombstone
+    set change_seq = (select seq from sync_counter where space_id = new.space_id)
+    where list_id = new.list_id and space_id = new.space_id;
+end;
@@ -0,0 +1,18 @@
+-- Processed `sync_push` op ids per user, with the outcome returned the first
+-- time (applied row, deletion or rejection, as JSON). A replayed op_id gets
+-- the stored outcome instead of being applied again. Rows are written in the
+-- same transaction as the change they describe and expire after the retention
+-- window enforced by the server (`SYNC_OP_RETENTION_MS`).
+--
+-- Logically reversible via:
+--   drop index idx_sync_op_user_created; drop table sync_op;
+create table if not exists sync_op (
+    space_id text not null references space(id) on delete cascade,
+    user_id text not null references user(id) on delete cascade,
+    op_id text not null,
+    outcome text not null,
+    created_ts integer not null,
+    primary key (space_id, user_id, op_id)
+);
+
+create index if not exists idx_sync_op_user_created on sync_op(space_id, user_id, created_ts);
@@ -0,0 +1,13 @@
+-- Tombstone retention: the background compaction job deletes task and list
+-- tombstones older than `TOMBSTONE_RETENTION_DAYS` and records, per space,
+-- the newest `deleted_ts` and `change_seq` it removed. A pull whose cursor is
+-- at or below that watermark may have missed a deletion, so the server
+-- answers it with a full snapshot flagged `full_resync_required`.
+--
+-- Logically reversible via:
+--   drop table sync_compaction;
+create table if not exists sync_compaction (
+    space_id text primary key references space(id) on delete cascade,
+    pruned_ts integer not null default 0,
+    pruned_seq integer not null default 0
+);
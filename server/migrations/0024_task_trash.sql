@@ -0,0 +1,21 @@
+-- Recoverable task deletes: `delete_task` copies the deleted row (as a
+-- `TaskRow` JSON snapshot) into `task_trash` alongside its tombstone, so
+-- `POST /tasks/:id/restore` can revive it with its title, notes and
+-- recurrence intact. `list_id` and `created_by_user_id` are copied out of the
+-- snapshot for the contributor scoping of `GET /tasks/trash`. Entries are
+-- purged after `TASK_TRASH_RETENTION_DAYS` by a background job.
+--
+-- Logically reversible via:
+--   drop index idx_task_trash_space_deleted; drop table task_trash;
+create table if not exists task_trash (
+    task_id text not null,
+    space_id text not null references space(id) on delete cascade,
+    list_id text not null,
+    created_by_user_id text,
+    task text not null,
+    deleted_ts integer not null,
+    deleted_by_user_id text not null,
+    primary key (task_id, space_id)
+);
+
+create index if not exists idx_task_trash_space_deleted on task_trash(space_id, deleted_ts);
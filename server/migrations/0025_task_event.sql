@@ -0,0 +1,22 @@
+-- Per-task change history: one row per task write (create, update, status
+-- change, delete, restore) from every path — REST, sync push and the API
+-- token — recording the acting user, the auth scope they used and a
+-- field-level diff (`{"field": {"from": .., "to": ..}}` as JSON). `list_id`
+-- is the task's list after the write, so history stays grant-scoped after
+-- the task is deleted.
+--
+-- Logically reversible via:
+--   drop index idx_task_event_task; drop table task_event;
+create table if not exists task_event (
+    id integer primary key autoincrement,
+    space_id text not null references space(id) on delete cascade,
+    task_id text not null,
+    list_id text not null,
+    kind text not null,
+    actor_user_id text not null,
+    scope text not null,
+    changes text not null,
+    ts integer not null
+);
+
+create index if not exists idx_task_event_task on task_event(space_id, task_id, id);
@@ -0,0 +1,34 @@
+-- Space-wide audit log of security-relevant actions: member creation and
+-- deletion, admin password resets, grant changes, session revocations,
+-- backup export/restore and failed logins. `actor_user_id` is null when a
+-- failed login names an unknown email; `detail` is a JSON object specific to
+-- the action. Rows are append-only: updates and deletes are refused by
+-- triggers. `space_id` is deliberately not a foreign key so a failed login
+-- naming an unknown space is still recorded.
+--
+-- Logically reversible via:
+--   drop trigger audit_log_no_delete; drop trigger audit_log_no_update;
+--   drop index idx_audit_log_space_ts; drop table audit_log;
+create table if not exists audit_log (
+    id integer primary key autoincrement,
+    space_id text not null,
+    ts integer not null,
+    actor_user_id text,
+    action text not null,
+    target_user_id text,
+    detail text not null default '{}'
+);
+
+create index if not exists idx_audit_log_space_ts on audit_log(space_id, ts);
+
+create trigger if not exists audit_log_no_update
+before update on audit_log
+begin
+    select raise(abort, 'audit_log is append-only');
+end;
+
+create trigger if not exists audit_log_no_delete
+before delete on audit_log
+begin
+    select raise(abort, 'audit_log is append-only');
+end;
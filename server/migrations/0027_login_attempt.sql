@@ -0,0 +1,15 @@
+-- Failed-attempt counters for `POST /auth/login` and `POST /auth/setup`.
+-- `throttle_key` is `account:<lowercased email>` or `ip:<client address>`;
+-- a key is locked out while `locked_until_ts` (ms) is in the future. Rows
+-- are server-global rather than per space: an email or address is throttled
+-- the same whichever space it targets. A successful login deletes its
+-- account row; a stale row is reset by the next failure.
+--
+-- Logically reversible via:
+--   drop table login_attempt;
+create table if not exists login_attempt (
+    throttle_key text primary key,
+    failures integer not null,
+    last_failure_ts integer not null,
+    locked_until_ts integer not null default 0
+);
@@ -0,0 +1,25 @@
+-- Refresh-token sessions. Login and first-run setup start a session family
+-- (`family_id`) and hand the client an opaque refresh token; only its
+-- SHA-256 (`token_hash`) is stored. `POST /auth/refresh` marks the
+-- presented row `rotated_ts` and inserts its successor in the same family.
+-- Presenting an already-rotated token is treated as theft: the whole family
+-- is revoked. Revoking sessions, changing a password, an admin password
+-- reset and removing a member set `revoked_ts` on the affected rows.
+--
+-- Logically reversible via:
+--   drop index idx_session_user; drop index idx_session_family;
+--   drop table session;
+create table if not exists session (
+    id text primary key,
+    family_id text not null,
+    user_id text not null references user(id) on delete cascade,
+    space_id text not null references space(id) on delete cascade,
+    token_hash text not null unique,
+    created_ts integer not null,
+    expires_ts integer not null,
+    rotated_ts integer,
+    revoked_ts integer
+);
+
+create index if not exists idx_session_family on session(family_id);
+create index if not exists idx_session_user on session(user_id, space_id);
@@ -0,0 +1,35 @@
+-- Per-device sessions. One row per signed-in device, keyed by the refresh
+-- token family (`session.family_id`); access JWTs carry it as their `sid`
+-- claim, so revoking the row signs that device out on its next request.
+-- `device_label` is supplied by the client at login, `user_agent` is the
+-- login request's header, `last_seen_ts` advances on every refresh and
+-- `expires_ts` tracks the family's newest refresh token. Families created
+-- before this migration are backfilled without a label or user agent.
+--
+-- Logically reversible via:
+--   drop index idx_device_session_user; drop table device_session;
+create table if not exists device_session (
+    id text primary key,
+    user_id text not null references user(id) on delete cascade,
+    space_id text not null references space(id) on delete cascade,
+    device_label text,
+    user_agent text,
+    created_ts integer not null,
+    last_seen_ts integer not null,
+    expires_ts integer not null,
+    revoked_ts integer
+);
+
+create index if not exists idx_device_session_user on device_session(user_id, space_id);
+
+insert or ignore into device_session (id, user_id, space_id, created_ts, last_seen_ts, expires_ts, revoked_ts)
+select
+    family_id,
+    min(user_id),
+    min(space_id),
+    min(created_ts),
+    max(created_ts),
+    max(expires_ts),
+    case when count(revoked_ts) = count(*) then max(revoked_ts) end
+from session
+group by family_id;
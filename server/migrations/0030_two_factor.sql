@@ -0,0 +1,36 @@
+-- TOTP two-factor authentication. `POST /auth/2fa/setup` stores a fresh
+-- base32 `secret` with `enabled_ts` null (pending); `POST /auth/2fa/verify`
+-- sets `enabled_ts` once the user proves their authenticator produces
+-- matching codes. The secret must stay readable to check codes, so it is
+-- stored as is. `last_used_step` is the 30-second step of the newest
+-- accepted code; a code from that step or an earlier one is refused, so a
+-- code cannot be replayed. Recovery codes are single-use and stored only as
+-- SHA-256 hashes (`code_hash`).
+--
+-- `space.require_admin_two_factor` is the admin policy: while it is set,
+-- an admin of the space who has not enabled 2FA acts as a contributor until
+-- they enroll.
+--
+-- Logically reversible via:
+--   drop index idx_recovery_code_user; drop table recovery_code;
+--   drop table user_totp;
+--   alter table space drop column require_admin_two_factor;
+create table if not exists user_totp (
+    user_id text primary key references user(id) on delete cascade,
+    secret text not null,
+    created_ts integer not null,
+    enabled_ts integer,
+    last_used_step integer not null default 0
+);
+
+create table if not exists recovery_code (
+    id text primary key,
+    user_id text not null references user(id) on delete cascade,
+    code_hash text not null unique,
+    created_ts integer not null,
+    used_ts integer
+);
+
+create index if not exists idx_recovery_code_user on recovery_code(user_id);
+
+alter table space add column require_admin_two_factor integer not null default 0;
@@ -0,0 +1,19 @@
+-- Single-use password reset tokens. An admin (`POST /auth/members/:user_id/
+-- reset-token`) or the `reset_token` CLI issues one for a user; only its
+-- SHA-256 (`token_hash`) is stored, and issuing a new token deletes the
+-- user's unused ones. The unauthenticated `POST /auth/reset` consumes it by
+-- setting `used_ts`. `space_id` is the space the reset is audited in.
+--
+-- Logically reversible via:
+--   drop index idx_password_reset_user; drop table password_reset;
+create table if not exists password_reset (
+    id text primary key,
+    user_id text not null references user(id) on delete cascade,
+    space_id text not null references space(id) on delete cascade,
+    token_hash text not null unique,
+    created_ts integer not null,
+    expires_ts integer not null,
+    used_ts integer
+);
+
+create index if not exists idx_password_reset_user on password_reset(user_id);
@@ -0,0 +1,17 @@
+-- Pending OpenID Connect sign-ins. `GET /auth/oidc/start` stores one row per
+-- authorization request: the `state` sent to the provider (primary key), the
+-- `nonce` the ID token must echo, the PKCE `code_verifier` and the space the
+-- user is signing in to. `POST /auth/oidc/callback` deletes the row it
+-- redeems, so each authorization response is accepted once; rows past
+-- `expires_ts` are refused and swept by later starts.
+--
+-- Logically reversible via:
+--   drop table oidc_login;
+create table if not exists oidc_login (
+    state text primary key,
+    nonce text not null,
+    code_verifier text not null,
+    space_id text not null,
+    created_ts integer not null,
+    expires_ts integer not null
+);
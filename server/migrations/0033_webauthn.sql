@@ -0,0 +1,34 @@
+-- Passkeys (WebAuthn). `user_credential` holds one row per registered
+-- authenticator: `id` is the credential ID (base64url, as the browser
+-- reports it), `public_key` the COSE public key (base64url) and `algorithm`
+-- its COSE algorithm (-7 ES256, -8 EdDSA, -257 RS256). `sign_count` is the
+-- authenticator's last reported signature counter; an assertion whose
+-- counter does not move forward (when the authenticator keeps one) is
+-- refused as a possible clone.
+--
+-- `webauthn_challenge` holds outstanding registration and sign-in
+-- challenges. Each is deleted when redeemed and refused after `expires_ts`;
+-- registration challenges are bound to the user who asked for them.
+--
+-- Logically reversible via:
+--   drop index idx_user_credential_user; drop table user_credential;
+--   drop table webauthn_challenge;
+create table if not exists user_credential (
+    id text primary key,
+    user_id text not null references user(id) on delete cascade,
+    public_key text not null,
+    algorithm integer not null,
+    sign_count integer not null default 0,
+    label text,
+    created_ts integer not null,
+    last_used_ts integer
+);
+
+create index if not exists idx_user_credential_user on user_credential(user_id);
+
+create table if not exists webauthn_challenge (
+    challenge text primary key,
+    purpose text not null check (purpose in ('register', 'login')),
+    user_id text references user(id) on delete cascade,
+    expires_ts integer not null
+);
@@ -0,0 +1,25 @@
+-- Named API tokens for automations (`X-TaskSync-Api-Token`). Admins mint
+-- them at `POST /auth/api-tokens`; each is bound to one member (`user_id`)
+-- and space and acts with that member's current role. Only a SHA-256 hash
+-- of the token is stored (`token_hash`); `token_prefix` keeps its first
+-- characters so a token can be recognised in lists. A token stops working
+-- after `expires_ts`, once `revoked_ts` is set, or when its member leaves
+-- the space. `last_used_ts` advances at most once a minute.
+--
+-- Logically reversible via:
+--   drop index idx_api_token_space; drop table api_token;
+create table if not exists api_token (
+    id text primary key,
+    user_id text not null references user(id) on delete cascade,
+    space_id text not null references space(id) on delete cascade,
+    name text not null,
+    token_hash text not null unique,
+    token_prefix text not null,
+    created_by text references user(id) on delete set null,
+    created_ts integer not null,
+    expires_ts integer not null,
+    last_used_ts integer,
+    revoked_ts integer
+);
+
+create index if not exists idx_api_token_space on api_token(space_id);
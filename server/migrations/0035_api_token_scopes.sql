@@ -0,0 +1,8 @@
+-- Scopes for named API tokens: a space-separated set of `tasks:read`,
+-- `tasks:write`, `tasks:complete`, `lists:read` and `backup:export`. Each
+-- `/api` route requires one of them. Tokens minted before this migration
+-- could only create tasks, so they get `tasks:write`.
+--
+-- Logically reversible via:
+--   alter table api_token drop column scopes;
+alter table api_token add column scopes text not null default 'tasks:write';
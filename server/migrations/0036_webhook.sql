@@ -0,0 +1,46 @@
+-- Outbound webhooks. `webhook` holds an admin's subscription: the `url` to
+-- POST to, the space-separated `events` it wants (`task.created`,
+-- `task.completed`, `task.deleted`), an optional `list_id` filter and the
+-- `secret` payloads are signed with (HMAC-SHA256). The secret has to stay
+-- readable to sign, so it is stored as is.
+--
+-- `webhook_delivery` is the durable queue and delivery log. A row is
+-- inserted in the same transaction as the task write that caused it, so an
+-- event is never lost to a crash between the write and the send. The
+-- delivery worker picks `pending` rows once `next_attempt_ts` has passed; a
+-- failed attempt backs off exponentially and the row ends `failed` after
+-- the last attempt, or `delivered` on any 2xx response. Finished rows are
+-- purged after 30 days.
+--
+-- Logically reversible via:
+--   drop index idx_webhook_delivery_due; drop index idx_webhook_delivery_webhook;
+--   drop table webhook_delivery; drop index idx_webhook_space; drop table webhook;
+create table if not exists webhook (
+    id text primary key,
+    space_id text not null references space(id) on delete cascade,
+    url text not null,
+    events text not null,
+    list_id text,
+    secret text not null,
+    created_by text references user(id) on delete set null,
+    created_ts integer not null
+);
+
+create index if not exists idx_webhook_space on webhook(space_id);
+
+create table if not exists webhook_delivery (
+    id integer primary key autoincrement,
+    webhook_id text not null references webhook(id) on delete cascade,
+    event text not null,
+    payload text not null,
+    status text not null check (status in ('pending', 'delivered', 'failed')),
+    attempts integer not null default 0,
+    next_attempt_ts integer not null,
+    last_attempt_ts integer,
+    last_status_code integer,
+    last_error text,
+    created_ts integer not null
+);
+
+create index if not exists idx_webhook_delivery_due on webhook_delivery(status, next_attempt_ts);
+create index if not exists idx_webhook_delivery_webhook on webhook_delivery(webhook_id, id);
@@ -0,0 +1,17 @@
+-- Who could read a list when it was deleted. Grants cascade away with the
+-- list, so `delete_list_for_ctx` copies them here next to the tombstone and
+-- contributor pulls only return `deleted_lists` entries they were granted,
+-- the same filter live lists use. Rows go with their tombstone (compaction,
+-- or a list re-created under the same id). Tombstones written before this
+-- migration have no snapshot and are only delivered to admins.
+--
+-- Logically reversible via:
+--   drop table list_tombstone_grant;
+create table if not exists list_tombstone_grant (
+    list_id text not null,
+    space_id text not null,
+    user_id text not null,
+    primary key (list_id, space_id, user_id),
+    foreign key (list_id, space_id)
+        references list_tombstone(list_id, space_id) on delete cascade
+);
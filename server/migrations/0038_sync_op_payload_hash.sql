@@ -0,0 +1,9 @@
+-- Fingerprint of the change each `sync_op` row settled (SHA-256 of the change
+-- as pushed: kind, target id and body). A push only replays the recorded
+-- outcome when both the op_id and the fingerprint match; the same op_id with
+-- a different payload is a new op and is applied. Rows recorded before this
+-- migration have an empty fingerprint, so their op_ids apply again once.
+--
+-- Logically reversible via:
+--   alter table sync_op drop column payload_hash;
+alter table sync_op add column payload_hash text not null default '';
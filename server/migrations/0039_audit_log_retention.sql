@@ -0,0 +1,22 @@
+-- Audit log retention. `audit_log_retention` holds the single horizon the
+-- purge job has advanced to (`AUDIT_LOG_RETENTION_DAYS`); the delete trigger
+-- from 0026 is replaced by one that only refuses deletes of rows at or after
+-- that horizon, so old rows can be purged while the recent log stays
+-- append-only. Updates are still refused outright.
+--
+-- Logically reversible via:
+--   drop trigger audit_log_no_delete; drop table audit_log_retention;
+--   then re-create audit_log_no_delete as in 0026.
+create table if not exists audit_log_retention (
+    id integer primary key check (id = 1),
+    purge_before_ts integer not null
+);
+
+drop trigger if exists audit_log_no_delete;
+
+create trigger if not exists audit_log_no_delete
+before delete on audit_log
+when old.ts >= coalesce((select purge_before_ts from audit_log_retention where id = 1), 0)
+begin
+    select raise(abort, 'audit_log is append-only');
+end;
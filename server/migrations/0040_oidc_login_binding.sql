@@ -0,0 +1,16 @@
+-- Browser binding and second factor for OpenID Connect sign-ins.
+-- `binding_hash` is the SHA-256 of a secret `GET /auth/oidc/start` returns
+-- only to the browser that started the sign-in; the callback must present
+-- it, so an authorization response planted in another browser (login CSRF)
+-- is refused. `user_id` is set once the provider has vouched for a member
+-- who has TOTP enabled: the row then waits for the code, and the callback
+-- is repeated with it instead of a second authorization code. Sign-ins
+-- started before this migration have no binding and are dropped.
+--
+-- Logically reversible via:
+--   alter table oidc_login drop column user_id;
+--   alter table oidc_login drop column binding_hash;
+delete from oidc_login;
+
+alter table oidc_login add column binding_hash text not null default '';
+alter table oidc_login add column user_id text references user(id) on delete cascade;
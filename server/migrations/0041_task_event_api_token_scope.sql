@@ -0,0 +1,8 @@
+-- History events written by the programmatic API before scoped tokens
+-- recorded their `scope` as `api_task_create` (the only API write then).
+-- Every API token now records `api_token`, so the old rows are renamed to
+-- match and readers see a single name.
+--
+-- Logically reversible via:
+--   none needed; both names meant "an API token" and nothing reads the old one.
+update task_event set scope = 'api_token' where scope = 'api_task_create';
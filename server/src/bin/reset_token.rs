@@ -0,0 +1,66 @@
+//! Issues a password reset token from the command line, for when no admin
+//! can sign in:
+//!
+//!     cargo run --bin reset_token -- user@example.com [space_id]
+//!
+//! Prints the token; the member redeems it at `POST /auth/reset` (the
+//! "Reset password" screen) within an hour. Tokens and their audit entry
+//! are written by the server's own modules, included below.
+
+use sqlx::SqlitePool;
+use std::env;
+
+#[allow(dead_code)]
+#[path = "../routes/audit_entry.rs"]
+mod audit_entry;
+#[path = "../routes/opaque_token.rs"]
+mod opaque_token;
+#[path = "../routes/reset_token.rs"]
+mod reset_token;
+
+use audit_entry::{insert_audit_entry, AuditAction, AuditEntry};
+use reset_token::insert_reset_token;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = env::args().skip(1);
+    let Some(email) = args.next() else {
+        anyhow::bail!("usage: reset_token <email> [space_id]");
+    };
+    let space_id = args.next();
+
+    let database_url =
+        env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://../data/tasksync.db".to_string());
+    let pool = SqlitePool::connect(&database_url).await?;
+    sqlx::migrate!("./migrations").run(&pool).await?;
+
+    let member: Option<(String, String)> = sqlx::query_as(
+        "select u.id, m.space_id from user u join membership m on m.user_id = u.id where lower(u.email) = lower(?1) and (?2 is null or m.space_id = ?2) order by m.space_id limit 1",
+    )
+    .bind(email.trim())
+    .bind(&space_id)
+    .fetch_optional(&pool)
+    .await?;
+    let Some((user_id, space_id)) = member else {
+        anyhow::bail!("no member with email {email}");
+    };
+
+    let mut tx = pool.begin().await?;
+    let (token, expires_ts) = insert_reset_token(&mut tx, &user_id, &space_id).await?;
+    insert_audit_entry(
+        &mut tx,
+        AuditEntry {
+            space_id: &space_id,
+            actor_user_id: None,
+            action: AuditAction::PasswordResetIssued,
+            target_user_id: Some(&user_id),
+            detail: serde_json::json!({ "via": "cli", "expires_ts": expires_ts }),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    println!("reset token for {email} (space {space_id}), valid for one hour:");
+    println!("{token}");
+    Ok(())
+}
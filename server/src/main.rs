@@ -9,7 +9,10 @@ use axum::{
     Router,
 };
 use routes::{
-    auth_routes, integration_routes, list_routes, sync_routes, task_routes, validate_boot_secrets,
+    auth_routes, integration_routes, list_routes, parse_audit_log_retention,
+    parse_tombstone_retention, parse_trash_retention, spawn_audit_log_purge,
+    spawn_tombstone_compaction, spawn_trash_purge, spawn_webhook_delivery, sync_routes,
+    task_routes, validate_boot_secrets,
 };
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 use std::{env, net::SocketAddr, path::PathBuf, str::FromStr};
@@ -108,6 +111,39 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
+    let tombstone_retention_ms =
+        match parse_tombstone_retention(env::var("TOMBSTONE_RETENTION_DAYS").ok()) {
+            Ok(retention_ms) => retention_ms,
+            Err(message) => {
+                tracing::error!("boot preflight failed:\n{message}");
+                anyhow::bail!(
+                    "boot preflight failed — fix TOMBSTONE_RETENTION_DAYS above and restart"
+                );
+            }
+        };
+
+    let trash_retention_ms = match parse_trash_retention(env::var("TASK_TRASH_RETENTION_DAYS").ok())
+    {
+        Ok(retention_ms) => retention_ms,
+        Err(message) => {
+            tracing::error!("boot preflight failed:\n{message}");
+            anyhow::bail!(
+                "boot preflight failed — fix TASK_TRASH_RETENTION_DAYS above and restart"
+            );
+        }
+    };
+
+    let audit_retention_ms =
+        match parse_audit_log_retention(env::var("AUDIT_LOG_RETENTION_DAYS").ok()) {
+            Ok(retention_ms) => retention_ms,
+            Err(message) => {
+                tracing::error!("boot preflight failed:\n{message}");
+                anyhow::bail!(
+                    "boot preflight failed — fix AUDIT_LOG_RETENTION_DAYS above and restart"
+                );
+            }
+        };
+
     let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| {
         let mut path = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         path.push("../data/tasksync.db");
@@ -128,6 +164,10 @@ async fn main() -> anyhow::Result<()> {
     let pool = SqlitePoolOptions::new().max_connections(5).connect_with(connect_opts).await?;
 
     sqlx::migrate!().run(&pool).await?;
+    spawn_tombstone_compaction(&pool, tombstone_retention_ms);
+    spawn_trash_purge(&pool, trash_retention_ms);
+    spawn_audit_log_purge(&pool, audit_retention_ms);
+    spawn_webhook_delivery(&pool);
 
     // Mirrors `routes::types::API_TOKEN_HEADER` (not re-exported across the
     // module boundary) — the programmatic task-creation API's request
@@ -159,7 +199,7 @@ async fn main() -> anyhow::Result<()> {
     let addr: SocketAddr = SocketAddr::from(([0, 0, 0, 0], 3000));
     let listener = tokio::net::TcpListener::bind(addr).await?;
     tracing::info!("listening on {addr}");
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     Ok(())
 }
 
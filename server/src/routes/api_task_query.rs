@@ -0,0 +1,150 @@
+//! Filtered, keyset-paged task reads for `GET /api/tasks`.
+//!
+//! Every filter is optional and they combine with `and`. Rows are walked in
+//! board order, `(task_order, id)`; a truncated page returns an opaque
+//! `next_cursor` naming the last row, and the next request passes it back
+//! as `cursor` together with the same filters. Visibility follows
+//! `get_tasks_for_ctx`: admins see the space, contributors their granted
+//! lists.
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+
+use super::tasks::TaskRow;
+use super::types::{is_valid_task_status, RequestCtx, Role};
+
+const DEFAULT_API_PAGE_SIZE: i64 = 100;
+/// Largest page a caller may request; larger `limit`s are clamped.
+const MAX_API_PAGE_SIZE: i64 = 500;
+
+#[derive(Default, Deserialize)]
+pub(super) struct ApiTaskQuery {
+    pub(super) list_id: Option<String>,
+    /// `pending`, `done` or `cancelled`.
+    pub(super) status: Option<String>,
+    /// Tasks due strictly before this `YYYY-MM-DD` date.
+    pub(super) due_before: Option<String>,
+    /// Tasks due strictly after this `YYYY-MM-DD` date.
+    pub(super) due_after: Option<String>,
+    pub(super) my_day: Option<bool>,
+    pub(super) priority: Option<i64>,
+    pub(super) assignee_user_id: Option<String>,
+    /// Case-insensitive substring of the title or notes.
+    pub(super) q: Option<String>,
+    /// Defaults to 100; clamped to 500.
+    pub(super) limit: Option<i64>,
+    /// `next_cursor` of the previous page.
+    pub(super) cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(super) struct ApiTaskPage {
+    pub(super) tasks: Vec<TaskRow>,
+    /// `None` on the last page.
+    pub(super) next_cursor: Option<String>,
+}
+
+/// The last row of a page. Serialized into the cursor; tampering only moves
+/// the caller around rows its scope already allows.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct TaskCursor {
+    after_order: String,
+    after_id: String,
+}
+
+impl TaskCursor {
+    fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    fn decode(token: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(token).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+fn valid_date(raw: &Option<String>) -> bool {
+    raw.as_deref().is_none_or(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok())
+}
+
+/// One page of the caller's tasks matching `query`. `400` for an unknown
+/// status, a malformed date, a non-positive limit or an unreadable cursor.
+pub(super) async fn read_api_task_page(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    query: &ApiTaskQuery,
+) -> Result<ApiTaskPage, StatusCode> {
+    if query.status.as_deref().is_some_and(|status| !is_valid_task_status(status))
+        || !valid_date(&query.due_before)
+        || !valid_date(&query.due_after)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let limit = match query.limit {
+        Some(limit) if limit <= 0 => return Err(StatusCode::BAD_REQUEST),
+        Some(limit) => limit.min(MAX_API_PAGE_SIZE),
+        None => DEFAULT_API_PAGE_SIZE,
+    };
+    let cursor = match query.cursor.as_deref() {
+        Some(token) => TaskCursor::decode(token).ok_or(StatusCode::BAD_REQUEST)?,
+        None => TaskCursor::default(),
+    };
+    let text = query.q.as_deref().map(str::trim).filter(|text| !text.is_empty());
+
+    let sql = if ctx.role == Role::Admin {
+        "select t.id, t.space_id, t.title, t.status, t.list_id, t.my_day, t.priority, t.task_order as \"order\", t.updated_ts, t.created_ts, t.url, t.recur_rule, t.due_date, t.punted_from_due_date, t.punted_on_date, t.occurrences_completed, t.completed_ts, t.notes, t.assignee_user_id, t.created_by_user_id from task t where t.space_id = ?1 and (?3 is null or t.list_id = ?3) and (?4 is null or t.status = ?4) and (?5 is null or t.due_date < ?5) and (?6 is null or t.due_date > ?6) and (?7 is null or t.my_day = ?7) and (?8 is null or t.priority = ?8) and (?9 is null or t.assignee_user_id = ?9) and (?10 is null or instr(lower(t.title || ' ' || coalesce(t.notes, '')), lower(?10)) > 0) and (t.task_order > ?11 or (t.task_order = ?11 and t.id > ?12)) order by t.task_order asc, t.id asc limit ?13"
+    } else {
+        "select t.id, t.space_id, t.title, t.status, t.list_id, t.my_day, t.priority, t.task_order as \"order\", t.updated_ts, t.created_ts, t.url, t.recur_rule, t.due_date, t.punted_from_due_date, t.punted_on_date, t.occurrences_completed, t.completed_ts, t.notes, t.assignee_user_id, t.created_by_user_id from task t join list_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2 and (?3 is null or t.list_id = ?3) and (?4 is null or t.status = ?4) and (?5 is null or t.due_date < ?5) and (?6 is null or t.due_date > ?6) and (?7 is null or t.my_day = ?7) and (?8 is null or t.priority = ?8) and (?9 is null or t.assignee_user_id = ?9) and (?10 is null or instr(lower(t.title || ' ' || coalesce(t.notes, '')), lower(?10)) > 0) and (t.task_order > ?11 or (t.task_order = ?11 and t.id > ?12)) order by t.task_order asc, t.id asc limit ?13"
+    };
+    // One extra row tells a full page apart from the final one.
+    let mut tasks = sqlx::query_as::<_, TaskRow>(sql)
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .bind(&query.list_id)
+        .bind(&query.status)
+        .bind(&query.due_before)
+        .bind(&query.due_after)
+        .bind(query.my_day.map(i64::from))
+        .bind(query.priority)
+        .bind(&query.assignee_user_id)
+        .bind(text)
+        .bind(&cursor.after_order)
+        .bind(&cursor.after_id)
+        .bind(limit + 1)
+        .fetch_all(conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut next_cursor = None;
+    if tasks.len() as i64 > limit {
+        tasks.truncate(limit as usize);
+        next_cursor = tasks.last().map(|task| {
+            TaskCursor { after_order: task.order.clone(), after_id: task.id.clone() }.encode()
+        });
+    }
+    Ok(ApiTaskPage { tasks, next_cursor })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips_and_rejects_garbage() {
+        let cursor = TaskCursor { after_order: "a0".to_string(), after_id: "t-9".to_string() };
+        assert_eq!(TaskCursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(TaskCursor::decode("not base64 !"), None);
+        assert_eq!(TaskCursor::decode(&URL_SAFE_NO_PAD.encode(b"{}")), None);
+    }
+
+    #[test]
+    fn dates_must_be_calendar_days() {
+        assert!(valid_date(&None));
+        assert!(valid_date(&Some("2026-02-28".to_string())));
+        assert!(!valid_date(&Some("2026-02-30".to_string())));
+        assert!(!valid_date(&Some("tomorrow".to_string())));
+    }
+}
@@ -0,0 +1,282 @@
+//! Named API tokens for the programmatic API (`X-TaskSync-Api-Token`).
+//!
+//! Admins mint any number of tokens at `POST /auth/api-tokens`, each bound
+//! to one member of their space (themselves by default) and expiring after
+//! `expires_in_days`, with a set of `scopes` limiting which `/api` routes
+//! it may call (`tasks:write` by default). The token is shown once, in the
+//! create response; the server keeps only its SHA-256 hash plus a short
+//! prefix for lists.
+//! `GET /auth/api-tokens` lists the space's live tokens and
+//! `DELETE /auth/api-tokens/:token_id` revokes one. A token acts as its
+//! member with their current role, so removing the member (or demoting
+//! them) takes effect on the next call. The legacy `TASK_API_TOKEN` keeps
+//! working alongside (see `ctx_from_api_token`).
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::opaque_token::{new_opaque_token, opaque_token_hash};
+use super::types::{
+    begin_write, ctx_from_headers, resolve_identity, AdminError, ApiScope, ApiScopes, AppState,
+    AuthScope, RequestCtx,
+};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+const DEFAULT_API_TOKEN_TTL_DAYS: i64 = 365;
+const MAX_API_TOKEN_TTL_DAYS: i64 = 3650;
+const MAX_API_TOKEN_NAME_CHARS: usize = 80;
+/// `last_used_ts` is only rewritten when older than this, so a busy
+/// automation does not turn every call into a write.
+const LAST_USED_RESOLUTION_MS: i64 = 60 * 1000;
+/// `tsk_` plus this many characters of the secret are kept for display.
+const TOKEN_PREFIX_CHARS: usize = 12;
+
+#[derive(Deserialize)]
+pub(super) struct CreateApiTokenBody {
+    /// What the token is for, e.g. "Shortcuts on my phone".
+    pub(super) name: String,
+    /// Member the token acts as; defaults to the caller.
+    #[serde(default)]
+    pub(super) user_id: Option<String>,
+    /// Defaults to a year; at most ten.
+    #[serde(default)]
+    pub(super) expires_in_days: Option<i64>,
+    /// Scope names such as `tasks:read`; defaults to `["tasks:write"]`.
+    #[serde(default)]
+    pub(super) scopes: Option<Vec<String>>,
+}
+
+#[derive(Serialize, FromRow)]
+pub(super) struct ApiTokenResponse {
+    pub(super) id: String,
+    pub(super) name: String,
+    pub(super) user_id: String,
+    /// The token's first characters, e.g. `tsk_1a2b3c4d`.
+    pub(super) token_prefix: String,
+    #[sqlx(try_from = "String")]
+    pub(super) scopes: ApiScopes,
+    pub(super) created_by: Option<String>,
+    pub(super) created_ts: i64,
+    pub(super) expires_ts: i64,
+    pub(super) last_used_ts: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(super) struct CreatedApiTokenResponse {
+    #[serde(flatten)]
+    pub(super) api_token: ApiTokenResponse,
+    /// The secret itself; not retrievable later.
+    pub(super) token: String,
+}
+
+/// Whether any API token could currently be accepted: the legacy
+/// `TASK_API_TOKEN` or an unrevoked database token. While neither exists
+/// the programmatic API stays hidden behind a bare `404`.
+pub(super) async fn api_tokens_enabled(state: &AppState) -> Result<bool, StatusCode> {
+    if state.api_token.is_some() {
+        return Ok(true);
+    }
+    sqlx::query_scalar("select exists(select 1 from api_token where revoked_ts is null)")
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(FromRow)]
+struct LiveApiToken {
+    id: String,
+    user_id: String,
+    space_id: String,
+    #[sqlx(try_from = "String")]
+    scopes: ApiScopes,
+    last_used_ts: Option<i64>,
+}
+
+/// Resolves a database API token to its member, or `None` if it is
+/// unknown, revoked or expired. `401` when the member has left the space.
+pub(super) async fn ctx_from_stored_api_token(
+    pool: &SqlitePool,
+    provided: &str,
+) -> Result<Option<RequestCtx>, StatusCode> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let token = sqlx::query_as::<_, LiveApiToken>(
+        "select id, user_id, space_id, scopes, last_used_ts from api_token where token_hash = ?1 and revoked_ts is null and expires_ts > ?2",
+    )
+    .bind(opaque_token_hash(provided.trim()))
+    .bind(now)
+    .fetch_optional(pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(token) = token else {
+        return Ok(None);
+    };
+    let (role, _, two_factor_enrollment_required) =
+        resolve_identity(pool, &token.space_id, &token.user_id, None).await?;
+    if token.last_used_ts.is_none_or(|ts| ts <= now - LAST_USED_RESOLUTION_MS) {
+        sqlx::query("update api_token set last_used_ts = ?1 where id = ?2")
+            .bind(now)
+            .bind(&token.id)
+            .execute(pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    Ok(Some(RequestCtx {
+        space_id: token.space_id,
+        user_id: token.user_id,
+        role,
+        scope: AuthScope::ApiToken(token.scopes),
+        session_id: None,
+        two_factor_enrollment_required,
+    }))
+}
+
+async fn require_admin(headers: &HeaderMap, state: &AppState) -> Result<RequestCtx, AdminError> {
+    let ctx = ctx_from_headers(headers, state).await?;
+    ctx.require_admin()?;
+    Ok(ctx)
+}
+
+/// Admin-only. `400` for a blank name, an expiry outside 1..=3650 days or
+/// an empty or unknown scope, `404` unless `user_id` is a member of the
+/// caller's space.
+pub(super) async fn auth_create_api_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<CreateApiTokenBody>,
+) -> Result<(StatusCode, Json<CreatedApiTokenResponse>), AdminError> {
+    let ctx = require_admin(&headers, &state).await?;
+    let name: String = body.name.trim().chars().take(MAX_API_TOKEN_NAME_CHARS).collect();
+    let days = body.expires_in_days.unwrap_or(DEFAULT_API_TOKEN_TTL_DAYS);
+    if name.is_empty() || !(1..=MAX_API_TOKEN_TTL_DAYS).contains(&days) {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    let scopes = match &body.scopes {
+        Some(names) => ApiScopes::parse(names.iter().map(String::as_str))
+            .filter(|scopes| !scopes.is_empty())
+            .ok_or(StatusCode::BAD_REQUEST)?,
+        None => ApiScope::TasksWrite.into(),
+    };
+    let user_id = body.user_id.unwrap_or_else(|| ctx.user_id.clone());
+    let is_member: bool = sqlx::query_scalar(
+        "select exists(select 1 from membership where user_id = ?1 and space_id = ?2)",
+    )
+    .bind(&user_id)
+    .bind(&ctx.space_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !is_member {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let token = new_opaque_token("tsk_");
+    let api_token = ApiTokenResponse {
+        id: format!("at-{}", Uuid::new_v4()),
+        name,
+        user_id,
+        token_prefix: token.chars().take(4 + TOKEN_PREFIX_CHARS).collect(),
+        scopes,
+        created_by: Some(ctx.user_id.clone()),
+        created_ts: now,
+        expires_ts: now + days * DAY_MS,
+        last_used_ts: None,
+    };
+    let mut tx = begin_write(&state.pool).await?;
+    sqlx::query(
+        "insert into api_token (id, user_id, space_id, name, token_hash, token_prefix, scopes, created_by, created_ts, expires_ts) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    )
+    .bind(&api_token.id)
+    .bind(&api_token.user_id)
+    .bind(&ctx.space_id)
+    .bind(&api_token.name)
+    .bind(opaque_token_hash(&token))
+    .bind(&api_token.token_prefix)
+    .bind(api_token.scopes.to_string())
+    .bind(&ctx.user_id)
+    .bind(now)
+    .bind(api_token.expires_ts)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::ApiTokenCreated,
+            target_user_id: Some(&api_token.user_id),
+            detail: json!({
+                "token_id": api_token.id,
+                "name": api_token.name,
+                "scopes": api_token.scopes,
+                "expires_ts": api_token.expires_ts,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((StatusCode::CREATED, Json(CreatedApiTokenResponse { api_token, token })))
+}
+
+/// Admin-only; the space's unrevoked tokens, newest first. Expired ones are
+/// included so they can be recognised and cleaned up.
+pub(super) async fn auth_api_tokens(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ApiTokenResponse>>, AdminError> {
+    let ctx = require_admin(&headers, &state).await?;
+    let tokens = sqlx::query_as::<_, ApiTokenResponse>(
+        "select id, name, user_id, token_prefix, scopes, created_by, created_ts, expires_ts, last_used_ts from api_token where space_id = ?1 and revoked_ts is null order by created_ts desc, id",
+    )
+    .bind(&ctx.space_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(tokens))
+}
+
+/// Admin-only; `404` for an unknown or already revoked token, or one of
+/// another space.
+pub(super) async fn auth_revoke_api_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(token_id): Path<String>,
+) -> Result<StatusCode, AdminError> {
+    let ctx = require_admin(&headers, &state).await?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut tx = begin_write(&state.pool).await?;
+    let revoked: Option<(String, String)> = sqlx::query_as(
+        "update api_token set revoked_ts = ?1 where id = ?2 and space_id = ?3 and revoked_ts is null returning user_id, name",
+    )
+    .bind(now)
+    .bind(&token_id)
+    .bind(&ctx.space_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some((user_id, name)) = revoked else {
+        return Err(StatusCode::NOT_FOUND.into());
+    };
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::ApiTokenRevoked,
+            target_user_id: Some(&user_id),
+            detail: json!({ "token_id": token_id, "name": name }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
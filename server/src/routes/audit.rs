@@ -0,0 +1,159 @@
+//! Append-only audit log of security-relevant actions in a space.
+//!
+//! The `auth.rs` handlers for member management, admin password resets,
+//! grants, session revocation, backups, login, lockout clearing and
+//! refresh-token reuse call `record_audit` in the transaction that performs
+//! the action, so the action and its entry commit together (or, for a failed
+//! login, once it has been refused). `GET /auth/audit` lets admins read the
+//! log, newest first, filtered by time range and actor.
+//!
+//! Entries older than `AUDIT_LOG_RETENTION_DAYS` (default 365) are purged by
+//! a background job. The table refuses deletes of anything newer than the
+//! horizon the job last recorded in `audit_log_retention`, so it stays
+//! append-only for every other writer.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+use std::time::Duration;
+
+use super::audit_entry::insert_audit_entry;
+pub(super) use super::audit_entry::{AuditAction, AuditEntry};
+use super::types::{ctx_from_headers, parse_retention_days, AdminError, AppState};
+
+const DEFAULT_AUDIT_PAGE_SIZE: i64 = 100;
+const MAX_AUDIT_PAGE_SIZE: i64 = 1000;
+const DEFAULT_AUDIT_LOG_RETENTION_DAYS: i64 = 365;
+/// How often the purge job runs; the first pass runs at boot.
+const AUDIT_LOG_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Parses `AUDIT_LOG_RETENTION_DAYS` (see `parse_retention_days`).
+pub fn parse_audit_log_retention(raw: Option<String>) -> Result<i64, String> {
+    parse_retention_days("AUDIT_LOG_RETENTION_DAYS", raw, DEFAULT_AUDIT_LOG_RETENTION_DAYS)
+}
+
+/// Appends `entry` on `conn`; pass the transaction that performs the action
+/// so the entry commits (or rolls back) with it.
+pub(super) async fn record_audit(
+    conn: &mut SqliteConnection,
+    entry: AuditEntry<'_>,
+) -> Result<(), StatusCode> {
+    insert_audit_entry(conn, entry).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Deletes entries with `ts < horizon_ts` across all spaces, first moving
+/// the horizon the delete trigger allows up to it. Returns the number removed.
+pub(super) async fn purge_audit_log(
+    pool: &SqlitePool,
+    horizon_ts: i64,
+) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    sqlx::query(
+        "insert into audit_log_retention (id, purge_before_ts) values (1, ?1) on conflict(id) do update set purge_before_ts = max(purge_before_ts, excluded.purge_before_ts)",
+    )
+    .bind(horizon_ts)
+    .execute(&mut *tx)
+    .await?;
+    let purged = sqlx::query("delete from audit_log where ts < ?1")
+        .bind(horizon_ts)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(purged)
+}
+
+/// Spawns the purge job. Failures are logged and retried on the next tick;
+/// they never take the server down.
+pub fn spawn_audit_log_purge(pool: &SqlitePool, retention_ms: i64) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(AUDIT_LOG_PURGE_INTERVAL);
+        loop {
+            tick.tick().await;
+            let horizon_ts = chrono::Utc::now().timestamp_millis() - retention_ms;
+            match purge_audit_log(&pool, horizon_ts).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, horizon_ts, "purged audit log"),
+                Err(err) => tracing::warn!(error = %err, "audit log purge failed"),
+            }
+        }
+    });
+}
+
+#[derive(Deserialize)]
+pub(super) struct AuditQuery {
+    /// Inclusive lower bound on `ts` (ms).
+    pub(super) from_ts: Option<i64>,
+    /// Exclusive upper bound on `ts` (ms).
+    pub(super) to_ts: Option<i64>,
+    pub(super) actor_user_id: Option<String>,
+    /// Defaults to 100; clamped to 1000.
+    pub(super) limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(super) struct AuditLogRow {
+    pub(super) id: i64,
+    pub(super) ts: i64,
+    pub(super) actor_user_id: Option<String>,
+    pub(super) action: String,
+    pub(super) target_user_id: Option<String>,
+    pub(super) detail: Value,
+}
+
+#[derive(FromRow)]
+struct AuditLogRecord {
+    id: i64,
+    ts: i64,
+    actor_user_id: Option<String>,
+    action: String,
+    target_user_id: Option<String>,
+    detail: String,
+}
+
+pub(super) async fn auth_audit(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditLogRow>>, AdminError> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    ctx.require_admin()?;
+    let limit = match query.limit {
+        Some(limit) if limit <= 0 => return Err(StatusCode::BAD_REQUEST.into()),
+        Some(limit) => limit.min(MAX_AUDIT_PAGE_SIZE),
+        None => DEFAULT_AUDIT_PAGE_SIZE,
+    };
+    let records = sqlx::query_as::<_, AuditLogRecord>(
+        "select id, ts, actor_user_id, action, target_user_id, detail from audit_log where space_id = ?1 and (?2 is null or ts >= ?2) and (?3 is null or ts < ?3) and (?4 is null or actor_user_id = ?4) order by ts desc, id desc limit ?5",
+    )
+    .bind(&ctx.space_id)
+    .bind(query.from_ts)
+    .bind(query.to_ts)
+    .bind(&query.actor_user_id)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let rows = records
+        .into_iter()
+        .map(|record| {
+            let detail = serde_json::from_str(&record.detail)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            Ok(AuditLogRow {
+                id: record.id,
+                ts: record.ts,
+                actor_user_id: record.actor_user_id,
+                action: record.action,
+                target_user_id: record.target_user_id,
+                detail,
+            })
+        })
+        .collect::<Result<Vec<_>, StatusCode>>()?;
+    Ok(Json(rows))
+}
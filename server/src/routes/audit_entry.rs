@@ -0,0 +1,114 @@
+//! Audit log entries and the insert behind `record_audit`.
+//!
+//! Free of web-framework types so the `reset_token` binary can include it
+//! (`#[path]`) and write entries exactly as the server does.
+
+use serde_json::Value;
+use sqlx::SqliteConnection;
+
+/// Longest string kept in an entry's `detail`; longer values are cut.
+const MAX_AUDIT_DETAIL_CHARS: usize = 256;
+
+#[derive(Clone, Copy)]
+pub(super) enum AuditAction {
+    MemberCreated,
+    MemberDeleted,
+    MemberPasswordReset,
+    GrantChanged,
+    SessionsRevoked,
+    BackupExported,
+    BackupRestored,
+    LoginFailed,
+    LockoutCleared,
+    RefreshTokenReused,
+    SessionRevoked,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    TwoFactorPolicyChanged,
+    RecoveryCodeUsed,
+    PasswordResetIssued,
+    PasswordResetCompleted,
+    PasskeyAdded,
+    PasskeyRemoved,
+    ApiTokenCreated,
+    ApiTokenRevoked,
+    WebhookCreated,
+    WebhookDeleted,
+}
+
+impl AuditAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::MemberCreated => "member_created",
+            AuditAction::MemberDeleted => "member_deleted",
+            AuditAction::MemberPasswordReset => "member_password_reset",
+            AuditAction::GrantChanged => "grant_changed",
+            AuditAction::SessionsRevoked => "sessions_revoked",
+            AuditAction::BackupExported => "backup_exported",
+            AuditAction::BackupRestored => "backup_restored",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::LockoutCleared => "lockout_cleared",
+            AuditAction::RefreshTokenReused => "refresh_token_reused",
+            AuditAction::SessionRevoked => "session_revoked",
+            AuditAction::TwoFactorEnabled => "two_factor_enabled",
+            AuditAction::TwoFactorDisabled => "two_factor_disabled",
+            AuditAction::TwoFactorPolicyChanged => "two_factor_policy_changed",
+            AuditAction::RecoveryCodeUsed => "recovery_code_used",
+            AuditAction::PasswordResetIssued => "password_reset_issued",
+            AuditAction::PasswordResetCompleted => "password_reset_completed",
+            AuditAction::PasskeyAdded => "passkey_added",
+            AuditAction::PasskeyRemoved => "passkey_removed",
+            AuditAction::ApiTokenCreated => "api_token_created",
+            AuditAction::ApiTokenRevoked => "api_token_revoked",
+            AuditAction::WebhookCreated => "webhook_created",
+            AuditAction::WebhookDeleted => "webhook_deleted",
+        }
+    }
+}
+
+/// One entry to append. `detail` must never carry secrets (passwords,
+/// hashes, tokens); its strings are cut to `MAX_AUDIT_DETAIL_CHARS`.
+pub(super) struct AuditEntry<'a> {
+    pub(super) space_id: &'a str,
+    pub(super) actor_user_id: Option<&'a str>,
+    pub(super) action: AuditAction,
+    pub(super) target_user_id: Option<&'a str>,
+    pub(super) detail: Value,
+}
+
+/// Appends `entry` on `conn`; pass the transaction that performs the action
+/// so the entry commits (or rolls back) with it.
+pub(super) async fn insert_audit_entry(
+    conn: &mut SqliteConnection,
+    entry: AuditEntry<'_>,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let detail = bounded_detail(entry.detail);
+    sqlx::query(
+        "insert into audit_log (space_id, ts, actor_user_id, action, target_user_id, detail) values (?1, ?2, ?3, ?4, ?5, ?6)",
+    )
+    .bind(entry.space_id)
+    .bind(now)
+    .bind(entry.actor_user_id)
+    .bind(entry.action.as_str())
+    .bind(entry.target_user_id)
+    .bind(detail.to_string())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Cuts every string in `detail` to `MAX_AUDIT_DETAIL_CHARS`, so a value
+/// that came from a request cannot grow a row without bound.
+fn bounded_detail(detail: Value) -> Value {
+    match detail {
+        Value::String(text) if text.chars().count() > MAX_AUDIT_DETAIL_CHARS => {
+            Value::String(text.chars().take(MAX_AUDIT_DETAIL_CHARS).collect())
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(bounded_detail).collect()),
+        Value::Object(fields) => Value::Object(
+            fields.into_iter().map(|(key, value)| (key, bounded_detail(value))).collect(),
+        ),
+        other => other,
+    }
+}
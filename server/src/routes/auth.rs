@@ -1,28 +1,69 @@
 use axum::{
     extract::{Path, State},
     http::{HeaderMap, StatusCode},
-    routing::{delete, get, patch, post},
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, SqlitePool};
+use serde_json::json;
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
 use uuid::Uuid;
 
+use super::api_tokens::{auth_api_tokens, auth_create_api_token, auth_revoke_api_token};
+use super::audit::{auth_audit, record_audit, AuditAction, AuditEntry};
+use super::login_throttle::{
+    auth_clear_lockout, clear_failures, ensure_not_locked, record_failure, ClientIp, LoginError,
+    ThrottleKey,
+};
+use super::oidc::{auth_oidc_callback, auth_oidc_start};
+use super::password_reset::{auth_issue_reset_token, auth_reset_password};
+use super::session::{
+    auth_logout, auth_member_sessions, auth_refresh, auth_revoke_member_session,
+    auth_revoke_session, auth_sessions, restart_current_session, revoke_user_sessions,
+    start_session, DeviceInfo, UserAgent,
+};
+use super::two_factor::{
+    auth_reset_member_two_factor, auth_set_two_factor_policy, auth_two_factor_disable,
+    auth_two_factor_setup, auth_two_factor_status, auth_two_factor_verify, check_second_factor,
+    SecondFactor,
+};
 use super::types::{
-    app_state, ctx_from_headers, hash_password, is_unique_violation, is_valid_task_status,
-    normalize_avatar_icon, normalize_completion_quotes_json, normalize_custom_sound_files_json,
-    normalize_profile_attachments, normalize_sound_data_url, normalize_sound_file_name,
-    normalize_sound_theme, normalize_streak_settings_json, normalize_ui_font,
-    normalize_ui_list_sort, normalize_ui_sidebar_panels, normalize_ui_theme,
-    parse_custom_sound_files_json, password_meets_policy, unix_now_secs, verify_password, AppState,
-    Role, BACKUP_SCHEMA_V1, SOUND_THEMES,
+    app_state, begin_write, ctx_from_headers, hash_password, is_unique_violation,
+    is_valid_task_status, issue_token, normalize_avatar_icon, normalize_completion_quotes_json,
+    normalize_custom_sound_files_json, normalize_profile_attachments, normalize_sound_data_url,
+    normalize_sound_file_name, normalize_sound_theme, normalize_streak_settings_json,
+    normalize_ui_font, normalize_ui_list_sort, normalize_ui_sidebar_panels, normalize_ui_theme,
+    parse_custom_sound_files_json, password_meets_policy, unix_now_secs, verify_password,
+    AdminError, AppState, BACKUP_SCHEMA_V1, SOUND_THEMES,
+};
+use super::webauthn::{
+    auth_webauthn_credentials, auth_webauthn_delete_credential, auth_webauthn_login_start,
+    auth_webauthn_register_finish, auth_webauthn_register_start, verify_passkey_assertion,
+    PasskeyAssertion,
+};
+use super::webhooks::{
+    auth_create_webhook, auth_delete_webhook, auth_webhook_deliveries, auth_webhooks,
 };
 
 #[derive(Deserialize)]
 pub(super) struct LoginBody {
+    /// Optional with `passkey`; if given it must match the passkey's owner.
+    #[serde(default)]
     pub(super) email: String,
+    /// Ignored with `passkey`.
+    #[serde(default)]
     pub(super) password: String,
     pub(super) space_id: Option<String>,
+    /// Shown in the session list, e.g. "Work laptop".
+    #[serde(default)]
+    pub(super) device_label: Option<String>,
+    /// TOTP or recovery code; required once the account has 2FA enabled.
+    #[serde(default)]
+    pub(super) two_factor_code: Option<String>,
+    /// Passkey assertion answering `POST /auth/webauthn/login/start`,
+    /// instead of the password.
+    #[serde(default)]
+    pub(super) passkey: Option<PasskeyAssertion>,
 }
 
 #[derive(Serialize, FromRow)]
@@ -45,13 +86,16 @@ pub(super) struct LoginResponse {
     pub(super) avatar_icon: Option<String>,
     pub(super) space_id: String,
     pub(super) role: String,
+    /// Opaque; trade it at `POST /auth/refresh` once `token` expires.
+    pub(super) refresh_token: String,
 }
 
 /// Shared response shape for endpoints that re-issue a fresh session token
-/// for the acting device (revoke-sessions, self change-password).
+/// for the acting device (refresh, revoke-sessions, self change-password).
 #[derive(Serialize)]
 pub(super) struct TokenResponse {
     pub(super) token: String,
+    pub(super) refresh_token: String,
 }
 
 /// Response for the unauthenticated first-run status check
@@ -61,6 +105,10 @@ pub(super) struct TokenResponse {
 #[derive(Serialize)]
 pub(super) struct AuthStatusResponse {
     pub(super) owner_exists: bool,
+    /// Single sign-on is configured (`GET /auth/oidc/start` is live).
+    pub(super) oidc_enabled: bool,
+    /// Passkeys are configured (`/auth/webauthn/*` is live).
+    pub(super) webauthn_enabled: bool,
 }
 
 /// Request body for the unauthenticated, self-guarded first-run owner
@@ -72,6 +120,8 @@ pub(super) struct AuthSetupBody {
     pub(super) password: String,
     pub(super) avatar_icon: Option<String>,
     pub(super) space_id: Option<String>,
+    #[serde(default)]
+    pub(super) device_label: Option<String>,
 }
 
 #[derive(Serialize, FromRow)]
@@ -312,38 +362,164 @@ pub(super) async fn password_matches_for_user(
     Ok(false)
 }
 
+/// Throttled per account and per client IP (see `login_throttle.rs`): a
+/// locked-out caller gets `429` before the password is checked. With 2FA
+/// enabled (see `two_factor.rs`) a correct password and no code answers
+/// `401 two_factor_required`; a wrong code counts as a failed attempt. A
+/// `passkey` assertion (see `webauthn.rs`) replaces the password, and with
+/// user verification the TOTP code too.
 pub(super) async fn login(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
     Json(body): Json<LoginBody>,
-) -> Result<Json<LoginResponse>, StatusCode> {
+) -> Result<Json<LoginResponse>, LoginError> {
     let password = body.password.trim();
-    if password.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
-    }
     let email = body.email.trim();
-    if email.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+    if body.passkey.is_none() && (password.is_empty() || email.is_empty()) {
+        return Err(StatusCode::BAD_REQUEST.into());
     }
     let space_id = body.space_id.unwrap_or_else(|| "s1".to_string());
-    let user = sqlx::query_as::<_, LoginUserRow>(
-        "select u.id as user_id, u.email, u.display, u.avatar_icon, u.password_hash, m.role, u.token_version from user u join membership m on m.user_id = u.id where lower(u.email) = lower(?1) and m.space_id = ?2 limit 1",
-    )
-    .bind(email)
-    .bind(&space_id)
-    .fetch_optional(&state.pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::UNAUTHORIZED)?;
+    let now = chrono::Utc::now().timestamp_millis();
+    // The account key comes first; a passkey sign-in may have no email.
+    let mut throttle_keys: Vec<ThrottleKey> = Vec::new();
+    if !email.is_empty() {
+        throttle_keys.push(ThrottleKey::account(&state.login_throttle, email));
+    }
+    throttle_keys.extend(client_ip.map(|ip| ThrottleKey::ip(&state.login_throttle, ip)));
+    ensure_not_locked(&state.pool, &throttle_keys, now).await?;
+
+    let (user, user_verified) = match &body.passkey {
+        Some(assertion) => match verify_passkey_assertion(&state, assertion).await? {
+            Some(passkey) => {
+                let user = sqlx::query_as::<_, LoginUserRow>(
+                    "select u.id as user_id, u.email, u.display, u.avatar_icon, u.password_hash, m.role, u.token_version from user u join membership m on m.user_id = u.id where u.id = ?1 and m.space_id = ?2 limit 1",
+                )
+                .bind(&passkey.user_id)
+                .bind(&space_id)
+                .fetch_optional(&state.pool)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                let user =
+                    user.filter(|user| email.is_empty() || user.email.eq_ignore_ascii_case(email));
+                (user, passkey.user_verified)
+            }
+            None => (None, false),
+        },
+        None => {
+            let user = sqlx::query_as::<_, LoginUserRow>(
+                "select u.id as user_id, u.email, u.display, u.avatar_icon, u.password_hash, m.role, u.token_version from user u join membership m on m.user_id = u.id where lower(u.email) = lower(?1) and m.space_id = ?2 limit 1",
+            )
+            .bind(email)
+            .bind(&space_id)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            let password_ok = match &user {
+                Some(user) => password_matches_for_user(&state, &user.user_id, password).await?,
+                None => false,
+            };
+            (user.filter(|_| password_ok), false)
+        }
+    };
+    let Some(user) = user else {
+        // No actor: nobody authenticated. Only attempts on a member of the
+        // space (the passkey's owner, or the account the email names) are
+        // recorded, under the stored email, so unauthenticated callers
+        // cannot write arbitrary rows into a space's log.
+        let mut conn = state.pool.acquire().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let target: Option<(String, String)> = sqlx::query_as(
+            "select u.id, u.email from user u join membership m on m.user_id = u.id where m.space_id = ?1 and (u.id = (select user_id from user_credential where id = ?2) or (?2 is null and lower(u.email) = lower(?3))) limit 1",
+        )
+        .bind(&space_id)
+        .bind(body.passkey.as_ref().map(|assertion| assertion.id.as_str()))
+        .bind(email)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if let Some((target_user_id, target_email)) = target {
+            let detail = match body.passkey {
+                Some(_) => json!({ "email": target_email, "reason": "passkey" }),
+                None => json!({ "email": target_email }),
+            };
+            record_audit(
+                &mut conn,
+                AuditEntry {
+                    space_id: &space_id,
+                    actor_user_id: None,
+                    action: AuditAction::LoginFailed,
+                    target_user_id: Some(&target_user_id),
+                    detail,
+                },
+            )
+            .await?;
+        }
+        drop(conn);
+        record_failure(&state.pool, &state.login_throttle, &throttle_keys, now).await?;
+        return Err(StatusCode::UNAUTHORIZED.into());
+    };
 
-    let password_ok = password_matches_for_user(&state, &user.user_id, password).await?;
-    if !password_ok {
-        return Err(StatusCode::UNAUTHORIZED);
+    // Spending a code and recording it (or its rejection) commit together.
+    let mut tx = begin_write(&state.pool).await?;
+    let second_factor = match user_verified {
+        // The passkey's PIN or biometric check is the second factor.
+        true => SecondFactor::NotEnrolled,
+        false => {
+            check_second_factor(&mut tx, &user.user_id, body.two_factor_code.as_deref()).await?
+        }
+    };
+    match second_factor {
+        SecondFactor::NotEnrolled | SecondFactor::Totp => {}
+        SecondFactor::Missing => return Err(LoginError::TwoFactorRequired),
+        SecondFactor::RecoveryCode => {
+            record_audit(
+                &mut tx,
+                AuditEntry {
+                    space_id: &space_id,
+                    actor_user_id: Some(&user.user_id),
+                    action: AuditAction::RecoveryCodeUsed,
+                    target_user_id: Some(&user.user_id),
+                    detail: json!({}),
+                },
+            )
+            .await?;
+        }
+        SecondFactor::Invalid => {
+            record_audit(
+                &mut tx,
+                AuditEntry {
+                    space_id: &space_id,
+                    actor_user_id: None,
+                    action: AuditAction::LoginFailed,
+                    target_user_id: Some(&user.user_id),
+                    detail: json!({ "email": user.email, "reason": "two_factor" }),
+                },
+            )
+            .await?;
+            tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            record_failure(&state.pool, &state.login_throttle, &throttle_keys, now).await?;
+            return Err(StatusCode::UNAUTHORIZED.into());
+        }
     }
 
-    let token =
-        super::types::issue_token(&user.user_id, &space_id, user.token_version, &state.jwt_secret)?;
+    if !email.is_empty() {
+        clear_failures(&mut tx, &throttle_keys[0]).await?;
+    }
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let device =
+        DeviceInfo { label: body.device_label.as_deref(), user_agent: user_agent.as_deref() };
+    let session = start_session(&state.pool, &user.user_id, &space_id, device).await?;
+    let token = issue_token(
+        &user.user_id,
+        &space_id,
+        user.token_version,
+        Some(&session.session_id),
+        &state.jwt_secret,
+    )?;
+    let refresh_token = session.refresh_token;
     Ok(Json(LoginResponse {
         token,
+        refresh_token,
         user_id: user.user_id,
         email: user.email,
         display: user.display,
@@ -365,12 +541,36 @@ pub(super) async fn auth_status(
             .fetch_one(&state.pool)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(AuthStatusResponse { owner_exists }))
+    Ok(Json(AuthStatusResponse {
+        owner_exists,
+        oidc_enabled: state.oidc.is_some(),
+        webauthn_enabled: state.webauthn.is_some(),
+    }))
 }
 
 /// Unauthenticated, self-guarded first-run owner provisioning. Deliberately
-/// does NOT call `ctx_from_headers` (see `auth_status`).
-///
+/// does NOT call `ctx_from_headers` (see `auth_status`). Every rejected
+/// attempt (`400`/`409`) counts against the client IP's login throttle, so
+/// the public endpoint cannot be used to burn bcrypt time indefinitely.
+pub(super) async fn auth_setup(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
+    Json(body): Json<AuthSetupBody>,
+) -> Result<(StatusCode, Json<LoginResponse>), LoginError> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let throttle_keys: Vec<ThrottleKey> =
+        client_ip.map(|ip| ThrottleKey::ip(&state.login_throttle, ip)).into_iter().collect();
+    ensure_not_locked(&state.pool, &throttle_keys, now).await?;
+    match provision_owner(&state, body, user_agent.as_deref()).await {
+        Err(status) if status.is_client_error() => {
+            record_failure(&state.pool, &state.login_throttle, &throttle_keys, now).await?;
+            Err(status.into())
+        }
+        result => result.map_err(LoginError::from),
+    }
+}
+
 /// Concurrency-safe by construction: the password is hashed BEFORE opening
 /// the transaction (bcrypt is expensive; doing it first keeps the guarded
 /// critical section short and guarantees a hashing failure never leaves a
@@ -380,9 +580,10 @@ pub(super) async fn auth_status(
 /// prevents two concurrent `/setup` calls from both winning the race — the
 /// loser's transaction rolls back (on `Err` the `Transaction` is dropped
 /// without `commit()`, which issues a `ROLLBACK`) and observes `409`.
-pub(super) async fn auth_setup(
-    State(state): State<AppState>,
-    Json(body): Json<AuthSetupBody>,
+async fn provision_owner(
+    state: &AppState,
+    body: AuthSetupBody,
+    user_agent: Option<&str>,
 ) -> Result<(StatusCode, Json<LoginResponse>), StatusCode> {
     let email = body.email.trim().to_lowercase();
     let display = body.display.trim().to_string();
@@ -454,7 +655,10 @@ pub(super) async fn auth_setup(
     tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // token_version starts at the `user.token_version` column default (0).
-    let token = super::types::issue_token(&user_id, &space_id, 0, &state.jwt_secret)?;
+    let device = DeviceInfo { label: body.device_label.as_deref(), user_agent };
+    let session = start_session(&state.pool, &user_id, &space_id, device).await?;
+    let token = issue_token(&user_id, &space_id, 0, Some(&session.session_id), &state.jwt_secret)?;
+    let refresh_token = session.refresh_token;
     Ok((
         StatusCode::CREATED,
         Json(LoginResponse {
@@ -465,6 +669,7 @@ pub(super) async fn auth_setup(
             avatar_icon,
             space_id,
             role: "admin".to_string(),
+            refresh_token,
         }),
     ))
 }
@@ -716,13 +921,13 @@ pub(super) async fn auth_update_preferences(
 }
 
 pub(super) async fn load_space_backup(
-    pool: &SqlitePool,
+    conn: &mut SqliteConnection,
     space_id: &str,
 ) -> Result<SpaceBackupBundle, StatusCode> {
     let space =
         sqlx::query_as::<_, BackupSpaceRow>("select id, name from space where id = ?1 limit 1")
             .bind(space_id)
-            .fetch_optional(pool)
+            .fetch_optional(&mut *conn)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
             .ok_or(StatusCode::NOT_FOUND)?;
@@ -731,7 +936,7 @@ pub(super) async fn load_space_backup(
         "select u.id, u.email, u.display, u.avatar_icon, u.password_hash, coalesce(u.sound_enabled, 1) as sound_enabled, coalesce(u.sound_volume, 60) as sound_volume, coalesce(u.sound_theme, 'chime_soft') as sound_theme, u.custom_sound_file_id, u.custom_sound_file_name, u.custom_sound_data_url, u.custom_sound_files_json, u.profile_attachments, u.ui_theme, u.ui_sidebar_panels, u.ui_list_sort, u.ui_font, u.ui_completion_quotes, u.streak_settings_json, u.streak_state_json from user u join membership m on m.user_id = u.id where m.space_id = ?1 order by u.id asc",
     )
     .bind(space_id)
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -739,7 +944,7 @@ pub(super) async fn load_space_backup(
         "select id, space_id, user_id, role from membership where space_id = ?1 order by id asc",
     )
     .bind(space_id)
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -747,7 +952,7 @@ pub(super) async fn load_space_backup(
         "select id, space_id, name, icon, color, list_order from list where space_id = ?1 order by list_order asc",
     )
     .bind(space_id)
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -755,7 +960,7 @@ pub(super) async fn load_space_backup(
         "select id, space_id, list_id, user_id from list_grant where space_id = ?1 order by id asc",
     )
     .bind(space_id)
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -763,7 +968,7 @@ pub(super) async fn load_space_backup(
         "select id, space_id, title, status, list_id, my_day, priority, task_order, updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id from task where space_id = ?1 order by task_order asc",
     )
     .bind(space_id)
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -782,12 +987,23 @@ pub(super) async fn load_space_backup(
 pub(super) async fn auth_export_backup(
     State(state): State<AppState>,
     headers: HeaderMap,
-) -> Result<Json<SpaceBackupBundle>, StatusCode> {
+) -> Result<Json<SpaceBackupBundle>, AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
-    let backup = load_space_backup(&state.pool, &ctx.space_id).await?;
+    ctx.require_admin()?;
+    let mut tx = begin_write(&state.pool).await?;
+    let backup = load_space_backup(&mut tx, &ctx.space_id).await?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::BackupExported,
+            target_user_id: None,
+            detail: json!({ "tasks": backup.tasks.len(), "users": backup.users.len() }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(Json(backup))
 }
 
@@ -795,16 +1011,14 @@ pub(super) async fn auth_restore_backup(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<SpaceBackupBundle>,
-) -> Result<Json<RestoreBackupResponse>, StatusCode> {
+) -> Result<Json<RestoreBackupResponse>, AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    ctx.require_admin()?;
     if body.schema != BACKUP_SCHEMA_V1 {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
     if body.space.id.trim().is_empty() || body.space.id != ctx.space_id {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
 
     let has_admin_actor = body.memberships.iter().any(|membership| {
@@ -813,7 +1027,7 @@ pub(super) async fn auth_restore_backup(
             && membership.role == "admin"
     });
     if !has_admin_actor {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
 
     for user in &body.users {
@@ -831,7 +1045,7 @@ pub(super) async fn auth_restore_backup(
             || normalize_completion_quotes_json(user.ui_completion_quotes.clone()).is_err()
             || normalize_streak_settings_json(user.streak_settings_json.clone()).is_err()
         {
-            return Err(StatusCode::BAD_REQUEST);
+            return Err(StatusCode::BAD_REQUEST.into());
         }
     }
     for membership in &body.memberships {
@@ -840,13 +1054,13 @@ pub(super) async fn auth_restore_backup(
             || membership.id.trim().is_empty()
             || !(membership.role == "admin" || membership.role == "contributor")
         {
-            return Err(StatusCode::BAD_REQUEST);
+            return Err(StatusCode::BAD_REQUEST.into());
         }
     }
     for list in &body.lists {
         if list.space_id != ctx.space_id || list.id.trim().is_empty() || list.name.trim().is_empty()
         {
-            return Err(StatusCode::BAD_REQUEST);
+            return Err(StatusCode::BAD_REQUEST.into());
         }
     }
     for grant in &body.list_grants {
@@ -855,7 +1069,7 @@ pub(super) async fn auth_restore_backup(
             || grant.list_id.trim().is_empty()
             || grant.user_id.trim().is_empty()
         {
-            return Err(StatusCode::BAD_REQUEST);
+            return Err(StatusCode::BAD_REQUEST.into());
         }
     }
     for task in &body.tasks {
@@ -866,7 +1080,7 @@ pub(super) async fn auth_restore_backup(
             || !is_valid_task_status(&task.status)
             || !(0..=3).contains(&task.priority)
         {
-            return Err(StatusCode::BAD_REQUEST);
+            return Err(StatusCode::BAD_REQUEST.into());
         }
     }
 
@@ -936,6 +1150,16 @@ pub(super) async fn auth_restore_backup(
         .execute(&mut *tx)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query("delete from task_trash where space_id = ?1")
+        .bind(&ctx.space_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query("delete from task_event where space_id = ?1")
+        .bind(&ctx.space_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     sqlx::query("delete from list_grant where space_id = ?1")
         .bind(&ctx.space_id)
         .execute(&mut *tx)
@@ -1020,9 +1244,7 @@ pub(super) async fn auth_restore_backup(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(Json(RestoreBackupResponse {
+    let response = RestoreBackupResponse {
         restored_at_ts: unix_now_secs() as i64,
         space_id: ctx.space_id,
         users: body.users.len() as i64,
@@ -1030,7 +1252,26 @@ pub(super) async fn auth_restore_backup(
         lists: body.lists.len() as i64,
         list_grants: body.list_grants.len() as i64,
         tasks: body.tasks.len() as i64,
-    }))
+    };
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &response.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::BackupRestored,
+            target_user_id: None,
+            detail: json!({
+                "users": response.users,
+                "memberships": response.memberships,
+                "lists": response.lists,
+                "list_grants": response.list_grants,
+                "tasks": response.tasks,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(response))
 }
 
 pub(super) async fn auth_change_password(
@@ -1057,22 +1298,20 @@ pub(super) async fn auth_change_password(
     // Bumping revokes every OTHER previously-issued session for this user;
     // the acting device stays logged in via the freshly re-issued token
     // below (returning token_version avoids a second round-trip).
+    let mut tx = begin_write(&state.pool).await?;
     let new_token_version: i64 = sqlx::query_scalar(
         "update user set password_hash = ?1, token_version = token_version + 1 where id = ?2 returning token_version",
     )
     .bind(new_password_hash)
     .bind(&ctx.user_id)
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    revoke_user_sessions(&mut tx, &ctx.user_id, None).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let token = super::types::issue_token(
-        &ctx.user_id,
-        &ctx.space_id,
-        new_token_version,
-        &state.jwt_secret,
-    )?;
-    Ok(Json(TokenResponse { token }))
+    let response = restart_current_session(&state, &ctx, new_token_version).await?;
+    Ok(Json(response))
 }
 
 /// Self-service "sign out everywhere": bumps the caller's own token_version,
@@ -1085,21 +1324,30 @@ pub(super) async fn auth_revoke_sessions(
     headers: HeaderMap,
 ) -> Result<Json<TokenResponse>, StatusCode> {
     let ctx = ctx_from_headers(&headers, &state).await?;
+    let mut tx = begin_write(&state.pool).await?;
     let new_token_version: i64 = sqlx::query_scalar(
         "update user set token_version = token_version + 1 where id = ?1 returning token_version",
     )
     .bind(&ctx.user_id)
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let revoked = revoke_user_sessions(&mut tx, &ctx.user_id, None).await?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::SessionsRevoked,
+            target_user_id: Some(&ctx.user_id),
+            detail: json!({ "refresh_sessions": revoked }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let token = super::types::issue_token(
-        &ctx.user_id,
-        &ctx.space_id,
-        new_token_version,
-        &state.jwt_secret,
-    )?;
-    Ok(Json(TokenResponse { token }))
+    let response = restart_current_session(&state, &ctx, new_token_version).await?;
+    Ok(Json(response))
 }
 
 pub(super) async fn auth_members(
@@ -1121,27 +1369,26 @@ pub(super) async fn auth_create_member(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<CreateMemberBody>,
-) -> Result<(StatusCode, Json<AuthMemberResponse>), StatusCode> {
+) -> Result<(StatusCode, Json<AuthMemberResponse>), AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    ctx.require_admin()?;
 
     let email = body.email.trim().to_lowercase();
     let display = body.display.trim();
     let password = body.password.trim();
     if email.is_empty() || display.is_empty() || !password_meets_policy(password) {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
     if body.role != "admin" && body.role != "contributor" {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
     let password_hash = hash_password(password)?;
 
+    let mut tx = begin_write(&state.pool).await?;
     let existing_user_id: Option<String> =
         sqlx::query_scalar("select id from user where lower(email) = lower(?1) limit 1")
             .bind(&email)
-            .fetch_optional(&state.pool)
+            .fetch_optional(&mut *tx)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -1151,7 +1398,7 @@ pub(super) async fn auth_create_member(
         )
         .bind(&password_hash)
         .bind(&found)
-        .execute(&state.pool)
+        .execute(&mut *tx)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         found
@@ -1165,7 +1412,7 @@ pub(super) async fn auth_create_member(
             .bind(display)
             .bind(normalize_avatar_icon(body.avatar_icon))
             .bind(&password_hash)
-            .execute(&state.pool)
+            .execute(&mut *tx)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         new_user_id
@@ -1178,13 +1425,13 @@ pub(super) async fn auth_create_member(
             .bind(&ctx.space_id)
             .bind(&user_id)
             .bind(&body.role)
-            .execute(&state.pool)
+            .execute(&mut *tx)
             .await;
     if let Err(err) = membership_res {
         if is_unique_violation(&err) {
-            return Err(StatusCode::CONFLICT);
+            return Err(StatusCode::CONFLICT.into());
         }
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
     }
 
     let member = sqlx::query_as::<_, AuthMemberResponse>(
@@ -1192,10 +1439,22 @@ pub(super) async fn auth_create_member(
     )
     .bind(&user_id)
     .bind(&ctx.space_id)
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::MemberCreated,
+            target_user_id: Some(&member.user_id),
+            detail: json!({ "email": member.email, "role": member.role }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok((StatusCode::CREATED, Json(member)))
 }
@@ -1204,25 +1463,24 @@ pub(super) async fn auth_delete_member(
     State(state): State<AppState>,
     headers: HeaderMap,
     Path(user_id): Path<String>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    ctx.require_admin()?;
     if user_id == ctx.user_id {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
 
+    let mut tx = begin_write(&state.pool).await?;
     let member_role: Option<String> = sqlx::query_scalar(
         "select role from membership where space_id = ?1 and user_id = ?2 limit 1",
     )
     .bind(&ctx.space_id)
     .bind(&user_id)
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let Some(member_role) = member_role else {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(StatusCode::NOT_FOUND.into());
     };
 
     if member_role == "admin" {
@@ -1230,31 +1488,44 @@ pub(super) async fn auth_delete_member(
             "select count(1) from membership where space_id = ?1 and role = 'admin'",
         )
         .bind(&ctx.space_id)
-        .fetch_one(&state.pool)
+        .fetch_one(&mut *tx)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         if admin_count <= 1 {
-            return Err(StatusCode::BAD_REQUEST);
+            return Err(StatusCode::BAD_REQUEST.into());
         }
     }
 
     sqlx::query("delete from list_grant where space_id = ?1 and user_id = ?2")
         .bind(&ctx.space_id)
         .bind(&user_id)
-        .execute(&state.pool)
+        .execute(&mut *tx)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let result = sqlx::query("delete from membership where space_id = ?1 and user_id = ?2")
         .bind(&ctx.space_id)
         .bind(&user_id)
-        .execute(&state.pool)
+        .execute(&mut *tx)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(StatusCode::NOT_FOUND.into());
     }
+    revoke_user_sessions(&mut tx, &user_id, Some(&ctx.space_id)).await?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::MemberDeleted,
+            target_user_id: Some(&user_id),
+            detail: json!({ "role": member_role }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -1264,24 +1535,23 @@ pub(super) async fn auth_set_member_password(
     headers: HeaderMap,
     Path(user_id): Path<String>,
     Json(body): Json<SetMemberPasswordBody>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    ctx.require_admin()?;
     let password = body.password.trim();
     if !password_meets_policy(password) {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
+    let mut tx = begin_write(&state.pool).await?;
     let member_exists: Option<i64> =
         sqlx::query_scalar("select 1 from membership where space_id = ?1 and user_id = ?2")
             .bind(&ctx.space_id)
             .bind(&user_id)
-            .fetch_optional(&state.pool)
+            .fetch_optional(&mut *tx)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     if member_exists.is_none() {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(StatusCode::NOT_FOUND.into());
     }
 
     let password_hash = hash_password(password)?;
@@ -1294,20 +1564,31 @@ pub(super) async fn auth_set_member_password(
     )
     .bind(password_hash)
     .bind(&user_id)
-    .execute(&state.pool)
+    .execute(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    revoke_user_sessions(&mut tx, &user_id, None).await?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::MemberPasswordReset,
+            target_user_id: Some(&user_id),
+            detail: json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub(super) async fn auth_grants(
     State(state): State<AppState>,
     headers: HeaderMap,
-) -> Result<Json<Vec<ListGrantResponse>>, StatusCode> {
+) -> Result<Json<Vec<ListGrantResponse>>, AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    ctx.require_admin()?;
     let grants = sqlx::query_as::<_, ListGrantResponse>(
         "select g.user_id, g.list_id from list_grant g join membership m on m.user_id = g.user_id and m.space_id = g.space_id where g.space_id = ?1 and m.role = 'contributor' order by g.user_id asc, g.list_id asc",
     )
@@ -1322,31 +1603,30 @@ pub(super) async fn auth_set_grant(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<SetListGrantBody>,
-) -> Result<Json<ListGrantResponse>, StatusCode> {
+) -> Result<Json<ListGrantResponse>, AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    ctx.require_admin()?;
+    let mut tx = begin_write(&state.pool).await?;
     let membership_role: Option<String> = sqlx::query_scalar(
         "select role from membership where space_id = ?1 and user_id = ?2 limit 1",
     )
     .bind(&ctx.space_id)
     .bind(&body.user_id)
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *tx)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     if membership_role.as_deref() != Some("contributor") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
     let list_exists: Option<i64> =
         sqlx::query_scalar("select 1 from list where id = ?1 and space_id = ?2")
             .bind(&body.list_id)
             .bind(&ctx.space_id)
-            .fetch_optional(&state.pool)
+            .fetch_optional(&mut *tx)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     if list_exists.is_none() {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(StatusCode::NOT_FOUND.into());
     }
     if body.granted {
         let grant_id = format!("g-{}", Uuid::new_v4());
@@ -1357,7 +1637,7 @@ pub(super) async fn auth_set_grant(
         .bind(&ctx.space_id)
         .bind(&body.list_id)
         .bind(&body.user_id)
-        .execute(&state.pool)
+        .execute(&mut *tx)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     } else {
@@ -1365,10 +1645,22 @@ pub(super) async fn auth_set_grant(
             .bind(&ctx.space_id)
             .bind(&body.list_id)
             .bind(&body.user_id)
-            .execute(&state.pool)
+            .execute(&mut *tx)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::GrantChanged,
+            target_user_id: Some(&body.user_id),
+            detail: json!({ "list_id": body.list_id, "granted": body.granted }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(Json(ListGrantResponse { user_id: body.user_id, list_id: body.list_id }))
 }
 
@@ -1376,17 +1668,45 @@ pub fn auth_routes(pool: &sqlx::SqlitePool) -> Router {
     let state = app_state(pool);
     Router::new()
         .route("/login", post(login))
+        .route("/refresh", post(auth_refresh))
+        .route("/logout", post(auth_logout))
         .route("/status", get(auth_status))
         .route("/setup", post(auth_setup))
+        .route("/reset", post(auth_reset_password))
+        .route("/oidc/start", get(auth_oidc_start))
+        .route("/oidc/callback", post(auth_oidc_callback))
+        .route("/webauthn/register/start", post(auth_webauthn_register_start))
+        .route("/webauthn/register/finish", post(auth_webauthn_register_finish))
+        .route("/webauthn/login/start", post(auth_webauthn_login_start))
+        .route("/webauthn/credentials", get(auth_webauthn_credentials))
+        .route("/webauthn/credentials/:credential_id", delete(auth_webauthn_delete_credential))
         .route("/me", get(auth_me).patch(auth_update_me))
         .route("/sound", get(auth_get_sound).patch(auth_update_sound))
         .route("/preferences", get(auth_get_preferences).patch(auth_update_preferences))
         .route("/backup", get(auth_export_backup).post(auth_restore_backup))
         .route("/password", patch(auth_change_password))
         .route("/revoke-sessions", post(auth_revoke_sessions))
+        .route("/sessions", get(auth_sessions))
+        .route("/sessions/:session_id", delete(auth_revoke_session))
+        .route("/2fa", get(auth_two_factor_status))
+        .route("/2fa/setup", post(auth_two_factor_setup))
+        .route("/2fa/verify", post(auth_two_factor_verify))
+        .route("/2fa/disable", post(auth_two_factor_disable))
+        .route("/2fa/policy", put(auth_set_two_factor_policy))
         .route("/members", get(auth_members).post(auth_create_member))
         .route("/members/:user_id", delete(auth_delete_member))
         .route("/members/:user_id/password", patch(auth_set_member_password))
+        .route("/members/:user_id/reset-token", post(auth_issue_reset_token))
+        .route("/members/:user_id/lockout", delete(auth_clear_lockout))
+        .route("/members/:user_id/sessions", get(auth_member_sessions))
+        .route("/members/:user_id/sessions/:session_id", delete(auth_revoke_member_session))
+        .route("/members/:user_id/2fa", delete(auth_reset_member_two_factor))
         .route("/grants", get(auth_grants).put(auth_set_grant))
+        .route("/audit", get(auth_audit))
+        .route("/api-tokens", get(auth_api_tokens).post(auth_create_api_token))
+        .route("/api-tokens/:token_id", delete(auth_revoke_api_token))
+        .route("/webhooks", get(auth_webhooks).post(auth_create_webhook))
+        .route("/webhooks/:webhook_id", delete(auth_delete_webhook))
+        .route("/webhooks/:webhook_id/deliveries", get(auth_webhook_deliveries))
         .with_state(state)
 }
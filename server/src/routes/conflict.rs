@@ -0,0 +1,109 @@
+//! Field-level conflict detection for `update_task` sync pushes that carry a
+//! `base_updated_ts`.
+//!
+//! Such a push is a patch: it should name only the fields the client edited
+//! since it last saw the task at `base_updated_ts`. Fields the server changed
+//! after that base are read from `task_field_clock` (stamped by triggers on
+//! every task write). A patch field conflicts only when the server also
+//! changed it since the base AND the two sides disagree on the value;
+//! everything else merges, so two devices editing different fields of the
+//! same task both keep their edits regardless of push order.
+
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+use sqlx::SqliteConnection;
+use std::collections::HashSet;
+
+use super::tasks::{get_task_for_ctx, TaskRow, UpdateTaskMeta};
+use super::types::RequestCtx;
+
+pub(super) enum MergeOutcome {
+    /// Apply this (possibly adjusted) patch through `update_task_meta_for_ctx`.
+    Apply(UpdateTaskMeta),
+    /// Both sides changed these fields to different values; nothing applied.
+    Conflict { fields: Vec<&'static str>, current: TaskRow },
+}
+
+/// Resolves a base-versioned patch against the server's current row.
+///
+/// A task that does not exist or is not visible to the caller passes through
+/// unchanged so `update_task_meta_for_ctx` produces its usual `404`/`403`.
+pub(super) async fn merge_with_base(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    task_id: &str,
+    mut patch: UpdateTaskMeta,
+    base_updated_ts: i64,
+) -> Result<MergeOutcome, StatusCode> {
+    let Some(current) = get_task_for_ctx(&mut *conn, ctx, task_id).await? else {
+        return Ok(MergeOutcome::Apply(patch));
+    };
+    preserve_omitted_punt_state(&mut patch, &current);
+    if current.updated_ts <= base_updated_ts {
+        return Ok(MergeOutcome::Apply(patch));
+    }
+
+    let changed_since_base: HashSet<String> = sqlx::query_scalar(
+        "select field from task_field_clock where task_id = ?1 and space_id = ?2 and updated_ts > ?3",
+    )
+    .bind(task_id)
+    .bind(&ctx.space_id)
+    .bind(base_updated_ts)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .into_iter()
+    .collect();
+
+    let current_json =
+        serde_json::to_value(&current).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let fields: Vec<&'static str> = patch_fields(&patch)
+        .into_iter()
+        .filter(|(field, value)| {
+            changed_since_base.contains(*field) && current_json.get(*field) != Some(value)
+        })
+        .map(|(field, _)| field)
+        .collect();
+    if fields.is_empty() {
+        return Ok(MergeOutcome::Apply(patch));
+    }
+    Ok(MergeOutcome::Conflict { fields, current })
+}
+
+/// `update_task_meta_for_ctx` writes the punt fields unconditionally, so a
+/// patch that omits them would clear them. Keep the server's punt state
+/// unless the patch moves the due date or recurrence — the same edits that
+/// clear punt state in the web client.
+fn preserve_omitted_punt_state(patch: &mut UpdateTaskMeta, current: &TaskRow) {
+    if patch.due_date.is_some() || patch.recur_rule.is_some() {
+        return;
+    }
+    if patch.punted_from_due_date.is_none() {
+        patch.punted_from_due_date = current.punted_from_due_date.clone();
+    }
+    if patch.punted_on_date.is_none() {
+        patch.punted_on_date = current.punted_on_date.clone();
+    }
+}
+
+/// The fields a patch sets, keyed by `TaskRow` column name, with values in
+/// the row's wire representation (`my_day` as `0`/`1`).
+fn patch_fields(patch: &UpdateTaskMeta) -> Vec<(&'static str, Value)> {
+    let candidates = [
+        ("title", patch.title.as_ref().map(|value| json!(value))),
+        ("status", patch.status.as_ref().map(|value| json!(value))),
+        ("list_id", patch.list_id.as_ref().map(|value| json!(value))),
+        ("my_day", patch.my_day.map(|value| json!(i64::from(value)))),
+        ("priority", patch.priority.map(|value| json!(value))),
+        ("url", patch.url.as_ref().map(|value| json!(value))),
+        ("recur_rule", patch.recur_rule.as_ref().map(|value| json!(value))),
+        ("due_date", patch.due_date.as_ref().map(|value| json!(value))),
+        ("punted_from_due_date", patch.punted_from_due_date.as_ref().map(|value| json!(value))),
+        ("punted_on_date", patch.punted_on_date.as_ref().map(|value| json!(value))),
+        ("notes", patch.notes.as_ref().map(|value| json!(value))),
+        ("occurrences_completed", patch.occurrences_completed.map(|value| json!(value))),
+        ("completed_ts", patch.completed_ts.map(|value| json!(value))),
+        ("assignee_user_id", patch.assignee_user_id.as_ref().map(|value| json!(value))),
+    ];
+    candidates.into_iter().filter_map(|(field, value)| value.map(|value| (field, value))).collect()
+}
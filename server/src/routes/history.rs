@@ -0,0 +1,265 @@
+//! Per-task change history (`task_event`).
+//!
+//! The task write paths (`create_task_for_ctx`, `update_task_meta_for_ctx`,
+//! `update_task_status_for_ctx`, `delete_task_for_ctx`,
+//! `restore_task_for_ctx`) record an event in the same transaction as the
+//! write, so REST, sync pushes and the API-token create are all covered. An
+//! event carries the acting user, the auth scope of the request and a
+//! field-level diff of the row. Creates, completions and deletes also
+//! queue outbound webhooks here (`enqueue_task_webhooks`), in the same
+//! transaction. `GET /tasks/:id/history` returns a task's
+//! events oldest first, scoped like `get_tasks_for_ctx`; a deleted task's
+//! history stays readable through the list it was last in.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::{FromRow, SqliteConnection};
+
+use super::tasks::TaskRow;
+use super::types::{ctx_from_headers, AppState, RequestCtx, Role};
+use super::webhooks::enqueue_task_webhooks;
+
+/// Columns that change on every write or never change; not part of a diff.
+const UNTRACKED_FIELDS: [&str; 4] = ["id", "space_id", "updated_ts", "created_ts"];
+
+#[derive(Clone, Copy)]
+pub(super) enum TaskEventKind {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+impl TaskEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            TaskEventKind::Created => "created",
+            TaskEventKind::Updated => "updated",
+            TaskEventKind::Deleted => "deleted",
+            TaskEventKind::Restored => "restored",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct TaskEventRow {
+    pub(super) id: i64,
+    pub(super) task_id: String,
+    pub(super) kind: String,
+    pub(super) actor_user_id: String,
+    pub(super) scope: String,
+    /// `{ "field": { "from": old, "to": new } }`; empty for deletes and
+    /// restores.
+    pub(super) changes: Value,
+    pub(super) ts: i64,
+}
+
+#[derive(FromRow)]
+struct TaskEventRecord {
+    id: i64,
+    task_id: String,
+    kind: String,
+    actor_user_id: String,
+    scope: String,
+    changes: String,
+    ts: i64,
+}
+
+/// Field-level diff between two versions of a row. `before` is `None` for a
+/// create, so every set field shows up as a change from `null`.
+pub(super) fn task_diff(before: Option<&TaskRow>, after: &TaskRow) -> Map<String, Value> {
+    let to_map = |row: &TaskRow| match serde_json::to_value(row) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    let before = before.map(to_map).unwrap_or_default();
+    let mut changes = Map::new();
+    for (field, to) in to_map(after) {
+        if UNTRACKED_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        let from = before.get(&field).cloned().unwrap_or(Value::Null);
+        if from != to {
+            changes.insert(field, json!({ "from": from, "to": to }));
+        }
+    }
+    changes
+}
+
+/// Records one write. Updates that changed nothing but `updated_ts` are
+/// skipped.
+pub(super) async fn record_task_event(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    kind: TaskEventKind,
+    before: Option<&TaskRow>,
+    after: &TaskRow,
+    ts: i64,
+) -> Result<(), StatusCode> {
+    let changes = match kind {
+        TaskEventKind::Created | TaskEventKind::Updated => task_diff(before, after),
+        TaskEventKind::Deleted | TaskEventKind::Restored => Map::new(),
+    };
+    if matches!(kind, TaskEventKind::Updated) && changes.is_empty() {
+        return Ok(());
+    }
+    let changes = serde_json::to_string(&changes).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query(
+        "insert into task_event (space_id, task_id, list_id, kind, actor_user_id, scope, changes, ts) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )
+    .bind(&ctx.space_id)
+    .bind(&after.id)
+    .bind(&after.list_id)
+    .bind(kind.as_str())
+    .bind(&ctx.user_id)
+    .bind(ctx.scope.as_str())
+    .bind(&changes)
+    .bind(ts)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Some(event) = webhook_event(kind, before, after) {
+        enqueue_task_webhooks(conn, ctx, event, after, ts).await?;
+    }
+    Ok(())
+}
+
+/// The webhook event a write fires, if any. A completion is a task turning
+/// `done`, or a recurring task rolling forward to its next occurrence.
+fn webhook_event(
+    kind: TaskEventKind,
+    before: Option<&TaskRow>,
+    after: &TaskRow,
+) -> Option<&'static str> {
+    match kind {
+        TaskEventKind::Created => Some("task.created"),
+        TaskEventKind::Deleted => Some("task.deleted"),
+        TaskEventKind::Restored => None,
+        TaskEventKind::Updated => {
+            let before = before?;
+            let completed = (after.status == "done" && before.status != "done")
+                || after.occurrences_completed > before.occurrences_completed;
+            completed.then_some("task.completed")
+        }
+    }
+}
+
+pub(super) async fn get_task_history(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TaskEventRow>>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let rows = get_task_history_for_ctx(&state, &ctx, &id).await?;
+    Ok(Json(rows))
+}
+
+/// `404` unless the task is visible to the caller: its current list, or for
+/// a deleted task the list of its latest event, must be granted to a
+/// contributor.
+pub(super) async fn get_task_history_for_ctx(
+    state: &AppState,
+    ctx: &RequestCtx,
+    id: &str,
+) -> Result<Vec<TaskEventRow>, StatusCode> {
+    let list_id: Option<String> = sqlx::query_scalar(
+        "select coalesce((select list_id from task where id = ?1 and space_id = ?2), (select list_id from task_event where task_id = ?1 and space_id = ?2 order by id desc limit 1))",
+    )
+    .bind(id)
+    .bind(&ctx.space_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(list_id) = list_id else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    if ctx.role == Role::Contributor {
+        let granted: Option<i64> = sqlx::query_scalar(
+            "select 1 from list_grant where space_id = ?1 and user_id = ?2 and list_id = ?3 limit 1",
+        )
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .bind(&list_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if granted.is_none() {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+
+    let records = sqlx::query_as::<_, TaskEventRecord>(
+        "select id, task_id, kind, actor_user_id, scope, changes, ts from task_event where space_id = ?1 and task_id = ?2 order by id asc",
+    )
+    .bind(&ctx.space_id)
+    .bind(id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    records
+        .into_iter()
+        .map(|record| {
+            let changes = serde_json::from_str(&record.changes)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            Ok(TaskEventRow {
+                id: record.id,
+                task_id: record.task_id,
+                kind: record.kind,
+                actor_user_id: record.actor_user_id,
+                scope: record.scope,
+                changes,
+                ts: record.ts,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(title: &str, status: &str, updated_ts: i64) -> TaskRow {
+        TaskRow {
+            id: "t1".into(),
+            space_id: "s1".into(),
+            title: title.into(),
+            status: status.into(),
+            list_id: "l1".into(),
+            my_day: 0,
+            priority: 0,
+            order: "a".into(),
+            updated_ts,
+            created_ts: 1,
+            url: None,
+            recur_rule: None,
+            due_date: None,
+            punted_from_due_date: None,
+            punted_on_date: None,
+            occurrences_completed: 0,
+            completed_ts: None,
+            notes: None,
+            assignee_user_id: None,
+            created_by_user_id: None,
+        }
+    }
+
+    #[test]
+    fn diff_lists_only_changed_tracked_fields() {
+        let changes = task_diff(Some(&row("Old", "pending", 1)), &row("New", "pending", 2));
+        assert_eq!(Value::Object(changes), json!({ "title": { "from": "Old", "to": "New" } }));
+    }
+
+    #[test]
+    fn create_diff_starts_from_null_and_skips_unset_fields() {
+        let changes = task_diff(None, &row("Fresh", "pending", 1));
+        assert_eq!(changes.get("title"), Some(&json!({ "from": null, "to": "Fresh" })));
+        assert!(changes.get("notes").is_none());
+        assert!(changes.get("updated_ts").is_none());
+    }
+}
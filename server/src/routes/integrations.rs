@@ -1,27 +1,56 @@
-//! Programmatic task-creation API (F-B): a single `POST /tasks` route
-//! (nested under `/api` in `main.rs`, so the effective path is
-//! `POST /api/tasks`) that lets an external/automated caller create a task
-//! using the static `X-TaskSync-Api-Token` header instead of a session.
+//! Programmatic API (F-B), nested under `/api` in `main.rs`: lets an
+//! external/automated caller work with tasks using an
+//! `X-TaskSync-Api-Token` header instead of a session — a named token
+//! minted at `POST /auth/api-tokens` (see `api_tokens.rs`) or the legacy
+//! static `TASK_API_TOKEN`.
 //!
-//! Deliberately thin: authentication/authorization is entirely delegated to
-//! `ctx_from_api_token`, and the actual insert is entirely delegated to the
-//! SAME `create_task_for_ctx` the browser `POST /tasks` route uses — this
-//! module adds no divergent validation or sync behavior, only the
-//! feature-off gate and the scope check.
+//! | Route                           | Scope            |
+//! |---------------------------------|------------------|
+//! | `GET /api/tasks?…`              | `tasks:read`     |
+//! | `GET /api/tasks/:id`            | `tasks:read`     |
+//! | `POST /api/tasks`               | `tasks:write`    |
+//! | `PATCH /api/tasks/:id`          | `tasks:write`    |
+//! | `POST /api/tasks/:id/complete`  | `tasks:complete` |
+//! | `GET /api/lists`                | `lists:read`     |
+//! | `GET /api/backup`               | `backup:export`  |
+//!
+//! Deliberately thin: authentication, the feature-off gate and the scope
+//! check live in the `ApiCtx` extractor, and every read and write is
+//! delegated to the SAME `*_for_ctx` helpers the browser routes use — this
+//! module adds no divergent validation or sync behavior, only the mapping
+//! to coded `ApiTaskError` bodies.
 
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    async_trait,
+    extract::{rejection::QueryRejection, FromRequestParts, Path, Query, State},
+    http::{request::Parts, StatusCode},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{get, post},
     Json, Router,
 };
 use serde::Serialize;
+use serde_json::json;
+use std::marker::PhantomData;
 
-use super::tasks::{create_task_for_ctx, CreateTask, TaskRow};
-use super::types::{app_state, ctx_from_api_token, AppState, AuthScope};
+use super::api_task_query::{read_api_task_page, ApiTaskPage, ApiTaskQuery};
+use super::api_tokens::api_tokens_enabled;
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::auth::{
+    load_space_backup, BackupListGrantRow, BackupListRow, BackupMembershipRow, BackupSpaceRow,
+    BackupTaskRow, BackupUserRow, SpaceBackupBundle,
+};
+use super::lists::{get_lists_for_ctx, ListRow};
+use super::recurrence::RecurRuleError;
+use super::tasks::{
+    create_task_for_ctx, get_task_for_ctx, update_task_meta_for_ctx, update_task_status_for_ctx,
+    CreateTask, TaskError, TaskRow, UpdateTaskMeta, UpdateTaskStatus,
+};
+use super::types::{
+    app_state, begin_write, ctx_from_api_token, AdminError, ApiScope, AppState, ErrorBody,
+    RequestCtx,
+};
 
-/// Error shape rendered by `create_task_via_api_token`.
+/// Error shape rendered by the `/api` handlers.
 ///
 /// Exactly two shapes, deliberately: `Concealed` (reserved solely for the
 /// feature-off gate) renders byte-for-byte identical to the pre-feature
@@ -34,15 +63,15 @@ use super::types::{app_state, ctx_from_api_token, AppState, AuthScope};
 #[derive(Debug)]
 pub(super) enum ApiTaskError {
     Concealed,
-    Coded { status: StatusCode, code: &'static str },
+    Coded { status: StatusCode, code: &'static str, message: &'static str },
 }
 
 impl ApiTaskError {
     fn coded(status: StatusCode, code: &'static str) -> Self {
-        ApiTaskError::Coded { status, code }
+        ApiTaskError::Coded { status, code, message: message_for_code(code) }
     }
 
-    /// Gate 3 (defense-in-depth scope assertion) constructor.
+    /// Gate 3 (the token lacks the route's `ApiScope`) constructor.
     pub(super) fn forbidden_scope() -> Self {
         ApiTaskError::coded(StatusCode::FORBIDDEN, "forbidden_scope")
     }
@@ -56,9 +85,10 @@ impl ApiTaskError {
         }
     }
 
-    /// Maps `create_task_for_ctx`'s `StatusCode`
-    /// (`404`/`400`/`409`/`403`/`500`) to a coded error.
-    pub(super) fn from_create(status: StatusCode) -> Self {
+    /// Maps a shared helper's `StatusCode` (`404`/`400`/`409`/`403`/`500`)
+    /// to a coded error. A `404` is `unknown_list`, the only thing it can
+    /// mean on the create path; see `from_update` for the other writes.
+    pub(super) fn from_status(status: StatusCode) -> Self {
         match status {
             StatusCode::NOT_FOUND => ApiTaskError::coded(status, "unknown_list"),
             StatusCode::BAD_REQUEST => ApiTaskError::coded(status, "invalid_request"),
@@ -68,6 +98,42 @@ impl ApiTaskError {
         }
     }
 
+    /// Maps a read or write path's `StatusCode` once the task itself is
+    /// known to be visible: a `404` can then only be the `list_id` or
+    /// `assignee_user_id` the body names.
+    pub(super) fn from_update(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => ApiTaskError::coded(status, "unknown_reference"),
+            _ => ApiTaskError::from_status(status),
+        }
+    }
+
+    /// Maps a `create_task_for_ctx` failure to a coded error. An invalid
+    /// `recur_rule` keeps its specific (static) parser message so callers can
+    /// see which RRULE part was rejected.
+    pub(super) fn from_task_error(err: TaskError) -> Self {
+        match err {
+            TaskError::Status(status) => ApiTaskError::from_status(status),
+            TaskError::InvalidRecurRule(err) => ApiTaskError::invalid_recur_rule(err),
+        }
+    }
+
+    /// `update_task_meta_for_ctx`'s counterpart to `from_task_error`.
+    pub(super) fn from_update_error(err: TaskError) -> Self {
+        match err {
+            TaskError::Status(status) => ApiTaskError::from_update(status),
+            TaskError::InvalidRecurRule(err) => ApiTaskError::invalid_recur_rule(err),
+        }
+    }
+
+    fn invalid_recur_rule(err: RecurRuleError) -> Self {
+        ApiTaskError::Coded {
+            status: StatusCode::BAD_REQUEST,
+            code: RecurRuleError::CODE,
+            message: err.message,
+        }
+    }
+
     /// The stable `code` for a `Coded` error, or `None` for `Concealed` (the
     /// feature-off gate, which is not a rejected create and carries no
     /// code). Used only to dispatch logging — `into_response` below reads
@@ -88,34 +154,13 @@ impl IntoResponse for ApiTaskError {
             // logging happens here (see `log_rejection`/`log_create_rejection`
             // below, called from the handler at the point of rejection).
             ApiTaskError::Concealed => StatusCode::NOT_FOUND.into_response(),
-            ApiTaskError::Coded { status, code } => {
-                let message = message_for_code(code);
+            ApiTaskError::Coded { status, code, message } => {
                 (status, Json(ErrorBody::new(code, message))).into_response()
             }
         }
     }
 }
 
-/// `{ "error": { "code": "...", "message": "..." } }` — namespaced under
-/// `error` so it never collides with the success `TaskRow` shape, which has
-/// no `error` field.
-#[derive(Serialize)]
-struct ErrorBody {
-    error: ErrorDetail,
-}
-
-#[derive(Serialize)]
-struct ErrorDetail {
-    code: &'static str,
-    message: &'static str,
-}
-
-impl ErrorBody {
-    fn new(code: &'static str, message: &'static str) -> Self {
-        ErrorBody { error: ErrorDetail { code, message } }
-    }
-}
-
 /// Static, human-readable hint per `code`. Deliberately static: no request
 /// data and no internal/DB text ever flows into a response body.
 fn message_for_code(code: &'static str) -> &'static str {
@@ -123,9 +168,16 @@ fn message_for_code(code: &'static str) -> &'static str {
         "unauthorized" => "missing or invalid API token",
         "forbidden_scope" => "token does not have permission to perform this action",
         "unknown_list" => "list_id does not exist in this space",
+        "unknown_task" => "task does not exist in this space",
+        "unknown_reference" => "list_id or assignee_user_id does not exist in this space",
         "invalid_request" => "request body failed validation",
+        "invalid_query" => "query parameters failed validation",
         "conflict" => "a task with this id already exists and could not be reconciled",
-        "forbidden" => "not permitted to create tasks in this list",
+        "invalid_recur_rule" => "recur_rule is not a supported keyword or RRULE",
+        "forbidden" => "the token's member is not permitted to do this",
+        "two_factor_enrollment_required" => {
+            "the token's member must enroll in two-factor authentication first"
+        }
         "internal_error" => "an internal error occurred",
         _ => "request failed",
     }
@@ -142,9 +194,14 @@ pub(super) fn reject_log_message(code: &'static str) -> &'static str {
         "unauthorized" => "rejected: unauthorized (missing/invalid token or owner unresolved)",
         "forbidden_scope" => "rejected: scope mismatch",
         "unknown_list" => "rejected: unknown list_id",
+        "unknown_task" => "rejected: unknown task",
+        "unknown_reference" => "rejected: unknown list_id or assignee_user_id",
         "invalid_request" => "rejected: invalid request body",
+        "invalid_query" => "rejected: invalid query parameters",
         "conflict" => "rejected: idempotent-create conflict",
+        "invalid_recur_rule" => "rejected: invalid recur_rule",
         "forbidden" => "rejected: forbidden",
+        "two_factor_enrollment_required" => "rejected: admin has not enrolled in 2FA",
         "internal_error" => "failed: internal error",
         _ => "rejected: request failed",
     }
@@ -190,57 +247,356 @@ fn log_create_rejection(err: &ApiTaskError, list_id: &str) {
     }
 }
 
-/// Ingest handler for the programmatic task-creation API.
+/// Logs `err` at the point of rejection and hands it back, so call sites
+/// stay a single `map_err`.
+fn rejected(err: ApiTaskError) -> ApiTaskError {
+    log_rejection(&err);
+    err
+}
+
+/// The `RequestCtx` of an `/api` request whose token holds `S::SCOPE`.
+/// Every `/api` handler takes one, so a route cannot skip the checks.
 ///
 /// Order matters:
-/// 1. Feature-off gate — `state.api_token.is_none()` means `TASK_API_TOKEN`
-///    was never configured, so the endpoint does not exist at all (`404`),
-///    checked BEFORE calling the verifier so an unset token never reaches
-///    `ctx_from_api_token` (which would otherwise return `401`).
-/// 2. `ctx_from_api_token` authenticates the header against the configured
-///    token and resolves the owner identity server-side.
-/// 3. A defense-in-depth scope assertion — this is the read that makes
-///    `AuthScope::ApiTaskCreate` and `RequestCtx.scope` genuinely used.
-/// 4. The shared `create_task_for_ctx` path — identical validation,
-///    idempotency, and tombstone-clear behavior to the browser create-task
-///    route and the sync path.
-///
-/// Gate ordering is preserved verbatim from the pre-`ApiTaskError` version —
-/// only the `Err(...)` payloads changed, not which requests are accepted or
-/// rejected.
+/// 1. Feature-off gate — with no `TASK_API_TOKEN` configured and no
+///    unrevoked named token (`api_tokens_enabled`), the API does not exist
+///    at all (`404`), checked BEFORE calling the verifier so a deployment
+///    without tokens never reaches `ctx_from_api_token` (which would
+///    otherwise return `401`).
+/// 2. `ctx_from_api_token` authenticates the header against the named
+///    tokens and the configured token and resolves the identity
+///    server-side.
+/// 3. The token must hold the route's scope (`forbidden_scope` otherwise).
+///    Scopes only narrow what the token's member could do with a session;
+///    the shared helpers still apply the member's role and grants.
+pub(super) struct ApiCtx<S> {
+    pub(super) ctx: RequestCtx,
+    scope: PhantomData<S>,
+}
+
+/// The `ApiScope` an `ApiCtx` requires; implemented by one marker type per
+/// scope.
+pub(super) trait RequiredScope {
+    const SCOPE: ApiScope;
+}
+
+pub(super) struct TasksReadScope;
+pub(super) struct TasksWriteScope;
+pub(super) struct TasksCompleteScope;
+pub(super) struct ListsReadScope;
+pub(super) struct BackupExportScope;
+
+impl RequiredScope for TasksReadScope {
+    const SCOPE: ApiScope = ApiScope::TasksRead;
+}
+
+impl RequiredScope for TasksWriteScope {
+    const SCOPE: ApiScope = ApiScope::TasksWrite;
+}
+
+impl RequiredScope for TasksCompleteScope {
+    const SCOPE: ApiScope = ApiScope::TasksComplete;
+}
+
+impl RequiredScope for ListsReadScope {
+    const SCOPE: ApiScope = ApiScope::ListsRead;
+}
+
+impl RequiredScope for BackupExportScope {
+    const SCOPE: ApiScope = ApiScope::BackupExport;
+}
+
+#[async_trait]
+impl<S: RequiredScope> FromRequestParts<AppState> for ApiCtx<S> {
+    type Rejection = ApiTaskError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let enabled = api_tokens_enabled(state)
+            .await
+            .map_err(|status| rejected(ApiTaskError::from_auth(status)))?;
+        if !enabled {
+            // Feature-off gate: pure early return, no log line (see
+            // `log_rejection`'s docs — this is not a "rejected create").
+            return Err(ApiTaskError::Concealed);
+        }
+        let ctx = ctx_from_api_token(&parts.headers, state)
+            .await
+            .map_err(|status| rejected(ApiTaskError::from_auth(status)))?;
+        if !ctx.scope.allows(S::SCOPE) {
+            return Err(rejected(ApiTaskError::forbidden_scope()));
+        }
+        Ok(ApiCtx { ctx, scope: PhantomData })
+    }
+}
+
+/// `tasks:write`. Ingest handler for the programmatic API: after `ApiCtx`,
+/// the shared `create_task_for_ctx` path — identical validation,
+/// idempotency, and tombstone-clear behavior to the browser create-task
+/// route and the sync path.
 pub(super) async fn create_task_via_api_token(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    ApiCtx { ctx, .. }: ApiCtx<TasksWriteScope>,
     Json(body): Json<CreateTask>,
 ) -> Result<(StatusCode, Json<TaskRow>), ApiTaskError> {
-    if state.api_token.is_none() {
-        // Feature-off gate: pure early return, no log line (see
-        // `log_rejection`'s docs — this is not a "rejected create").
-        return Err(ApiTaskError::Concealed);
-    }
-    let ctx = ctx_from_api_token(&headers, &state).await.map_err(|status| {
-        let err = ApiTaskError::from_auth(status);
-        log_rejection(&err);
-        err
-    })?;
-    if ctx.scope != AuthScope::ApiTaskCreate {
-        let err = ApiTaskError::forbidden_scope();
-        log_rejection(&err);
-        return Err(err);
-    }
     // Cloned before `body` moves into `create_task_for_ctx`, purely so the
     // `unknown_list` rejection can name it in the log line. Does not alter
-    // the create path or `create_task_for_ctx`'s signature/arguments.
+    // the create path or `create_task_for_ctx`'s arguments.
     let requested_list_id = body.list_id.clone();
-    let (status, rec) = create_task_for_ctx(&state, &ctx, body).await.map_err(|status| {
-        let err = ApiTaskError::from_create(status);
+    let mut tx = begin_write(&state.pool)
+        .await
+        .map_err(|status| rejected(ApiTaskError::from_status(status)))?;
+    let (status, rec) = create_task_for_ctx(&mut tx, &ctx, body).await.map_err(|err| {
+        let err = ApiTaskError::from_task_error(err);
         log_create_rejection(&err, &requested_list_id);
         err
     })?;
+    tx.commit()
+        .await
+        .map_err(|_| rejected(ApiTaskError::from_status(StatusCode::INTERNAL_SERVER_ERROR)))?;
     Ok((status, Json(rec)))
 }
 
+/// `tasks:read`. One page of the tasks the token's member can see, in
+/// board order, narrowed by the filters in `ApiTaskQuery`. A malformed
+/// query is `invalid_query`, reported only after the token checks out.
+pub(super) async fn get_tasks_via_api_token(
+    State(state): State<AppState>,
+    ApiCtx { ctx, .. }: ApiCtx<TasksReadScope>,
+    query: Result<Query<ApiTaskQuery>, QueryRejection>,
+) -> Result<Json<ApiTaskPage>, ApiTaskError> {
+    let invalid_query = || rejected(ApiTaskError::coded(StatusCode::BAD_REQUEST, "invalid_query"));
+    let Ok(Query(query)) = query else {
+        return Err(invalid_query());
+    };
+    let mut conn = state
+        .pool
+        .acquire()
+        .await
+        .map_err(|_| rejected(ApiTaskError::from_status(StatusCode::INTERNAL_SERVER_ERROR)))?;
+    let page =
+        read_api_task_page(&mut conn, &ctx, &query).await.map_err(|status| match status {
+            StatusCode::BAD_REQUEST => invalid_query(),
+            status => rejected(ApiTaskError::from_status(status)),
+        })?;
+    Ok(Json(page))
+}
+
+/// `tasks:read`. `unknown_task` when the task does not exist or is not
+/// visible to the token's member.
+pub(super) async fn get_task_via_api_token(
+    State(state): State<AppState>,
+    ApiCtx { ctx, .. }: ApiCtx<TasksReadScope>,
+    Path(id): Path<String>,
+) -> Result<Json<TaskRow>, ApiTaskError> {
+    let mut conn = state
+        .pool
+        .acquire()
+        .await
+        .map_err(|_| rejected(ApiTaskError::from_status(StatusCode::INTERNAL_SERVER_ERROR)))?;
+    let task = find_task(&mut conn, &ctx, &id).await?;
+    Ok(Json(task))
+}
+
+/// The task, or a logged `unknown_task` rejection. Write handlers call it
+/// first so a `404` from the shared write path can only mean a missing
+/// `list_id`/`assignee_user_id`.
+async fn find_task(
+    conn: &mut sqlx::SqliteConnection,
+    ctx: &RequestCtx,
+    id: &str,
+) -> Result<TaskRow, ApiTaskError> {
+    get_task_for_ctx(conn, ctx, id)
+        .await
+        .map_err(|status| rejected(ApiTaskError::from_status(status)))?
+        .ok_or_else(|| rejected(ApiTaskError::coded(StatusCode::NOT_FOUND, "unknown_task")))
+}
+
+/// `tasks:write`. Partial update through the shared
+/// `update_task_meta_for_ctx`, so the member's contributor limits apply.
+pub(super) async fn update_task_via_api_token(
+    State(state): State<AppState>,
+    ApiCtx { ctx, .. }: ApiCtx<TasksWriteScope>,
+    Path(id): Path<String>,
+    Json(body): Json<UpdateTaskMeta>,
+) -> Result<Json<TaskRow>, ApiTaskError> {
+    let mut tx = begin_write(&state.pool)
+        .await
+        .map_err(|status| rejected(ApiTaskError::from_status(status)))?;
+    find_task(&mut tx, &ctx, &id).await?;
+    let rec = update_task_meta_for_ctx(&mut tx, &ctx, id, body)
+        .await
+        .map_err(|err| rejected(ApiTaskError::from_update_error(err)))?;
+    tx.commit()
+        .await
+        .map_err(|_| rejected(ApiTaskError::from_status(StatusCode::INTERNAL_SERVER_ERROR)))?;
+    Ok(Json(rec))
+}
+
+/// `tasks:complete`. Marks the task done exactly like ticking it off in
+/// the app, so a recurring task rolls forward to its next occurrence.
+pub(super) async fn complete_task_via_api_token(
+    State(state): State<AppState>,
+    ApiCtx { ctx, .. }: ApiCtx<TasksCompleteScope>,
+    Path(id): Path<String>,
+) -> Result<Json<TaskRow>, ApiTaskError> {
+    let mut tx = begin_write(&state.pool)
+        .await
+        .map_err(|status| rejected(ApiTaskError::from_status(status)))?;
+    find_task(&mut tx, &ctx, &id).await?;
+    let body = UpdateTaskStatus { status: "done".to_string() };
+    let rec = update_task_status_for_ctx(&mut tx, &ctx, id, body)
+        .await
+        .map_err(|status| rejected(ApiTaskError::from_update(status)))?;
+    tx.commit()
+        .await
+        .map_err(|_| rejected(ApiTaskError::from_status(StatusCode::INTERNAL_SERVER_ERROR)))?;
+    Ok(Json(rec))
+}
+
+/// `lists:read`. The lists the token's member can see, in sidebar order.
+pub(super) async fn get_lists_via_api_token(
+    State(state): State<AppState>,
+    ApiCtx { ctx, .. }: ApiCtx<ListsReadScope>,
+) -> Result<Json<Vec<ListRow>>, ApiTaskError> {
+    let lists = get_lists_for_ctx(&state, &ctx)
+        .await
+        .map_err(|status| rejected(ApiTaskError::from_status(status)))?;
+    Ok(Json(lists))
+}
+
+/// `GET /auth/backup`'s bundle as an API token may read it: without
+/// password hashes, so a leaked token does not leak every member's
+/// credentials. Not accepted by `POST /auth/backup/restore`.
+#[derive(Serialize)]
+pub(super) struct ApiBackupBundle {
+    pub(super) schema: String,
+    pub(super) exported_at_ts: i64,
+    pub(super) space: BackupSpaceRow,
+    pub(super) users: Vec<ApiBackupUserRow>,
+    pub(super) memberships: Vec<BackupMembershipRow>,
+    pub(super) lists: Vec<BackupListRow>,
+    pub(super) list_grants: Vec<BackupListGrantRow>,
+    pub(super) tasks: Vec<BackupTaskRow>,
+}
+
+/// `BackupUserRow` without `password_hash`.
+#[derive(Serialize)]
+pub(super) struct ApiBackupUserRow {
+    pub(super) id: String,
+    pub(super) email: String,
+    pub(super) display: String,
+    pub(super) avatar_icon: Option<String>,
+    pub(super) sound_enabled: bool,
+    pub(super) sound_volume: i64,
+    pub(super) sound_theme: String,
+    pub(super) custom_sound_file_id: Option<String>,
+    pub(super) custom_sound_file_name: Option<String>,
+    pub(super) custom_sound_data_url: Option<String>,
+    pub(super) custom_sound_files_json: Option<String>,
+    pub(super) profile_attachments: Option<String>,
+    pub(super) ui_theme: Option<String>,
+    pub(super) ui_sidebar_panels: Option<String>,
+    pub(super) ui_list_sort: Option<String>,
+    pub(super) ui_font: Option<String>,
+    pub(super) ui_completion_quotes: Option<String>,
+    pub(super) streak_settings_json: Option<String>,
+    pub(super) streak_state_json: Option<String>,
+}
+
+impl From<BackupUserRow> for ApiBackupUserRow {
+    fn from(user: BackupUserRow) -> Self {
+        ApiBackupUserRow {
+            id: user.id,
+            email: user.email,
+            display: user.display,
+            avatar_icon: user.avatar_icon,
+            sound_enabled: user.sound_enabled,
+            sound_volume: user.sound_volume,
+            sound_theme: user.sound_theme,
+            custom_sound_file_id: user.custom_sound_file_id,
+            custom_sound_file_name: user.custom_sound_file_name,
+            custom_sound_data_url: user.custom_sound_data_url,
+            custom_sound_files_json: user.custom_sound_files_json,
+            profile_attachments: user.profile_attachments,
+            ui_theme: user.ui_theme,
+            ui_sidebar_panels: user.ui_sidebar_panels,
+            ui_list_sort: user.ui_list_sort,
+            ui_font: user.ui_font,
+            ui_completion_quotes: user.ui_completion_quotes,
+            streak_settings_json: user.streak_settings_json,
+            streak_state_json: user.streak_state_json,
+        }
+    }
+}
+
+impl From<SpaceBackupBundle> for ApiBackupBundle {
+    fn from(backup: SpaceBackupBundle) -> Self {
+        ApiBackupBundle {
+            schema: backup.schema,
+            exported_at_ts: backup.exported_at_ts,
+            space: backup.space,
+            users: backup.users.into_iter().map(ApiBackupUserRow::from).collect(),
+            memberships: backup.memberships,
+            lists: backup.lists,
+            list_grants: backup.list_grants,
+            tasks: backup.tasks,
+        }
+    }
+}
+
+/// `backup:export`. The `GET /auth/backup` bundle without password hashes
+/// (`ApiBackupBundle`); it also needs the token's member to be an admin
+/// (`forbidden` otherwise). Audited as a `backup_exported` with
+/// `"via": "api_token"`.
+pub(super) async fn export_backup_via_api_token(
+    State(state): State<AppState>,
+    ApiCtx { ctx, .. }: ApiCtx<BackupExportScope>,
+) -> Result<Json<ApiBackupBundle>, ApiTaskError> {
+    if let Err(err) = ctx.require_admin() {
+        let code = match err {
+            AdminError::TwoFactorEnrollmentRequired => "two_factor_enrollment_required",
+            AdminError::Status(_) => "forbidden",
+        };
+        return Err(rejected(ApiTaskError::coded(StatusCode::FORBIDDEN, code)));
+    }
+    let mut tx = begin_write(&state.pool)
+        .await
+        .map_err(|status| rejected(ApiTaskError::from_status(status)))?;
+    let backup = load_space_backup(&mut tx, &ctx.space_id)
+        .await
+        .map_err(|status| rejected(ApiTaskError::from_status(status)))?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::BackupExported,
+            target_user_id: None,
+            detail: json!({
+                "tasks": backup.tasks.len(),
+                "users": backup.users.len(),
+                "via": "api_token",
+            }),
+        },
+    )
+    .await
+    .map_err(|status| rejected(ApiTaskError::from_status(status)))?;
+    tx.commit()
+        .await
+        .map_err(|_| rejected(ApiTaskError::from_status(StatusCode::INTERNAL_SERVER_ERROR)))?;
+    Ok(Json(backup.into()))
+}
+
 pub fn integration_routes(pool: &sqlx::SqlitePool) -> Router {
     let state = app_state(pool);
-    Router::new().route("/tasks", post(create_task_via_api_token)).with_state(state)
+    Router::new()
+        .route("/tasks", get(get_tasks_via_api_token).post(create_task_via_api_token))
+        .route("/tasks/:id", get(get_task_via_api_token).patch(update_task_via_api_token))
+        .route("/tasks/:id/complete", post(complete_task_via_api_token))
+        .route("/lists", get(get_lists_via_api_token))
+        .route("/backup", get(export_backup_via_api_token))
+        .with_state(state)
 }
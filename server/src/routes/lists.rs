@@ -5,12 +5,15 @@ use axum::{
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, SqliteConnection};
 use uuid::Uuid;
 
-use super::types::{app_state, ctx_from_headers, AppState, RequestCtx, Role};
+use super::types::{
+    app_state, begin_write, ctx_from_headers, is_unique_violation, AdminError, AppState,
+    RequestCtx, Role,
+};
 
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow)]
 pub(super) struct ListRow {
     pub(super) id: String,
     pub(super) space_id: String,
@@ -18,17 +21,28 @@ pub(super) struct ListRow {
     pub(super) icon: Option<String>,
     pub(super) color: Option<String>,
     pub(super) order: String,
+    pub(super) updated_ts: i64,
+}
+
+#[derive(Serialize, FromRow)]
+pub(super) struct DeletedListRow {
+    pub(super) id: String,
+    pub(super) deleted_ts: i64,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub(super) struct CreateList {
+    /// Client-generated id, so a list created offline keeps its id (and its
+    /// tasks' `list_id`s) when the queued create is pushed. Replaying a create
+    /// for an existing list returns it with `200`.
+    pub(super) id: Option<String>,
     pub(super) name: String,
     pub(super) icon: Option<String>,
     pub(super) color: Option<String>,
     pub(super) order: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub(super) struct UpdateList {
     pub(super) name: Option<String>,
     pub(super) icon: Option<String>,
@@ -51,7 +65,7 @@ pub(super) async fn get_lists_for_ctx(
 ) -> Result<Vec<ListRow>, StatusCode> {
     let lists = if ctx.role == Role::Admin {
         sqlx::query_as::<_, ListRow>(
-            "select id, space_id, name, icon, color, list_order as \"order\" from list where space_id = ?1 order by list_order asc",
+            "select id, space_id, name, icon, color, list_order as \"order\", updated_ts from list where space_id = ?1 order by list_order asc",
         )
         .bind(&ctx.space_id)
         .fetch_all(&state.pool)
@@ -59,7 +73,7 @@ pub(super) async fn get_lists_for_ctx(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     } else {
         sqlx::query_as::<_, ListRow>(
-            "select l.id, l.space_id, l.name, l.icon, l.color, l.list_order as \"order\" from list l join list_grant g on g.list_id = l.id and g.space_id = l.space_id where l.space_id = ?1 and g.user_id = ?2 order by l.list_order asc",
+            "select l.id, l.space_id, l.name, l.icon, l.color, l.list_order as \"order\", l.updated_ts from list l join list_grant g on g.list_id = l.id and g.space_id = l.space_id where l.space_id = ?1 and g.user_id = ?2 order by l.list_order asc",
         )
         .bind(&ctx.space_id)
         .bind(&ctx.user_id)
@@ -70,19 +84,68 @@ pub(super) async fn get_lists_for_ctx(
     Ok(lists)
 }
 
+/// Lists whose `change_seq` is in `(since_seq, cursor_seq]`, for the
+/// `delta-v2` pull. Scoped like `get_lists_for_ctx`.
+pub(super) async fn get_lists_changed_for_ctx(
+    state: &AppState,
+    ctx: &RequestCtx,
+    since_seq: i64,
+    cursor_seq: i64,
+) -> Result<Vec<ListRow>, StatusCode> {
+    if ctx.role == Role::Admin {
+        return sqlx::query_as::<_, ListRow>(
+            "select id, space_id, name, icon, color, list_order as \"order\", updated_ts from list where space_id = ?1 and change_seq > ?2 and change_seq <= ?3 order by list_order asc",
+        )
+        .bind(&ctx.space_id)
+        .bind(since_seq)
+        .bind(cursor_seq)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    sqlx::query_as::<_, ListRow>(
+        "select l.id, l.space_id, l.name, l.icon, l.color, l.list_order as \"order\", l.updated_ts from list l join list_grant g on g.list_id = l.id and g.space_id = l.space_id where l.space_id = ?1 and g.user_id = ?2 and l.change_seq > ?3 and l.change_seq <= ?4 order by l.list_order asc",
+    )
+    .bind(&ctx.space_id)
+    .bind(&ctx.user_id)
+    .bind(since_seq)
+    .bind(cursor_seq)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 pub(super) async fn create_list(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<CreateList>,
-) -> Result<(StatusCode, Json<ListRow>), StatusCode> {
+) -> Result<(StatusCode, Json<ListRow>), AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
-    let id = Uuid::new_v4().to_string();
+    ctx.require_admin()?;
+    let mut tx = begin_write(&state.pool).await?;
+    let (status, rec) = create_list_for_ctx(&mut tx, &ctx, body).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((status, Json(rec)))
+}
+
+pub(super) async fn create_list_for_ctx(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    body: CreateList,
+) -> Result<(StatusCode, ListRow), StatusCode> {
+    ctx.require_admin().map_err(|err| err.status())?;
+    let id = body
+        .id
+        .as_ref()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
     let order = body.order.unwrap_or_else(|| "z".into());
-    let rec = sqlx::query_as::<_, ListRow>(
-		"insert into list (id, space_id, name, icon, color, list_order) values (?1, ?2, ?3, ?4, ?5, ?6) returning id, space_id, name, icon, color, list_order as \"order\"",
+    let now = chrono::Utc::now().timestamp_millis();
+    let insert_result = sqlx::query_as::<_, ListRow>(
+		"insert into list (id, space_id, name, icon, color, list_order, updated_ts) values (?1, ?2, ?3, ?4, ?5, ?6, ?7) returning id, space_id, name, icon, color, list_order as \"order\", updated_ts",
 	)
 	.bind(&id)
 	.bind(&ctx.space_id)
@@ -90,11 +153,36 @@ pub(super) async fn create_list(
 	.bind(&body.icon)
 	.bind(&body.color)
 	.bind(&order)
-	.fetch_one(&state.pool)
-	.await
-	.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+	.bind(now)
+	.fetch_one(&mut *conn)
+	.await;
+    let (status, rec) = match insert_result {
+        Ok(inserted) => (StatusCode::CREATED, inserted),
+        Err(err) => {
+            if !is_unique_violation(&err) {
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+            let existing = sqlx::query_as::<_, ListRow>(
+                "select id, space_id, name, icon, color, list_order as \"order\", updated_ts from list where id = ?1 and space_id = ?2 limit 1",
+            )
+            .bind(&id)
+            .bind(&ctx.space_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::CONFLICT)?;
+            (StatusCode::OK, existing)
+        }
+    };
 
-    Ok((StatusCode::CREATED, Json(rec)))
+    sqlx::query("delete from list_tombstone where list_id = ?1 and space_id = ?2")
+        .bind(&rec.id)
+        .bind(&ctx.space_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((status, rec))
 }
 
 pub(super) async fn update_list(
@@ -102,57 +190,116 @@ pub(super) async fn update_list(
     headers: HeaderMap,
     Path(id): Path<String>,
     Json(body): Json<UpdateList>,
-) -> Result<Json<ListRow>, StatusCode> {
+) -> Result<Json<ListRow>, AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
-    let rec = sqlx::query_as::<_, ListRow>(
-		"update list set name = coalesce(?1, name), icon = coalesce(?2, icon), color = coalesce(?3, color), list_order = coalesce(?4, list_order) where id = ?5 and space_id = ?6 returning id, space_id, name, icon, color, list_order as \"order\"",
+    ctx.require_admin()?;
+    let mut tx = begin_write(&state.pool).await?;
+    let rec = update_list_for_ctx(&mut tx, &ctx, id, body).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(rec))
+}
+
+pub(super) async fn update_list_for_ctx(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    id: String,
+    body: UpdateList,
+) -> Result<ListRow, StatusCode> {
+    ctx.require_admin().map_err(|err| err.status())?;
+    let now = chrono::Utc::now().timestamp_millis();
+    sqlx::query_as::<_, ListRow>(
+		"update list set name = coalesce(?1, name), icon = coalesce(?2, icon), color = coalesce(?3, color), list_order = coalesce(?4, list_order), updated_ts = ?5 where id = ?6 and space_id = ?7 returning id, space_id, name, icon, color, list_order as \"order\", updated_ts",
 	)
 	.bind(&body.name)
 	.bind(&body.icon)
 	.bind(&body.color)
 	.bind(&body.order)
+	.bind(now)
 	.bind(&id)
 	.bind(&ctx.space_id)
-	.fetch_one(&state.pool)
+	.fetch_one(&mut *conn)
 	.await
-	.map_err(|_| StatusCode::NOT_FOUND)?;
-
-    Ok(Json(rec))
+	.map_err(|_| StatusCode::NOT_FOUND)
 }
 
 pub(super) async fn delete_list(
     State(state): State<AppState>,
     headers: HeaderMap,
     Path(id): Path<String>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AdminError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    if ctx.role != Role::Admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    ctx.require_admin()?;
+    let mut tx = begin_write(&state.pool).await?;
+    let status = delete_list_for_ctx(&mut tx, &ctx, id).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(status)
+}
+
+/// Deletes an empty list and records a `list_tombstone`; run inside the
+/// caller's write transaction so both land together. List grants cascade
+/// away with the list, so they are copied to `list_tombstone_grant` first and
+/// contributors only receive tombstones for lists they could read.
+pub(super) async fn delete_list_for_ctx(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    id: String,
+) -> Result<StatusCode, StatusCode> {
+    ctx.require_admin().map_err(|err| err.status())?;
 
     let task_count: i64 =
         sqlx::query_scalar("select count(1) from task where list_id = ?1 and space_id = ?2")
             .bind(&id)
             .bind(&ctx.space_id)
-            .fetch_one(&state.pool)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     if task_count > 0 {
         return Err(StatusCode::CONFLICT);
     }
 
+    let granted: Vec<String> =
+        sqlx::query_scalar("select user_id from list_grant where list_id = ?1 and space_id = ?2")
+            .bind(&id)
+            .bind(&ctx.space_id)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let now = chrono::Utc::now().timestamp_millis();
     let rows = sqlx::query("delete from list where id = ?1 and space_id = ?2")
         .bind(&id)
         .bind(&ctx.space_id)
-        .execute(&state.pool)
+        .execute(&mut *conn)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     if rows.rows_affected() == 0 {
         return Err(StatusCode::NOT_FOUND);
     }
+    sqlx::query(
+        "insert into list_tombstone (list_id, space_id, deleted_ts) values (?1, ?2, ?3) on conflict(list_id, space_id) do update set deleted_ts = excluded.deleted_ts",
+    )
+    .bind(&id)
+    .bind(&ctx.space_id)
+    .bind(now)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query("delete from list_tombstone_grant where list_id = ?1 and space_id = ?2")
+        .bind(&id)
+        .bind(&ctx.space_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    for user_id in granted {
+        sqlx::query(
+            "insert into list_tombstone_grant (list_id, space_id, user_id) values (?1, ?2, ?3)",
+        )
+        .bind(&id)
+        .bind(&ctx.space_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
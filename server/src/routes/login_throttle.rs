@@ -0,0 +1,444 @@
+//! Brute-force protection for the public auth endpoints (tech-debt #048).
+//!
+//! `POST /auth/login` counts failures per account (the submitted email,
+//! known or not) and per client IP; `POST /auth/setup` counts them per IP.
+//! Once a key reaches its failure limit it is locked out for
+//! `LOGIN_LOCKOUT_BASE_SECS`, doubling with every further failure up to
+//! `LOGIN_LOCKOUT_MAX_SECS`. While locked, the endpoint answers `429` with
+//! `Retry-After` before looking at the password, so a correct guess is
+//! indistinguishable from a wrong one. The account lockout refuses every
+//! client, whatever its address. A member locked out by someone else's
+//! guessing can still sign in with a passkey (which sends no email) or
+//! single sign-on, or use a reset token. Counters live in `login_attempt`
+//! and survive restarts. A successful login clears its account counter; an
+//! admin can clear a member's lockout with
+//! `DELETE /auth/members/:user_id/lockout`.
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Path, State},
+    http::{header::RETRY_AFTER, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+};
+
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::types::{begin_write, ctx_from_headers, AdminError, AppState, ErrorBody};
+
+/// Limits read from the environment by `app_state`; validated at boot by
+/// `validate_boot_secrets`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct LoginThrottleConfig {
+    /// `LOGIN_MAX_FAILURES_PER_ACCOUNT` (default 5).
+    pub(super) account_max_failures: i64,
+    /// `LOGIN_MAX_FAILURES_PER_IP` (default 20).
+    pub(super) ip_max_failures: i64,
+    /// `LOGIN_LOCKOUT_BASE_SECS` (default 30).
+    pub(super) lockout_base_secs: i64,
+    /// `LOGIN_LOCKOUT_MAX_SECS` (default 900).
+    pub(super) lockout_max_secs: i64,
+    /// `TRUST_FORWARDED_FOR` (default false): take the client IP from the
+    /// last `X-Forwarded-For` hop. Only safe behind a reverse proxy that
+    /// appends it; otherwise every client could pick its own address.
+    pub(super) trust_forwarded_for: bool,
+}
+
+impl Default for LoginThrottleConfig {
+    fn default() -> Self {
+        LoginThrottleConfig {
+            account_max_failures: 5,
+            ip_max_failures: 20,
+            lockout_base_secs: 30,
+            lockout_max_secs: 900,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+/// Parses the throttle variables via `var`. Unset/blank values take the
+/// default; anything else that is not a positive whole number (or
+/// `true`/`false` for `TRUST_FORWARDED_FOR`) is a boot failure naming the
+/// variable, and so is a base lockout longer than the maximum.
+pub(super) fn parse_login_throttle(
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<LoginThrottleConfig, String> {
+    let defaults = LoginThrottleConfig::default();
+    let mut failures = Vec::new();
+    let mut positive = |name: &str, default: i64| {
+        let Some(value) = var(name).filter(|value| !value.trim().is_empty()) else {
+            return default;
+        };
+        match value.trim().parse::<i64>() {
+            Ok(parsed) if parsed > 0 => parsed,
+            _ => {
+                failures.push(format!(
+                    "invalid {name} \"{value}\": expected a positive whole number, e.g. {default}"
+                ));
+                default
+            }
+        }
+    };
+    let mut config = LoginThrottleConfig {
+        account_max_failures: positive(
+            "LOGIN_MAX_FAILURES_PER_ACCOUNT",
+            defaults.account_max_failures,
+        ),
+        ip_max_failures: positive("LOGIN_MAX_FAILURES_PER_IP", defaults.ip_max_failures),
+        lockout_base_secs: positive("LOGIN_LOCKOUT_BASE_SECS", defaults.lockout_base_secs),
+        lockout_max_secs: positive("LOGIN_LOCKOUT_MAX_SECS", defaults.lockout_max_secs),
+        ..defaults
+    };
+    match var("TRUST_FORWARDED_FOR").map(|value| value.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("false") => {}
+        Some("true") => config.trust_forwarded_for = true,
+        Some(other) => failures
+            .push(format!("invalid TRUST_FORWARDED_FOR \"{other}\": expected true or false")),
+    }
+    if config.lockout_base_secs > config.lockout_max_secs {
+        failures.push(format!(
+            "LOGIN_LOCKOUT_BASE_SECS ({}) must not exceed LOGIN_LOCKOUT_MAX_SECS ({})",
+            config.lockout_base_secs, config.lockout_max_secs
+        ));
+    }
+    if failures.is_empty() {
+        Ok(config)
+    } else {
+        Err(failures.join("\n"))
+    }
+}
+
+/// Failure from the throttled auth endpoints: a bare status, or `429` with
+/// `Retry-After`.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum LoginError {
+    Status(StatusCode),
+    Throttled {
+        retry_after_secs: i64,
+    },
+    /// The password matched but the account has 2FA enabled and no
+    /// `two_factor_code` was sent: `401` with code `two_factor_required`.
+    TwoFactorRequired,
+}
+
+impl From<StatusCode> for LoginError {
+    fn from(status: StatusCode) -> Self {
+        LoginError::Status(status)
+    }
+}
+
+impl IntoResponse for LoginError {
+    fn into_response(self) -> Response {
+        match self {
+            LoginError::Status(status) => status.into_response(),
+            LoginError::Throttled { retry_after_secs } => {
+                (StatusCode::TOO_MANY_REQUESTS, [(RETRY_AFTER, retry_after_secs.to_string())])
+                    .into_response()
+            }
+            LoginError::TwoFactorRequired => (
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorBody::new(
+                    "two_factor_required",
+                    "two_factor_code is required for this account",
+                )),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// The caller's address: the TCP peer, or the last `X-Forwarded-For` hop
+/// when `TRUST_FORWARDED_FOR` is set. `None` when neither is available (no
+/// `ConnectInfo`, as in handler tests), which skips the per-IP counter.
+pub(super) struct ClientIp(pub(super) Option<IpAddr>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if state.login_throttle.trust_forwarded_for {
+            let forwarded = parts
+                .headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+                .and_then(|hop| hop.trim().parse::<IpAddr>().ok());
+            if forwarded.is_some() {
+                return Ok(ClientIp(forwarded));
+            }
+        }
+        let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+        Ok(ClientIp(peer))
+    }
+}
+
+/// One failure counter and the limit that locks it.
+pub(super) struct ThrottleKey {
+    key: String,
+    max_failures: i64,
+}
+
+impl ThrottleKey {
+    pub(super) fn account(config: &LoginThrottleConfig, email: &str) -> Self {
+        ThrottleKey {
+            key: format!("account:{}", email.trim().to_lowercase()),
+            max_failures: config.account_max_failures,
+        }
+    }
+
+    /// IPv6 clients are keyed by their /64, which one host can rotate within.
+    pub(super) fn ip(config: &LoginThrottleConfig, ip: IpAddr) -> Self {
+        let ip = match ip {
+            IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => {
+                    let [a, b, c, d, ..] = v6.segments();
+                    IpAddr::V6(Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0))
+                }
+            },
+            v4 => v4,
+        };
+        ThrottleKey { key: format!("ip:{ip}"), max_failures: config.ip_max_failures }
+    }
+}
+
+#[derive(FromRow, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct AttemptState {
+    pub(super) failures: i64,
+    pub(super) last_failure_ts: i64,
+    pub(super) locked_until_ts: i64,
+}
+
+/// The counter after one more failure at `now` (ms). A counter idle for
+/// longer than the maximum lockout starts over, so old typos never add up
+/// to a lockout.
+pub(super) fn after_failure(
+    config: &LoginThrottleConfig,
+    max_failures: i64,
+    prior: Option<AttemptState>,
+    now: i64,
+) -> AttemptState {
+    let idle_reset_ms = config.lockout_max_secs * 1000;
+    let prior_failures = match prior {
+        Some(prior) if prior.locked_until_ts > now => prior.failures,
+        Some(prior) if now - prior.last_failure_ts <= idle_reset_ms => prior.failures,
+        _ => 0,
+    };
+    let failures = prior_failures + 1;
+    let locked_until_ts = if failures >= max_failures {
+        let doublings = (failures - max_failures).min(32) as u32;
+        let lockout_secs = config
+            .lockout_base_secs
+            .saturating_mul(1_i64 << doublings)
+            .min(config.lockout_max_secs);
+        now + lockout_secs * 1000
+    } else {
+        0
+    };
+    AttemptState { failures, last_failure_ts: now, locked_until_ts }
+}
+
+/// `429` when any of `keys` is locked out at `now`.
+pub(super) async fn ensure_not_locked(
+    pool: &SqlitePool,
+    keys: &[ThrottleKey],
+    now: i64,
+) -> Result<(), LoginError> {
+    for key in keys {
+        let locked_until_ts: Option<i64> =
+            sqlx::query_scalar("select locked_until_ts from login_attempt where throttle_key = ?1")
+                .bind(&key.key)
+                .fetch_optional(pool)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if let Some(locked_until_ts) = locked_until_ts.filter(|until| *until > now) {
+            // Round up so a client waiting exactly `Retry-After` is let in.
+            let retry_after_secs = (locked_until_ts - now + 999) / 1000;
+            return Err(LoginError::Throttled { retry_after_secs });
+        }
+    }
+    Ok(())
+}
+
+pub(super) async fn record_failure(
+    pool: &SqlitePool,
+    config: &LoginThrottleConfig,
+    keys: &[ThrottleKey],
+    now: i64,
+) -> Result<(), StatusCode> {
+    let mut tx = begin_write(pool).await?;
+    for key in keys {
+        let prior = sqlx::query_as::<_, AttemptState>(
+            "select failures, last_failure_ts, locked_until_ts from login_attempt where throttle_key = ?1",
+        )
+        .bind(&key.key)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let next = after_failure(config, key.max_failures, prior, now);
+        sqlx::query(
+            "insert or replace into login_attempt (throttle_key, failures, last_failure_ts, locked_until_ts) values (?1, ?2, ?3, ?4)",
+        )
+        .bind(&key.key)
+        .bind(next.failures)
+        .bind(next.last_failure_ts)
+        .bind(next.locked_until_ts)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if next.locked_until_ts > now && prior.is_none_or(|prior| prior.locked_until_ts <= now) {
+            tracing::warn!(
+                throttle_key = %key.key,
+                failures = next.failures,
+                locked_until_ts = next.locked_until_ts,
+                "auth attempts locked out"
+            );
+        }
+    }
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(())
+}
+
+/// Deletes the counter for `key`; returns whether one existed.
+pub(super) async fn clear_failures(
+    conn: &mut SqliteConnection,
+    key: &ThrottleKey,
+) -> Result<bool, StatusCode> {
+    let result = sqlx::query("delete from login_attempt where throttle_key = ?1")
+        .bind(&key.key)
+        .execute(conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Admin-only: clears the account lockout of a member of the caller's
+/// space. The per-IP counter is left alone; it expires on its own.
+pub(super) async fn auth_clear_lockout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, AdminError> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    ctx.require_admin()?;
+    let mut tx = begin_write(&state.pool).await?;
+    let email: String = sqlx::query_scalar(
+        "select u.email from user u join membership m on m.user_id = u.id where u.id = ?1 and m.space_id = ?2",
+    )
+    .bind(&user_id)
+    .bind(&ctx.space_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    let cleared =
+        clear_failures(&mut tx, &ThrottleKey::account(&state.login_throttle, &email)).await?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::LockoutCleared,
+            target_user_id: Some(&user_id),
+            detail: json!({ "cleared": cleared }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(vars: &[(&str, &str)]) -> Result<LoginThrottleConfig, String> {
+        let vars: HashMap<String, String> =
+            vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        parse_login_throttle(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn config_defaults_when_unset_and_reads_overrides() {
+        assert_eq!(parse(&[]), Ok(LoginThrottleConfig::default()));
+        let config = parse(&[
+            ("LOGIN_MAX_FAILURES_PER_ACCOUNT", " 3 "),
+            ("LOGIN_LOCKOUT_MAX_SECS", "60"),
+            ("TRUST_FORWARDED_FOR", "TRUE"),
+        ])
+        .expect("valid overrides");
+        assert_eq!(config.account_max_failures, 3);
+        assert_eq!(config.lockout_max_secs, 60);
+        assert!(config.trust_forwarded_for);
+    }
+
+    #[test]
+    fn config_rejects_malformed_values_naming_each_variable() {
+        let err = parse(&[
+            ("LOGIN_MAX_FAILURES_PER_IP", "0"),
+            ("LOGIN_LOCKOUT_BASE_SECS", "5s"),
+            ("TRUST_FORWARDED_FOR", "yes"),
+        ])
+        .unwrap_err();
+        for name in ["LOGIN_MAX_FAILURES_PER_IP", "LOGIN_LOCKOUT_BASE_SECS", "TRUST_FORWARDED_FOR"]
+        {
+            assert!(err.contains(name), "{err}");
+        }
+        let err = parse(&[("LOGIN_LOCKOUT_BASE_SECS", "600"), ("LOGIN_LOCKOUT_MAX_SECS", "60")])
+            .unwrap_err();
+        assert!(err.contains("must not exceed"), "{err}");
+    }
+
+    #[test]
+    fn lockout_starts_at_the_limit_and_doubles_up_to_the_cap() {
+        let config = LoginThrottleConfig {
+            account_max_failures: 3,
+            lockout_base_secs: 30,
+            lockout_max_secs: 100,
+            ..LoginThrottleConfig::default()
+        };
+        let mut state = None;
+        let mut lockouts = Vec::new();
+        for attempt in 0..6 {
+            let now = attempt * 1000;
+            let next = after_failure(&config, 3, state, now);
+            lockouts.push(if next.locked_until_ts > 0 {
+                (next.locked_until_ts - now) / 1000
+            } else {
+                0
+            });
+            state = Some(next);
+        }
+        assert_eq!(lockouts, [0, 0, 30, 60, 100, 100]);
+    }
+
+    #[test]
+    fn idle_counter_starts_over() {
+        let config = LoginThrottleConfig::default();
+        let prior = AttemptState { failures: 4, last_failure_ts: 0, locked_until_ts: 0 };
+        let later = config.lockout_max_secs * 1000 + 1;
+        assert_eq!(after_failure(&config, 5, Some(prior), later).failures, 1);
+        assert_eq!(after_failure(&config, 5, Some(prior), later - 1).failures, 5);
+    }
+
+    #[test]
+    fn ipv6_clients_share_a_key_per_slash_64() {
+        let config = LoginThrottleConfig::default();
+        let a = ThrottleKey::ip(&config, "2001:db8:1:2:aaaa::1".parse().unwrap());
+        let b = ThrottleKey::ip(&config, "2001:db8:1:2:bbbb::2".parse().unwrap());
+        let mapped = ThrottleKey::ip(&config, "::ffff:192.0.2.7".parse().unwrap());
+        assert_eq!(a.key, b.key);
+        assert_eq!(a.key, "ip:2001:db8:1:2::");
+        assert_eq!(mapped.key, "ip:192.0.2.7");
+    }
+}
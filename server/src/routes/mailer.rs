@@ -0,0 +1,165 @@
+//! Outgoing mail behind the `Mailer` trait. `MAILER` picks the transport:
+//! unset sends nothing (callers fall back to handing the content to an
+//! admin), `log` writes each message's recipient and subject to the server
+//! log and `file` writes it as a `.eml` file under `MAILER_DIR`. Both are
+//! stand-ins for local testing. Bodies carry secrets such as reset tokens,
+//! so `log` only writes them, at debug level, with the explicit dev flag
+//! `MAILER_LOG_BODIES=true`. A real transport such as SMTP or a provider's
+//! HTTP API implements `Mailer` and is added to `MailerConfig`.
+
+use axum::async_trait;
+use std::{path::PathBuf, sync::Arc};
+use uuid::Uuid;
+
+pub(super) struct MailMessage {
+    pub(super) to: String,
+    pub(super) subject: String,
+    pub(super) body: String,
+}
+
+#[async_trait]
+pub(super) trait Mailer: Send + Sync {
+    /// `Err` carries a log-safe description of the failure.
+    async fn send(&self, message: &MailMessage) -> Result<(), String>;
+}
+
+/// Read from the environment by `app_state`; validated at boot by
+/// `validate_boot_secrets`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum MailerConfig {
+    Disabled,
+    /// `bodies`: `MAILER_LOG_BODIES`, also log each body at debug level.
+    Log {
+        bodies: bool,
+    },
+    File(PathBuf),
+}
+
+/// Parses `MAILER` (`log` or `file`, unset/blank disables mail),
+/// `MAILER_LOG_BODIES` (`true`/`false`, only for `log`) and `MAILER_DIR`
+/// (required for `file`).
+pub(super) fn parse_mailer(var: impl Fn(&str) -> Option<String>) -> Result<MailerConfig, String> {
+    let Some(kind) = var("MAILER").filter(|value| !value.trim().is_empty()) else {
+        return Ok(MailerConfig::Disabled);
+    };
+    match kind.trim() {
+        "log" => {
+            match var("MAILER_LOG_BODIES").map(|value| value.trim().to_ascii_lowercase()).as_deref()
+            {
+                None | Some("") | Some("false") => Ok(MailerConfig::Log { bodies: false }),
+                Some("true") => Ok(MailerConfig::Log { bodies: true }),
+                Some(other) => {
+                    Err(format!("invalid MAILER_LOG_BODIES \"{other}\": expected true or false"))
+                }
+            }
+        }
+        "file" => var("MAILER_DIR")
+            .map(|dir| dir.trim().to_string())
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| MailerConfig::File(PathBuf::from(dir)))
+            .ok_or_else(|| {
+                "MAILER=file needs MAILER_DIR, the directory to write mail to".to_string()
+            }),
+        _ => Err(format!(
+            "invalid MAILER \"{kind}\": expected \"log\" or \"file\", or leave it unset"
+        )),
+    }
+}
+
+impl MailerConfig {
+    pub(super) fn build(&self) -> Option<Arc<dyn Mailer>> {
+        match self {
+            MailerConfig::Disabled => None,
+            MailerConfig::Log { bodies } => Some(Arc::new(LogMailer { bodies: *bodies })),
+            MailerConfig::File(dir) => Some(Arc::new(FileMailer { dir: dir.clone() })),
+        }
+    }
+}
+
+struct LogMailer {
+    bodies: bool,
+}
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, message: &MailMessage) -> Result<(), String> {
+        tracing::info!(to = %message.to, subject = %message.subject, "mail not sent (MAILER=log)");
+        if self.bodies {
+            tracing::debug!(to = %message.to, "mail body:\n{}", message.body);
+        }
+        Ok(())
+    }
+}
+
+struct FileMailer {
+    dir: PathBuf,
+}
+
+fn render_eml(message: &MailMessage) -> String {
+    format!("To: {}\r\nSubject: {}\r\n\r\n{}\r\n", message.to, message.subject, message.body)
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: &MailMessage) -> Result<(), String> {
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            chrono::Utc::now().timestamp_millis(),
+            Uuid::new_v4().simple()
+        ));
+        let contents = render_eml(message);
+        tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(path.parent().expect("mail path has a directory"))?;
+            std::fs::write(&path, contents)
+        })
+        .await
+        .map_err(|err| format!("mail writer task failed: {err}"))?
+        .map_err(|err| format!("could not write mail file: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(vars: &[(&str, &str)]) -> Result<MailerConfig, String> {
+        let vars: HashMap<String, String> =
+            vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        parse_mailer(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn mailer_is_disabled_unless_configured() {
+        assert_eq!(parse(&[]), Ok(MailerConfig::Disabled));
+        assert_eq!(parse(&[("MAILER", " ")]), Ok(MailerConfig::Disabled));
+        assert_eq!(parse(&[("MAILER", "log")]), Ok(MailerConfig::Log { bodies: false }));
+        assert_eq!(
+            parse(&[("MAILER", "log"), ("MAILER_LOG_BODIES", " TRUE ")]),
+            Ok(MailerConfig::Log { bodies: true })
+        );
+        assert_eq!(
+            parse(&[("MAILER", "file"), ("MAILER_DIR", " /tmp/mail ")]),
+            Ok(MailerConfig::File(PathBuf::from("/tmp/mail")))
+        );
+    }
+
+    #[test]
+    fn file_mailer_needs_a_directory_and_unknown_kinds_fail() {
+        assert!(parse(&[("MAILER", "file")]).unwrap_err().contains("MAILER_DIR"));
+        assert!(parse(&[("MAILER", "smtp")]).unwrap_err().contains("invalid MAILER \"smtp\""));
+        assert!(parse(&[("MAILER", "log"), ("MAILER_LOG_BODIES", "yes")])
+            .unwrap_err()
+            .contains("MAILER_LOG_BODIES"));
+    }
+
+    #[test]
+    fn eml_has_headers_then_body() {
+        let message = MailMessage {
+            to: "a@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "Line".to_string(),
+        };
+        assert_eq!(render_eml(&message), "To: a@example.com\r\nSubject: Hello\r\n\r\nLine\r\n");
+    }
+}
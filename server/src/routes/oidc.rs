@@ -0,0 +1,716 @@
+//! OpenID Connect single sign-on: the authorization code flow with PKCE.
+//!
+//! Off unless `OIDC_ISSUER` is set (see `parse_oidc`). The web client calls
+//! `GET /auth/oidc/start`, keeps the returned `state` and `binding` in
+//! session storage and sends the browser to the returned
+//! `authorization_url`. The provider redirects back to `OIDC_REDIRECT_URL`
+//! (the web client) with `code` and `state`; the client checks the state is
+//! the one it kept and posts both to `POST /auth/oidc/callback` with the
+//! binding. Only the browser that started a sign-in holds its binding, so an
+//! authorization response planted in another browser (login CSRF) is
+//! refused. The server redeems the code at the provider's token endpoint,
+//! checks the ID token's signature against the provider's JWKS along with
+//! its issuer, audience, expiry and nonce, and maps its `email` claim, which
+//! must be verified, to a member of the space. With
+//! `OIDC_AUTO_PROVISION=true` an unknown email becomes a contributor with no
+//! password. The session and response are those of `POST /auth/login`.
+//!
+//! Provider metadata and keys are fetched on each sign-in, so a key
+//! rotation needs no restart. A member with TOTP enabled (see
+//! `two_factor.rs`) still needs their code: without one the callback answers
+//! `two_factor_required` and keeps the sign-in, already vouched for by the
+//! provider, until the client repeats the callback with `two_factor_code`
+//! (and no `code`) or it expires.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
+
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::auth::{LoginResponse, LoginUserRow};
+use super::login_throttle::{ensure_not_locked, record_failure, ClientIp, LoginError, ThrottleKey};
+use super::opaque_token::opaque_token_hash;
+use super::session::{start_session, DeviceInfo, UserAgent};
+use super::two_factor::{check_second_factor, SecondFactor};
+use super::types::{begin_write, is_unique_violation, issue_token, AppState};
+
+/// How long a started sign-in may take to come back to the callback.
+const OIDC_LOGIN_TTL_MS: i64 = 10 * 60 * 1000;
+
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Read from the environment by `app_state`; validated at boot by
+/// `validate_boot_secrets`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct OidcConfig {
+    /// `OIDC_ISSUER`, exactly as the provider's `iss` claim spells it.
+    pub(super) issuer: String,
+    /// `OIDC_CLIENT_ID`.
+    pub(super) client_id: String,
+    /// `OIDC_CLIENT_SECRET`; unset for a public client, which relies on
+    /// PKCE alone.
+    pub(super) client_secret: Option<String>,
+    /// `OIDC_REDIRECT_URL`: where the provider sends the browser back to,
+    /// i.e. the web client. Must be registered with the provider.
+    pub(super) redirect_url: String,
+    /// `OIDC_AUTO_PROVISION` (default false).
+    pub(super) auto_provision: bool,
+}
+
+/// Parses the `OIDC_*` variables. Unset/blank `OIDC_ISSUER` disables
+/// single sign-on; once it is set, `OIDC_CLIENT_ID` and `OIDC_REDIRECT_URL`
+/// are required, both URLs must be `http(s)` and `OIDC_AUTO_PROVISION` must
+/// be `true` or `false`.
+pub(super) fn parse_oidc(
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<Option<OidcConfig>, String> {
+    let value = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let Some(issuer) = value("OIDC_ISSUER") else {
+        return Ok(None);
+    };
+    let mut failures = Vec::new();
+    let mut url = |name: &str, raw: Option<String>| match raw {
+        Some(raw) if Url::parse(&raw).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) => {
+            raw
+        }
+        Some(raw) => {
+            failures.push(format!("invalid {name} \"{raw}\": expected an http(s) URL"));
+            raw
+        }
+        None => {
+            failures.push(format!("OIDC_ISSUER is set, so {name} is required"));
+            String::new()
+        }
+    };
+    let issuer = url("OIDC_ISSUER", Some(issuer));
+    let redirect_url = url("OIDC_REDIRECT_URL", value("OIDC_REDIRECT_URL"));
+    let client_id = value("OIDC_CLIENT_ID").unwrap_or_else(|| {
+        failures.push("OIDC_ISSUER is set, so OIDC_CLIENT_ID is required".to_string());
+        String::new()
+    });
+    let auto_provision = match value("OIDC_AUTO_PROVISION").map(|v| v.to_ascii_lowercase()) {
+        None => false,
+        Some(v) if v == "false" => false,
+        Some(v) if v == "true" => true,
+        Some(other) => {
+            failures
+                .push(format!("invalid OIDC_AUTO_PROVISION \"{other}\": expected true or false"));
+            false
+        }
+    };
+    if !failures.is_empty() {
+        return Err(failures.join("\n"));
+    }
+    Ok(Some(OidcConfig {
+        issuer,
+        client_id,
+        client_secret: value("OIDC_CLIENT_SECRET"),
+        redirect_url,
+        auto_provision,
+    }))
+}
+
+impl OidcConfig {
+    pub(super) fn build(self) -> Arc<OidcProvider> {
+        let http = reqwest::Client::builder()
+            .timeout(PROVIDER_TIMEOUT)
+            .build()
+            .expect("HTTP client for the OIDC provider");
+        Arc::new(OidcProvider { config: self, http })
+    }
+}
+
+pub(super) struct OidcProvider {
+    config: OidcConfig,
+    http: reqwest::Client,
+}
+
+/// The fields of the provider's discovery document this flow uses.
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenEndpointResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    nonce: Option<String>,
+    email: Option<String>,
+    /// A boolean per the spec; some providers send the string `"true"`.
+    email_verified: Option<Value>,
+    name: Option<String>,
+}
+
+impl IdTokenClaims {
+    fn verified_email(&self) -> Option<String> {
+        let verified = match &self.email_verified {
+            Some(Value::Bool(verified)) => *verified,
+            Some(Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+        let email = self.email.as_deref().map(str::trim).filter(|email| !email.is_empty())?;
+        verified.then(|| email.to_lowercase())
+    }
+}
+
+/// The provider misbehaved or could not be reached.
+fn provider_error(what: &str, err: impl std::fmt::Display) -> StatusCode {
+    tracing::warn!("OIDC provider {what} failed: {err}");
+    StatusCode::BAD_GATEWAY
+}
+
+impl OidcProvider {
+    async fn get_json<T: DeserializeOwned>(&self, what: &str, url: &str) -> Result<T, StatusCode> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| provider_error(what, err))?
+            .json()
+            .await
+            .map_err(|err| provider_error(what, err))
+    }
+
+    async fn discover(&self) -> Result<ProviderMetadata, StatusCode> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = self.get_json("discovery", &url).await?;
+        if metadata.issuer != self.config.issuer {
+            return Err(provider_error(
+                "discovery",
+                format!("issuer \"{}\" does not match OIDC_ISSUER", metadata.issuer),
+            ));
+        }
+        Ok(metadata)
+    }
+
+    /// `401` when the provider refuses the code (expired, reused, or a
+    /// verifier that does not match).
+    async fn redeem_code(
+        &self,
+        metadata: &ProviderMetadata,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<String, StatusCode> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|err| provider_error("token request", err))?;
+        if response.status().is_client_error() {
+            tracing::info!(status = %response.status(), "OIDC provider refused the authorization code");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let tokens: TokenEndpointResponse = response
+            .error_for_status()
+            .map_err(|err| provider_error("token request", err))?
+            .json()
+            .await
+            .map_err(|err| provider_error("token request", err))?;
+        Ok(tokens.id_token)
+    }
+
+    /// Checks signature, `iss`, `aud` and `exp`. `HS*` tokens are keyed with
+    /// the client secret, as the spec allows; anything else must be signed
+    /// by a key from the provider's JWKS.
+    async fn verify_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, StatusCode> {
+        let header = decode_header(id_token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+        let key = match header.alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                let secret =
+                    self.config.client_secret.as_deref().ok_or(StatusCode::UNAUTHORIZED)?;
+                DecodingKey::from_secret(secret.as_bytes())
+            }
+            _ => {
+                let jwks: JwkSet = self.get_json("JWKS", &metadata.jwks_uri).await?;
+                let jwk = match &header.kid {
+                    Some(kid) => jwks.find(kid),
+                    None if jwks.keys.len() == 1 => jwks.keys.first(),
+                    None => None,
+                };
+                DecodingKey::from_jwk(jwk.ok_or(StatusCode::UNAUTHORIZED)?)
+                    .map_err(|_| StatusCode::UNAUTHORIZED)?
+            }
+        };
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        decode::<IdTokenClaims>(id_token, &key, &validation).map(|data| data.claims).map_err(
+            |err| {
+                tracing::info!("OIDC ID token rejected: {err}");
+                StatusCode::UNAUTHORIZED
+            },
+        )
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct OidcStartQuery {
+    pub(super) space_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(super) struct OidcStartResponse {
+    pub(super) authorization_url: String,
+    /// Echoed by the provider; the client checks the redirect carries it.
+    pub(super) state: String,
+    /// Secret the client keeps and sends to the callback; only its hash is
+    /// stored.
+    pub(super) binding: String,
+}
+
+/// Unauthenticated. `404` when single sign-on is off, `502` when the
+/// provider's discovery document cannot be fetched.
+pub(super) async fn auth_oidc_start(
+    State(state): State<AppState>,
+    Query(query): Query<OidcStartQuery>,
+) -> Result<Json<OidcStartResponse>, StatusCode> {
+    let provider = state.oidc.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let space_id = query
+        .space_id
+        .map(|space_id| space_id.trim().to_string())
+        .filter(|space_id| !space_id.is_empty())
+        .unwrap_or_else(|| "s1".to_string());
+    let metadata = provider.discover().await?;
+
+    let login_state = Uuid::new_v4().simple().to_string();
+    let binding = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let nonce = Uuid::new_v4().simple().to_string();
+    let code_verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+    let mut authorization_url = Url::parse(&metadata.authorization_endpoint)
+        .map_err(|err| provider_error("discovery", err))?;
+    authorization_url
+        .query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.config.client_id)
+        .append_pair("redirect_uri", &provider.config.redirect_url)
+        .append_pair("scope", "openid email profile")
+        .append_pair("state", &login_state)
+        .append_pair("nonce", &nonce)
+        .append_pair("code_challenge", &code_challenge)
+        .append_pair("code_challenge_method", "S256");
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut tx = begin_write(&state.pool).await?;
+    sqlx::query("delete from oidc_login where expires_ts <= ?1")
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query(
+        "insert into oidc_login (state, nonce, code_verifier, space_id, created_ts, expires_ts, binding_hash) values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )
+    .bind(&login_state)
+    .bind(&nonce)
+    .bind(&code_verifier)
+    .bind(&space_id)
+    .bind(now)
+    .bind(now + OIDC_LOGIN_TTL_MS)
+    .bind(opaque_token_hash(&binding))
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(OidcStartResponse {
+        authorization_url: authorization_url.into(),
+        state: login_state,
+        binding,
+    }))
+}
+
+#[derive(Deserialize)]
+pub(super) struct OidcCallbackBody {
+    /// Omitted when repeating the callback with `two_factor_code`.
+    #[serde(default)]
+    pub(super) code: String,
+    pub(super) state: String,
+    /// From `GET /auth/oidc/start`, kept by the browser that started.
+    #[serde(default)]
+    pub(super) binding: String,
+    #[serde(default)]
+    pub(super) device_label: Option<String>,
+    /// TOTP or recovery code; required once the member has 2FA enabled.
+    #[serde(default)]
+    pub(super) two_factor_code: Option<String>,
+}
+
+/// A started sign-in, as `oidc_login` stores it.
+#[derive(sqlx::FromRow)]
+struct PendingOidcLogin {
+    nonce: String,
+    code_verifier: String,
+    space_id: String,
+    created_ts: i64,
+    expires_ts: i64,
+    binding_hash: String,
+    /// Set once the provider vouched for a member whose TOTP is pending.
+    user_id: Option<String>,
+}
+
+/// Unauthenticated; answers like `POST /auth/login`. `400` without a state,
+/// binding or code; `401` for an unknown, used or expired `state`, a
+/// binding that does not match it, a code the provider refuses, an ID token
+/// that fails verification or a wrong `two_factor_code`; `401` with code
+/// `two_factor_required` while the member's TOTP is missing; `403` when the
+/// token has no verified email or the email is not a member of the space
+/// (and auto-provisioning is off); `502` when the provider misbehaves.
+/// Rejected attempts count against the client IP's login throttle, like
+/// `auth_setup`.
+pub(super) async fn auth_oidc_callback(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
+    Json(body): Json<OidcCallbackBody>,
+) -> Result<Json<LoginResponse>, LoginError> {
+    let provider = state.oidc.clone().ok_or(StatusCode::NOT_FOUND)?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let throttle_keys: Vec<ThrottleKey> =
+        client_ip.map(|ip| ThrottleKey::ip(&state.login_throttle, ip)).into_iter().collect();
+    ensure_not_locked(&state.pool, &throttle_keys, now).await?;
+    match complete_oidc_login(&state, &provider, body, user_agent.as_deref(), now).await {
+        Err(LoginError::Status(status)) if status.is_client_error() => {
+            record_failure(&state.pool, &state.login_throttle, &throttle_keys, now).await?;
+            Err(status.into())
+        }
+        result => result.map(Json),
+    }
+}
+
+async fn complete_oidc_login(
+    state: &AppState,
+    provider: &OidcProvider,
+    body: OidcCallbackBody,
+    user_agent: Option<&str>,
+    now: i64,
+) -> Result<LoginResponse, LoginError> {
+    let login_state = body.state.trim();
+    let binding = body.binding.trim();
+    if login_state.is_empty() || binding.is_empty() {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    let pending = sqlx::query_as::<_, PendingOidcLogin>(
+        "delete from oidc_login where state = ?1 and binding_hash = ?2 and expires_ts > ?3 returning nonce, code_verifier, space_id, created_ts, expires_ts, binding_hash, user_id",
+    )
+    .bind(login_state)
+    .bind(opaque_token_hash(binding))
+    .bind(now)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::UNAUTHORIZED)?;
+    let space_id = pending.space_id.clone();
+
+    let user = match &pending.user_id {
+        // The provider already vouched for this member; only TOTP is left.
+        Some(user_id) => {
+            find_member_by_id(state, user_id, &space_id).await?.ok_or(StatusCode::UNAUTHORIZED)?
+        }
+        None => {
+            let code = body.code.trim();
+            if code.is_empty() {
+                return Err(StatusCode::BAD_REQUEST.into());
+            }
+            let metadata = provider.discover().await?;
+            let id_token = provider.redeem_code(&metadata, code, &pending.code_verifier).await?;
+            let claims = provider.verify_id_token(&metadata, &id_token).await?;
+            if claims.nonce.as_deref() != Some(pending.nonce.as_str()) {
+                return Err(StatusCode::UNAUTHORIZED.into());
+            }
+            let email = claims.verified_email().ok_or(StatusCode::FORBIDDEN)?;
+            match find_member(state, &email, &space_id).await? {
+                Some(user) => user,
+                None if provider.config.auto_provision => {
+                    provision_contributor(state, &email, claims.name.as_deref(), &space_id).await?;
+                    find_member(state, &email, &space_id)
+                        .await?
+                        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?
+                }
+                None => return Err(StatusCode::FORBIDDEN.into()),
+            }
+        }
+    };
+
+    let mut tx = begin_write(&state.pool).await?;
+    match check_second_factor(&mut tx, &user.user_id, body.two_factor_code.as_deref()).await? {
+        SecondFactor::NotEnrolled | SecondFactor::Totp => {}
+        SecondFactor::Missing => {
+            // Keep the sign-in, now tied to the member, for the retry with
+            // the code; the authorization code cannot be redeemed twice.
+            sqlx::query(
+                "insert into oidc_login (state, nonce, code_verifier, space_id, created_ts, expires_ts, binding_hash, user_id) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )
+            .bind(login_state)
+            .bind(&pending.nonce)
+            .bind(&pending.code_verifier)
+            .bind(&space_id)
+            .bind(pending.created_ts)
+            .bind(pending.expires_ts)
+            .bind(&pending.binding_hash)
+            .bind(&user.user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            return Err(LoginError::TwoFactorRequired);
+        }
+        SecondFactor::RecoveryCode => {
+            record_audit(
+                &mut tx,
+                AuditEntry {
+                    space_id: &space_id,
+                    actor_user_id: Some(&user.user_id),
+                    action: AuditAction::RecoveryCodeUsed,
+                    target_user_id: Some(&user.user_id),
+                    detail: json!({ "via": "oidc" }),
+                },
+            )
+            .await?;
+        }
+        // The sign-in is spent; the member starts over at the provider.
+        SecondFactor::Invalid => {
+            record_audit(
+                &mut tx,
+                AuditEntry {
+                    space_id: &space_id,
+                    actor_user_id: None,
+                    action: AuditAction::LoginFailed,
+                    target_user_id: Some(&user.user_id),
+                    detail: json!({ "email": user.email, "reason": "two_factor", "via": "oidc" }),
+                },
+            )
+            .await?;
+            tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            return Err(StatusCode::UNAUTHORIZED.into());
+        }
+    }
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let device = DeviceInfo { label: body.device_label.as_deref(), user_agent };
+    let session = start_session(&state.pool, &user.user_id, &space_id, device).await?;
+    let token = issue_token(
+        &user.user_id,
+        &space_id,
+        user.token_version,
+        Some(&session.session_id),
+        &state.jwt_secret,
+    )?;
+    Ok(LoginResponse {
+        token,
+        refresh_token: session.refresh_token,
+        user_id: user.user_id,
+        email: user.email,
+        display: user.display,
+        avatar_icon: user.avatar_icon,
+        space_id,
+        role: user.role,
+    })
+}
+
+async fn find_member(
+    state: &AppState,
+    email: &str,
+    space_id: &str,
+) -> Result<Option<LoginUserRow>, StatusCode> {
+    sqlx::query_as::<_, LoginUserRow>(
+        "select u.id as user_id, u.email, u.display, u.avatar_icon, u.password_hash, m.role, u.token_version from user u join membership m on m.user_id = u.id where lower(u.email) = lower(?1) and m.space_id = ?2 limit 1",
+    )
+    .bind(email)
+    .bind(space_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn find_member_by_id(
+    state: &AppState,
+    user_id: &str,
+    space_id: &str,
+) -> Result<Option<LoginUserRow>, StatusCode> {
+    sqlx::query_as::<_, LoginUserRow>(
+        "select u.id as user_id, u.email, u.display, u.avatar_icon, u.password_hash, m.role, u.token_version from user u join membership m on m.user_id = u.id where u.id = ?1 and m.space_id = ?2",
+    )
+    .bind(user_id)
+    .bind(space_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Adds `email` to `space_id` as a contributor, creating the user (with no
+/// password, so only single sign-on works for them until an admin sets one)
+/// unless it already exists in another space. `403` for an unknown space.
+async fn provision_contributor(
+    state: &AppState,
+    email: &str,
+    name: Option<&str>,
+    space_id: &str,
+) -> Result<(), StatusCode> {
+    let mut tx = begin_write(&state.pool).await?;
+    let space_exists: bool = sqlx::query_scalar("select exists(select 1 from space where id = ?1)")
+        .bind(space_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !space_exists {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let existing_user_id: Option<String> =
+        sqlx::query_scalar("select id from user where lower(email) = lower(?1) limit 1")
+            .bind(email)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let user_id = match existing_user_id {
+        Some(user_id) => user_id,
+        None => {
+            let user_id = format!("u-{}", Uuid::new_v4());
+            let display = name
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| email.split('@').next().unwrap_or(email));
+            sqlx::query("insert into user (id, email, display) values (?1, ?2, ?3)")
+                .bind(&user_id)
+                .bind(email)
+                .bind(display)
+                .execute(&mut *tx)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            user_id
+        }
+    };
+    let inserted = sqlx::query(
+        "insert into membership (id, space_id, user_id, role) values (?1, ?2, ?3, 'contributor')",
+    )
+    .bind(format!("m-{}", Uuid::new_v4()))
+    .bind(space_id)
+    .bind(&user_id)
+    .execute(&mut *tx)
+    .await;
+    match inserted {
+        Ok(_) => {}
+        // Provisioned by a concurrent sign-in.
+        Err(err) if is_unique_violation(&err) => return Ok(()),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id,
+            actor_user_id: None,
+            action: AuditAction::MemberCreated,
+            target_user_id: Some(&user_id),
+            detail: json!({ "email": email, "role": "contributor", "via": "oidc" }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(vars: &[(&str, &str)]) -> Result<Option<OidcConfig>, String> {
+        let vars: HashMap<String, String> =
+            vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        parse_oidc(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn oidc_is_off_without_an_issuer() {
+        assert_eq!(parse(&[]), Ok(None));
+        assert_eq!(parse(&[("OIDC_ISSUER", " "), ("OIDC_CLIENT_ID", "tasksync")]), Ok(None));
+    }
+
+    #[test]
+    fn oidc_config_reads_every_variable() {
+        let config = parse(&[
+            ("OIDC_ISSUER", "https://id.example.com/realms/team"),
+            ("OIDC_CLIENT_ID", " tasksync "),
+            ("OIDC_CLIENT_SECRET", "s3cret"),
+            ("OIDC_REDIRECT_URL", "https://tasks.example.com/"),
+            ("OIDC_AUTO_PROVISION", "TRUE"),
+        ]);
+        assert_eq!(
+            config,
+            Ok(Some(OidcConfig {
+                issuer: "https://id.example.com/realms/team".to_string(),
+                client_id: "tasksync".to_string(),
+                client_secret: Some("s3cret".to_string()),
+                redirect_url: "https://tasks.example.com/".to_string(),
+                auto_provision: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn oidc_config_reports_every_problem() {
+        let message = parse(&[("OIDC_ISSUER", "id.example.com"), ("OIDC_AUTO_PROVISION", "yes")])
+            .unwrap_err();
+        assert!(message.contains("invalid OIDC_ISSUER \"id.example.com\""));
+        assert!(message.contains("OIDC_REDIRECT_URL is required"));
+        assert!(message.contains("OIDC_CLIENT_ID is required"));
+        assert!(message.contains("invalid OIDC_AUTO_PROVISION \"yes\""));
+    }
+
+    #[test]
+    fn only_a_verified_email_counts() {
+        let claims = |email: Option<&str>, verified: Option<Value>| IdTokenClaims {
+            nonce: None,
+            email: email.map(str::to_string),
+            email_verified: verified,
+            name: None,
+        };
+        assert_eq!(
+            claims(Some(" Ada@Example.com "), Some(json!(true))).verified_email().as_deref(),
+            Some("ada@example.com")
+        );
+        assert_eq!(
+            claims(Some("ada@example.com"), Some(json!("true"))).verified_email().as_deref(),
+            Some("ada@example.com")
+        );
+        assert_eq!(claims(Some("ada@example.com"), Some(json!(false))).verified_email(), None);
+        assert_eq!(claims(Some("ada@example.com"), None).verified_email(), None);
+        assert_eq!(claims(None, Some(json!(true))).verified_email(), None);
+    }
+}
@@ -0,0 +1,20 @@
+//! Generation and hashing of the opaque secrets handed to clients: API
+//! tokens, refresh tokens, password reset tokens and webhook secrets.
+//!
+//! Free of web-framework types so the `reset_token` binary can include it
+//! (`#[path]`) alongside `reset_token.rs`.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// `prefix` followed by 244 random bits from two v4 UUIDs, as hex.
+pub(super) fn new_opaque_token(prefix: &str) -> String {
+    format!("{prefix}{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// What gets stored in place of a token; unsalted, which is fine for
+/// random secrets of this length.
+pub(super) fn opaque_token_hash(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
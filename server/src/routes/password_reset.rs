@@ -0,0 +1,186 @@
+//! Password reset with single-use tokens.
+//!
+//! An admin calls `POST /auth/members/:user_id/reset-token` (or an operator
+//! runs the `reset_token` binary) to issue a token for a member. With a
+//! `Mailer` configured the token is mailed to the member; otherwise it is
+//! returned to the admin to pass on. The member then calls the
+//! unauthenticated `POST /auth/reset` with the token and a new password.
+//! Only a hash of the token is stored, it expires after
+//! `PASSWORD_RESET_TTL_MS` and a successful reset spends it, bumps the
+//! member's `token_version` and revokes their sessions, like an admin
+//! password reset. 2FA, when enabled, still applies at the next login.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::login_throttle::{
+    clear_failures, ensure_not_locked, record_failure, ClientIp, LoginError, ThrottleKey,
+};
+use super::mailer::MailMessage;
+use super::opaque_token::opaque_token_hash;
+use super::reset_token::{insert_reset_token, PASSWORD_RESET_TTL_MS};
+use super::session::revoke_user_sessions;
+use super::types::{
+    begin_write, ctx_from_headers, hash_password, password_meets_policy, AdminError, AppState,
+};
+
+#[derive(Deserialize)]
+pub(super) struct ResetPasswordBody {
+    pub(super) token: String,
+    pub(super) new_password: String,
+}
+
+#[derive(Serialize)]
+pub(super) struct ResetTokenResponse {
+    pub(super) expires_ts: i64,
+    /// The token was mailed to the member.
+    pub(super) delivered: bool,
+    /// Only when it was not delivered, for the admin to pass on.
+    pub(super) reset_token: Option<String>,
+}
+
+fn reset_mail(email: &str, token: &str) -> MailMessage {
+    MailMessage {
+        to: email.to_string(),
+        subject: "Reset your TaskSync password".to_string(),
+        body: format!(
+            "A password reset was requested for your TaskSync account ({email}).\n\n\
+             Reset code: {token}\n\n\
+             Enter it on the sign-in screen under \"Reset password\" within {} minutes. \
+             If you did not expect this message, you can ignore it.",
+            PASSWORD_RESET_TTL_MS / 60_000
+        ),
+    }
+}
+
+/// Admin-only; `404` unless `user_id` is a member of the caller's space.
+pub(super) async fn auth_issue_reset_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Result<(StatusCode, Json<ResetTokenResponse>), AdminError> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    ctx.require_admin()?;
+    let email: String = sqlx::query_scalar(
+        "select u.email from user u join membership m on m.user_id = u.id where u.id = ?1 and m.space_id = ?2",
+    )
+    .bind(&user_id)
+    .bind(&ctx.space_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    // The token and its audit entry commit together; the mail goes out
+    // afterwards so the write lock is not held across the send.
+    let mut tx = begin_write(&state.pool).await?;
+    let (token, expires_ts) = insert_reset_token(&mut tx, &user_id, &ctx.space_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::PasswordResetIssued,
+            target_user_id: Some(&user_id),
+            detail: json!({ "expires_ts": expires_ts }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let delivered = match &state.mailer {
+        Some(mailer) => match mailer.send(&reset_mail(&email, &token)).await {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!(user_id = %user_id, "password reset mail not sent: {err}");
+                false
+            }
+        },
+        None => false,
+    };
+    Ok((
+        StatusCode::CREATED,
+        Json(ResetTokenResponse {
+            expires_ts,
+            delivered,
+            reset_token: (!delivered).then_some(token),
+        }),
+    ))
+}
+
+/// Unauthenticated; the token is the credential. Rejected attempts (`400`
+/// for a blank token or a password failing `password_meets_policy`, `401`
+/// for an unknown, used or expired token) count against the client IP's
+/// login throttle, like `auth_setup`.
+pub(super) async fn auth_reset_password(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Json(body): Json<ResetPasswordBody>,
+) -> Result<StatusCode, LoginError> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let throttle_keys: Vec<ThrottleKey> =
+        client_ip.map(|ip| ThrottleKey::ip(&state.login_throttle, ip)).into_iter().collect();
+    ensure_not_locked(&state.pool, &throttle_keys, now).await?;
+    match reset_password(&state, body, now).await {
+        Err(status) if status.is_client_error() => {
+            record_failure(&state.pool, &state.login_throttle, &throttle_keys, now).await?;
+            Err(status.into())
+        }
+        result => result.map_err(LoginError::from),
+    }
+}
+
+async fn reset_password(
+    state: &AppState,
+    body: ResetPasswordBody,
+    now: i64,
+) -> Result<StatusCode, StatusCode> {
+    let token = body.token.trim();
+    let new_password = body.new_password.trim();
+    if token.is_empty() || !password_meets_policy(new_password) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    // Hash before taking the write lock; bcrypt is slow.
+    let password_hash = hash_password(new_password)?;
+
+    let mut tx = begin_write(&state.pool).await?;
+    let (user_id, space_id): (String, String) = sqlx::query_as(
+        "update password_reset set used_ts = ?1 where token_hash = ?2 and used_ts is null and expires_ts > ?1 returning user_id, space_id",
+    )
+    .bind(now)
+    .bind(opaque_token_hash(token))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::UNAUTHORIZED)?;
+    let email: String = sqlx::query_scalar(
+        "update user set password_hash = ?1, token_version = token_version + 1 where id = ?2 returning email",
+    )
+    .bind(&password_hash)
+    .bind(&user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    revoke_user_sessions(&mut tx, &user_id, None).await?;
+    clear_failures(&mut tx, &ThrottleKey::account(&state.login_throttle, &email)).await?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &space_id,
+            actor_user_id: Some(&user_id),
+            action: AuditAction::PasswordResetCompleted,
+            target_user_id: Some(&user_id),
+            detail: json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,880 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+
+use super::types::ErrorBody;
+
+/// Upper bound on calendar days examined when looking for the next RRULE
+/// occurrence, about 110 years whatever the `FREQ`. Generous enough for sparse
+/// rules (Feb 29 falling on a Monday) while bounding the work a rule that can
+/// never match costs on every validation and roll-forward.
+const MAX_RRULE_SCAN_DAYS: u64 = 40_000;
+
+/// Longest each month can be, for rejecting `BYMONTHDAY`/`BYMONTH` pairs that
+/// never meet.
+const MAX_MONTH_DAYS: [i32; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+/// A parsed `task.recur_rule`: either one of the web client's preset keywords
+/// or an RFC 5545 `RRULE`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum RecurRule {
+    Keyword(RecurKeyword),
+    Rrule(Rrule),
+}
+
+/// Preset keywords kept in lockstep with `recurrenceRules` in
+/// `web/src/lib/tasks/recurrence.ts`. Existing rows store these verbatim, so
+/// they stay valid alongside full RRULE strings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum RecurKeyword {
+    Daily,
+    Weekdays,
+    Weekly,
+    Biweekly,
+    Monthly,
+    Quarterly,
+    Biannual,
+    Annual,
+    LastDayOfMonth,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ByDay {
+    ordinal: Option<i32>,
+    weekday: Weekday,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SeriesEnd {
+    Never,
+    Count(u32),
+    Until(NaiveDate),
+}
+
+/// The supported `RRULE` subset: `FREQ` (DAILY/WEEKLY/MONTHLY/YEARLY),
+/// `INTERVAL`, `BYDAY`, `BYMONTHDAY`, `BYMONTH`, `BYSETPOS`, `WKST`, and one
+/// of `COUNT`/`UNTIL`. Tasks are date-granular, so DTSTART is the task's
+/// anchor date rather than a stored property.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Rrule {
+    freq: Freq,
+    interval: u32,
+    by_day: Vec<ByDay>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+    by_set_pos: Vec<i32>,
+    week_start: Weekday,
+    end: SeriesEnd,
+}
+
+/// Why a `recur_rule` was rejected. Messages are static so no request data is
+/// echoed back in the response body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct RecurRuleError {
+    pub(super) message: &'static str,
+}
+
+impl RecurRuleError {
+    pub(super) const CODE: &'static str = "invalid_recur_rule";
+
+    fn new(message: &'static str) -> Self {
+        RecurRuleError { message }
+    }
+}
+
+impl IntoResponse for RecurRuleError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(ErrorBody::new(Self::CODE, self.message))).into_response()
+    }
+}
+
+impl RecurRule {
+    pub(super) fn parse(raw: &str) -> Result<Self, RecurRuleError> {
+        if let Some(keyword) = RecurKeyword::parse(raw) {
+            return Ok(RecurRule::Keyword(keyword));
+        }
+        let upper = raw.to_ascii_uppercase();
+        let body = upper.strip_prefix("RRULE:").unwrap_or(&upper);
+        if !body.contains('=') {
+            return Err(RecurRuleError::new("recur_rule must be a preset keyword or an RRULE"));
+        }
+        Rrule::parse(body).map(RecurRule::Rrule)
+    }
+
+    /// The first occurrence strictly after `after`, for a series anchored at
+    /// `anchor`. `None` means the series has ended.
+    fn next_occurrence(&self, anchor: NaiveDate, after: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            RecurRule::Keyword(keyword) => {
+                let mut next = keyword.next_after(anchor);
+                while next <= after {
+                    let candidate = keyword.next_after(next);
+                    if candidate == next {
+                        break;
+                    }
+                    next = candidate;
+                }
+                Some(next)
+            }
+            RecurRule::Rrule(rule) => rule.next_occurrence(anchor, after, true),
+        }
+    }
+
+    /// Whether completing occurrence number `occurrences_completed + 1` ends
+    /// the series under `COUNT`.
+    fn count_exhausted(&self, occurrences_completed: i64) -> bool {
+        match self {
+            RecurRule::Rrule(Rrule { end: SeriesEnd::Count(count), .. }) => {
+                occurrences_completed + 1 >= i64::from(*count)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl RecurKeyword {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "daily" => Some(Self::Daily),
+            "weekdays" => Some(Self::Weekdays),
+            "weekly" => Some(Self::Weekly),
+            "biweekly" => Some(Self::Biweekly),
+            "monthly" => Some(Self::Monthly),
+            "quarterly" => Some(Self::Quarterly),
+            "biannual" => Some(Self::Biannual),
+            "annual" => Some(Self::Annual),
+            "lastDayOfMonth" => Some(Self::LastDayOfMonth),
+            _ => None,
+        }
+    }
+
+    /// The occurrence immediately after `date`. Month-based keywords clamp to
+    /// the last day of a shorter target month (Jan 31 + 1 month = Feb 28/29).
+    fn next_after(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Daily => add_days(date, 1),
+            Self::Weekdays => next_weekday(date),
+            Self::Weekly => add_days(date, 7),
+            Self::Biweekly => add_days(date, 14),
+            Self::Monthly => add_months(date, 1),
+            Self::Quarterly => add_months(date, 3),
+            Self::Biannual => add_months(date, 6),
+            Self::Annual => add_months(date, 12),
+            Self::LastDayOfMonth => last_day_of_month(add_months(first_of_month(date), 1)),
+        }
+    }
+}
+
+impl Rrule {
+    fn parse(body: &str) -> Result<Self, RecurRuleError> {
+        let mut freq = None;
+        let mut interval = None;
+        let mut by_day = None;
+        let mut by_month_day = None;
+        let mut by_month = None;
+        let mut by_set_pos = None;
+        let mut week_start = None;
+        let mut count = None;
+        let mut until = None;
+
+        for part in body.split(';').filter(|part| !part.is_empty()) {
+            let Some((key, value)) = part.split_once('=') else {
+                return Err(RecurRuleError::new("RRULE parts must be KEY=VALUE pairs"));
+            };
+            let duplicate = match key {
+                "FREQ" => freq.replace(parse_freq(value)?).is_some(),
+                "INTERVAL" => interval
+                    .replace(parse_positive(value, "INTERVAL must be a positive integer")?)
+                    .is_some(),
+                "BYDAY" => by_day.replace(parse_list(value, parse_by_day)?).is_some(),
+                "BYMONTHDAY" => by_month_day
+                    .replace(parse_list(value, |item| {
+                        parse_signed_in_range(
+                            item,
+                            31,
+                            "BYMONTHDAY values must be 1 to 31 or -31 to -1",
+                        )
+                    })?)
+                    .is_some(),
+                "BYMONTH" => by_month
+                    .replace(parse_list(value, |item| {
+                        item.parse::<u32>()
+                            .ok()
+                            .filter(|month| (1..=12).contains(month))
+                            .ok_or(RecurRuleError::new("BYMONTH values must be 1 to 12"))
+                    })?)
+                    .is_some(),
+                "BYSETPOS" => by_set_pos
+                    .replace(parse_list(value, |item| {
+                        parse_signed_in_range(
+                            item,
+                            366,
+                            "BYSETPOS values must be 1 to 366 or -366 to -1",
+                        )
+                    })?)
+                    .is_some(),
+                "WKST" => week_start
+                    .replace(
+                        parse_weekday(value)
+                            .ok_or(RecurRuleError::new("WKST must be a weekday such as MO"))?,
+                    )
+                    .is_some(),
+                "COUNT" => count
+                    .replace(parse_positive(value, "COUNT must be a positive integer")?)
+                    .is_some(),
+                "UNTIL" => until.replace(parse_until(value)?).is_some(),
+                _ => return Err(RecurRuleError::new("RRULE contains an unsupported part")),
+            };
+            if duplicate {
+                return Err(RecurRuleError::new("RRULE parts may not repeat"));
+            }
+        }
+
+        let freq = freq.ok_or(RecurRuleError::new("RRULE is missing FREQ"))?;
+        let by_day: Vec<ByDay> = by_day.unwrap_or_default();
+        let by_month_day: Vec<i32> = by_month_day.unwrap_or_default();
+        let by_month: Vec<u32> = by_month.unwrap_or_default();
+        let by_set_pos: Vec<i32> = by_set_pos.unwrap_or_default();
+
+        if !matches!(freq, Freq::Monthly | Freq::Yearly)
+            && by_day.iter().any(|day| day.ordinal.is_some())
+        {
+            return Err(RecurRuleError::new(
+                "BYDAY ordinals are only allowed with FREQ=MONTHLY or FREQ=YEARLY",
+            ));
+        }
+        let ordinal_limit = if freq == Freq::Monthly { 5 } else { 53 };
+        if by_day.iter().filter_map(|day| day.ordinal).any(|ordinal| ordinal.abs() > ordinal_limit)
+        {
+            return Err(RecurRuleError::new("BYDAY ordinal is out of range for FREQ"));
+        }
+        if freq == Freq::Weekly && !by_month_day.is_empty() {
+            return Err(RecurRuleError::new("BYMONTHDAY is not allowed with FREQ=WEEKLY"));
+        }
+        if !by_set_pos.is_empty()
+            && by_day.is_empty()
+            && by_month_day.is_empty()
+            && by_month.is_empty()
+        {
+            return Err(RecurRuleError::new("BYSETPOS requires BYDAY, BYMONTHDAY or BYMONTH"));
+        }
+        if !by_month_day.is_empty() && !month_day_reachable(&by_month_day, &by_month) {
+            return Err(RecurRuleError::new("BYMONTHDAY never falls in a BYMONTH month"));
+        }
+        let end = match (count, until) {
+            (Some(_), Some(_)) => {
+                return Err(RecurRuleError::new("COUNT and UNTIL cannot both be set"))
+            }
+            (Some(count), None) => SeriesEnd::Count(count),
+            (None, Some(until)) => SeriesEnd::Until(until),
+            (None, None) => SeriesEnd::Never,
+        };
+
+        let rule = Rrule {
+            freq,
+            interval: interval.unwrap_or(1),
+            by_day,
+            by_month_day,
+            by_month,
+            by_set_pos,
+            week_start: week_start.unwrap_or(Weekday::Mon),
+            end,
+        };
+        let probe = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap_or(NaiveDate::MIN);
+        if rule.next_occurrence(probe, probe.pred_opt().unwrap_or(probe), false).is_none() {
+            return Err(RecurRuleError::new("RRULE never produces an occurrence"));
+        }
+        Ok(rule)
+    }
+
+    /// Walks the series period by period (a day, week, month or year, stepped
+    /// by `INTERVAL`) from the period containing `dtstart`, returning the first
+    /// occurrence on or after `dtstart` and strictly after `after`.
+    fn next_occurrence(
+        &self,
+        dtstart: NaiveDate,
+        after: NaiveDate,
+        respect_until: bool,
+    ) -> Option<NaiveDate> {
+        let until = match self.end {
+            SeriesEnd::Until(until) if respect_until => Some(until),
+            _ => None,
+        };
+        let first_period = self.period_start(dtstart);
+        let mut scanned_days = 0;
+        for index in 0.. {
+            let period = self.nth_period(first_period, index)?;
+            if until.is_some_and(|until| period > until) {
+                return None;
+            }
+            scanned_days += u64::from(self.period_span(period));
+            if scanned_days > MAX_RRULE_SCAN_DAYS {
+                return None;
+            }
+            let found = self
+                .occurrences_in_period(period, dtstart)
+                .into_iter()
+                .find(|date| *date >= dtstart && *date > after);
+            if let Some(date) = found {
+                return match until {
+                    Some(until) if date > until => None,
+                    _ => Some(date),
+                };
+            }
+        }
+        None
+    }
+
+    fn period_start(&self, date: NaiveDate) -> NaiveDate {
+        match self.freq {
+            Freq::Daily => date,
+            Freq::Weekly => {
+                let offset = (7 + date.weekday().num_days_from_monday()
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                date.checked_sub_days(Days::new(u64::from(offset))).unwrap_or(date)
+            }
+            Freq::Monthly => first_of_month(date),
+            Freq::Yearly => NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap_or(date),
+        }
+    }
+
+    fn nth_period(&self, first: NaiveDate, index: u64) -> Option<NaiveDate> {
+        let steps = index.checked_mul(u64::from(self.interval))?;
+        match self.freq {
+            Freq::Daily => first.checked_add_days(Days::new(steps)),
+            Freq::Weekly => first.checked_add_days(Days::new(steps.checked_mul(7)?)),
+            Freq::Monthly => first.checked_add_months(Months::new(u32::try_from(steps).ok()?)),
+            Freq::Yearly => {
+                first.checked_add_months(Months::new(u32::try_from(steps.checked_mul(12)?).ok()?))
+            }
+        }
+    }
+
+    fn period_span(&self, period: NaiveDate) -> u32 {
+        match self.freq {
+            Freq::Daily => 1,
+            Freq::Weekly => 7,
+            Freq::Monthly => days_in_month(period),
+            Freq::Yearly => days_in_year(period),
+        }
+    }
+
+    /// Every date in `period` the rule selects, sorted, after `BYSETPOS`.
+    fn occurrences_in_period(&self, period: NaiveDate, dtstart: NaiveDate) -> Vec<NaiveDate> {
+        let dates: Vec<NaiveDate> = (0..u64::from(self.period_span(period)))
+            .filter_map(|offset| period.checked_add_days(Days::new(offset)))
+            .filter(|date| self.selects(*date, dtstart))
+            .collect();
+        if self.by_set_pos.is_empty() {
+            return dates;
+        }
+        let len = dates.len() as i32;
+        let mut picked: Vec<NaiveDate> = self
+            .by_set_pos
+            .iter()
+            .filter_map(|pos| {
+                let index = if *pos > 0 { pos - 1 } else { len + pos };
+                usize::try_from(index).ok().and_then(|index| dates.get(index).copied())
+            })
+            .collect();
+        picked.sort();
+        picked.dedup();
+        picked
+    }
+
+    fn selects(&self, date: NaiveDate, dtstart: NaiveDate) -> bool {
+        if !self.by_month.is_empty() && !self.by_month.contains(&date.month()) {
+            return false;
+        }
+        if !self.by_month_day.is_empty()
+            && !self.by_month_day.iter().any(|day| month_day_matches(date, *day))
+        {
+            return false;
+        }
+        if !self.by_day.is_empty() && !self.by_day.iter().any(|day| self.by_day_matches(date, *day))
+        {
+            return false;
+        }
+        // Without a narrowing BY* part, the series inherits DTSTART's position
+        // within the period (RFC 5545 section 3.3.10).
+        match self.freq {
+            Freq::Daily => true,
+            Freq::Weekly => !self.by_day.is_empty() || date.weekday() == dtstart.weekday(),
+            Freq::Monthly => {
+                !self.by_day.is_empty()
+                    || !self.by_month_day.is_empty()
+                    || date.day() == dtstart.day()
+            }
+            Freq::Yearly => {
+                if !self.by_day.is_empty() || !self.by_month_day.is_empty() {
+                    true
+                } else if !self.by_month.is_empty() {
+                    date.day() == dtstart.day()
+                } else {
+                    date.month() == dtstart.month() && date.day() == dtstart.day()
+                }
+            }
+        }
+    }
+
+    fn by_day_matches(&self, date: NaiveDate, by_day: ByDay) -> bool {
+        if date.weekday() != by_day.weekday {
+            return false;
+        }
+        let Some(ordinal) = by_day.ordinal else {
+            return true;
+        };
+        // Ordinals count within the month for MONTHLY (and YEARLY with
+        // BYMONTH), otherwise within the year.
+        let (position, span) = if self.freq == Freq::Monthly || !self.by_month.is_empty() {
+            (date.day(), days_in_month(date))
+        } else {
+            (date.ordinal(), days_in_year(date))
+        };
+        let nth = if ordinal > 0 { (position - 1) / 7 + 1 } else { (span - position) / 7 + 1 };
+        nth as i32 == ordinal.abs()
+    }
+}
+
+fn parse_freq(value: &str) -> Result<Freq, RecurRuleError> {
+    match value {
+        "DAILY" => Ok(Freq::Daily),
+        "WEEKLY" => Ok(Freq::Weekly),
+        "MONTHLY" => Ok(Freq::Monthly),
+        "YEARLY" => Ok(Freq::Yearly),
+        _ => Err(RecurRuleError::new("FREQ must be DAILY, WEEKLY, MONTHLY or YEARLY")),
+    }
+}
+
+fn parse_positive(value: &str, message: &'static str) -> Result<u32, RecurRuleError> {
+    value.parse::<u32>().ok().filter(|value| *value > 0).ok_or(RecurRuleError::new(message))
+}
+
+fn parse_signed_in_range(
+    value: &str,
+    limit: i32,
+    message: &'static str,
+) -> Result<i32, RecurRuleError> {
+    value
+        .parse::<i32>()
+        .ok()
+        .filter(|value| *value != 0 && value.abs() <= limit)
+        .ok_or(RecurRuleError::new(message))
+}
+
+fn parse_list<T>(
+    value: &str,
+    parse_item: impl Fn(&str) -> Result<T, RecurRuleError>,
+) -> Result<Vec<T>, RecurRuleError> {
+    value.split(',').map(parse_item).collect()
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_by_day(value: &str) -> Result<ByDay, RecurRuleError> {
+    let invalid = RecurRuleError::new("BYDAY must list weekdays such as MO, 2TU or -1FR");
+    let split = value.len().checked_sub(2).ok_or(invalid)?;
+    let (prefix, day) = value.split_at(split);
+    let weekday = parse_weekday(day).ok_or(invalid)?;
+    let ordinal = if prefix.is_empty() {
+        None
+    } else {
+        Some(prefix.parse::<i32>().ok().filter(|ordinal| *ordinal != 0).ok_or(invalid)?)
+    };
+    Ok(ByDay { ordinal, weekday })
+}
+
+fn parse_until(value: &str) -> Result<NaiveDate, RecurRuleError> {
+    let invalid =
+        RecurRuleError::new("UNTIL must be a date (YYYYMMDD) or date-time (YYYYMMDDTHHMMSS[Z])");
+    // Only the date is used, so UTC and floating date-times are alike.
+    let date = match value.split_once('T') {
+        Some((date, time)) => {
+            let digits = time.strip_suffix('Z').unwrap_or(time);
+            let valid_time = digits.len() == 6 && digits.bytes().all(|byte| byte.is_ascii_digit());
+            if !valid_time {
+                return Err(invalid);
+            }
+            date
+        }
+        None => value,
+    };
+    if date.len() != 8 {
+        return Err(invalid);
+    }
+    NaiveDate::parse_from_str(date, "%Y%m%d").map_err(|_| invalid)
+}
+
+fn add_days(date: NaiveDate, days: u64) -> NaiveDate {
+    date.checked_add_days(Days::new(days)).unwrap_or(NaiveDate::MAX)
+}
+
+fn add_months(date: NaiveDate, months: u32) -> NaiveDate {
+    date.checked_add_months(Months::new(months)).unwrap_or(NaiveDate::MAX)
+}
+
+fn next_weekday(date: NaiveDate) -> NaiveDate {
+    let mut next = add_days(date, 1);
+    while matches!(next.weekday(), Weekday::Sat | Weekday::Sun) {
+        next = add_days(next, 1);
+    }
+    next
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+fn last_day_of_month(date: NaiveDate) -> NaiveDate {
+    add_months(first_of_month(date), 1).pred_opt().unwrap_or(date)
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+    last_day_of_month(date).day()
+}
+
+fn days_in_year(date: NaiveDate) -> u32 {
+    if date.leap_year() {
+        366
+    } else {
+        365
+    }
+}
+
+fn month_day_matches(date: NaiveDate, month_day: i32) -> bool {
+    let day = date.day() as i32;
+    if month_day > 0 {
+        day == month_day
+    } else {
+        days_in_month(date) as i32 + month_day + 1 == day
+    }
+}
+
+/// Whether some `BYMONTHDAY` value exists in some month `BYMONTH` allows (any
+/// month when it is empty), so `BYMONTH=2;BYMONTHDAY=30` fails at parse time
+/// instead of scanning the whole horizon.
+fn month_day_reachable(by_month_day: &[i32], by_month: &[u32]) -> bool {
+    let fits = |month: u32| {
+        let longest = MAX_MONTH_DAYS[month as usize - 1];
+        by_month_day.iter().any(|day| day.abs() <= longest)
+    };
+    if by_month.is_empty() {
+        (1..=12).any(fits)
+    } else {
+        by_month.iter().copied().any(fits)
+    }
+}
+
+pub(super) fn parse_due_date(raw: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok()
+}
+
+pub(super) fn format_due_date(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// Trims `recur_rule` and rejects anything that is neither a preset keyword
+/// nor a supported RRULE; blank values are treated as absent.
+pub(super) fn normalize_recur_rule(raw: Option<String>) -> Result<Option<String>, RecurRuleError> {
+    let Some(value) = raw else {
+        return Ok(None);
+    };
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    RecurRule::parse(trimmed)?;
+    Ok(Some(trimmed.to_string()))
+}
+
+/// Next due date for a completed recurring task, matching the web client's
+/// `nextRecurringDueAfterCurrent`: anchor on the pre-punt due date when the
+/// task was punted (else the due date, else `today`) and take the first
+/// occurrence strictly after the current due date. `None` means the series
+/// ended (`COUNT` reached or past `UNTIL`).
+pub(super) fn next_due_after_current(
+    rule: &RecurRule,
+    due_date: Option<&str>,
+    punted_from_due_date: Option<&str>,
+    occurrences_completed: i64,
+    today: NaiveDate,
+) -> Option<NaiveDate> {
+    if rule.count_exhausted(occurrences_completed) {
+        return None;
+    }
+    let current = due_date.and_then(parse_due_date);
+    let anchor = punted_from_due_date.and_then(parse_due_date).or(current).unwrap_or(today);
+    rule.next_occurrence(anchor, current.unwrap_or(anchor).max(anchor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(raw: &str) -> NaiveDate {
+        parse_due_date(raw).expect("valid test date")
+    }
+
+    fn rule(raw: &str) -> RecurRule {
+        RecurRule::parse(raw).expect("valid test rule")
+    }
+
+    fn next(raw: &str, anchor: &str) -> Option<NaiveDate> {
+        rule(raw).next_occurrence(date(anchor), date(anchor))
+    }
+
+    fn rejection(raw: &str) -> &'static str {
+        RecurRule::parse(raw).expect_err("rule should be rejected").message
+    }
+
+    #[test]
+    fn parse_accepts_every_web_keyword_and_rejects_others() {
+        for keyword in [
+            "daily",
+            "weekdays",
+            "weekly",
+            "biweekly",
+            "monthly",
+            "quarterly",
+            "biannual",
+            "annual",
+            "lastDayOfMonth",
+        ] {
+            assert!(matches!(RecurRule::parse(keyword), Ok(RecurRule::Keyword(_))), "{keyword}");
+        }
+        assert!(RecurRule::parse("Daily").is_err());
+        assert!(RecurRule::parse("hourly").is_err());
+    }
+
+    #[test]
+    fn keyword_next_after_steps_each_rule() {
+        let base = date("2026-01-30");
+        assert_eq!(RecurKeyword::Daily.next_after(base), date("2026-01-31"));
+        assert_eq!(RecurKeyword::Weekly.next_after(base), date("2026-02-06"));
+        assert_eq!(RecurKeyword::Biweekly.next_after(base), date("2026-02-13"));
+        assert_eq!(RecurKeyword::Quarterly.next_after(base), date("2026-04-30"));
+        assert_eq!(RecurKeyword::Biannual.next_after(base), date("2026-07-30"));
+        assert_eq!(RecurKeyword::Annual.next_after(base), date("2027-01-30"));
+    }
+
+    #[test]
+    fn weekdays_keyword_skips_the_weekend() {
+        // 2026-02-06 is a Friday.
+        assert_eq!(RecurKeyword::Weekdays.next_after(date("2026-02-06")), date("2026-02-09"));
+        assert_eq!(RecurKeyword::Weekdays.next_after(date("2026-02-07")), date("2026-02-09"));
+        assert_eq!(RecurKeyword::Weekdays.next_after(date("2026-02-09")), date("2026-02-10"));
+    }
+
+    #[test]
+    fn month_keywords_clamp_to_shorter_months() {
+        assert_eq!(RecurKeyword::Monthly.next_after(date("2026-01-31")), date("2026-02-28"));
+        assert_eq!(RecurKeyword::Monthly.next_after(date("2028-01-31")), date("2028-02-29"));
+        assert_eq!(RecurKeyword::Annual.next_after(date("2028-02-29")), date("2029-02-28"));
+    }
+
+    #[test]
+    fn last_day_of_month_keyword_snaps_to_the_following_month_end() {
+        let last_day = RecurKeyword::LastDayOfMonth;
+        assert_eq!(last_day.next_after(date("2026-01-31")), date("2026-02-28"));
+        assert_eq!(last_day.next_after(date("2026-02-10")), date("2026-03-31"));
+        assert_eq!(last_day.next_after(date("2026-12-31")), date("2027-01-31"));
+    }
+
+    #[test]
+    fn rrule_accepts_prefix_and_is_case_insensitive() {
+        assert!(matches!(RecurRule::parse("RRULE:FREQ=DAILY"), Ok(RecurRule::Rrule(_))));
+        assert!(matches!(RecurRule::parse("freq=weekly;byday=mo"), Ok(RecurRule::Rrule(_))));
+    }
+
+    #[test]
+    fn rrule_every_second_tuesday() {
+        // Anchored on Tue 2026-02-03: every other week on Tuesday.
+        assert_eq!(next("FREQ=WEEKLY;INTERVAL=2;BYDAY=TU", "2026-02-03"), Some(date("2026-02-17")));
+    }
+
+    #[test]
+    fn rrule_second_tuesday_of_the_month() {
+        assert_eq!(next("FREQ=MONTHLY;BYDAY=2TU", "2026-02-10"), Some(date("2026-03-10")));
+    }
+
+    #[test]
+    fn rrule_first_weekday_of_the_month() {
+        // 2026-08-01 is a Saturday, so the first weekday is Monday 08-03.
+        let first_weekday = "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=1";
+        assert_eq!(next(first_weekday, "2026-07-01"), Some(date("2026-08-03")));
+    }
+
+    #[test]
+    fn rrule_last_day_of_month_and_last_friday() {
+        assert_eq!(next("FREQ=MONTHLY;BYMONTHDAY=-1", "2026-01-31"), Some(date("2026-02-28")));
+        assert_eq!(next("FREQ=MONTHLY;BYDAY=-1FR", "2026-01-30"), Some(date("2026-02-27")));
+    }
+
+    #[test]
+    fn rrule_monthly_without_by_parts_skips_months_missing_the_day() {
+        assert_eq!(next("FREQ=MONTHLY", "2026-01-31"), Some(date("2026-03-31")));
+    }
+
+    #[test]
+    fn rrule_yearly_with_by_month_and_ordinal_weekday() {
+        // US Thanksgiving: fourth Thursday of November.
+        let thanksgiving = "FREQ=YEARLY;BYMONTH=11;BYDAY=4TH";
+        assert_eq!(next(thanksgiving, "2026-11-26"), Some(date("2027-11-25")));
+    }
+
+    #[test]
+    fn rrule_every_three_days_until_end_of_year() {
+        let every_three_days = "FREQ=DAILY;INTERVAL=3;UNTIL=20261231";
+        assert_eq!(next(every_three_days, "2026-12-25"), Some(date("2026-12-28")));
+        assert_eq!(next(every_three_days, "2026-12-28"), Some(date("2026-12-31")));
+        assert_eq!(next(every_three_days, "2026-12-31"), None);
+    }
+
+    #[test]
+    fn rrule_count_ends_the_series_after_the_final_completion() {
+        let counted = rule("FREQ=WEEKLY;COUNT=3");
+        let due = Some("2026-02-02");
+        let today = date("2026-02-02");
+        assert!(next_due_after_current(&counted, due, None, 1, today).is_some());
+        assert_eq!(next_due_after_current(&counted, due, None, 2, today), None);
+    }
+
+    #[test]
+    fn rrule_rejections_carry_specific_messages() {
+        assert_eq!(rejection("INTERVAL=2"), "RRULE is missing FREQ");
+        assert_eq!(rejection("FREQ=HOURLY"), "FREQ must be DAILY, WEEKLY, MONTHLY or YEARLY");
+        assert_eq!(rejection("FREQ=DAILY;INTERVAL=0"), "INTERVAL must be a positive integer");
+        assert_eq!(
+            rejection("FREQ=WEEKLY;BYDAY=2TU"),
+            "BYDAY ordinals are only allowed with FREQ=MONTHLY or FREQ=YEARLY"
+        );
+        assert_eq!(
+            rejection("FREQ=MONTHLY;BYDAY=XX"),
+            "BYDAY must list weekdays such as MO, 2TU or -1FR"
+        );
+        assert_eq!(
+            rejection("FREQ=MONTHLY;BYMONTHDAY=32"),
+            "BYMONTHDAY values must be 1 to 31 or -31 to -1"
+        );
+        assert_eq!(
+            rejection("FREQ=WEEKLY;BYMONTHDAY=1"),
+            "BYMONTHDAY is not allowed with FREQ=WEEKLY"
+        );
+        assert_eq!(
+            rejection("FREQ=MONTHLY;BYSETPOS=1"),
+            "BYSETPOS requires BYDAY, BYMONTHDAY or BYMONTH"
+        );
+        assert_eq!(
+            rejection("FREQ=DAILY;COUNT=2;UNTIL=20261231"),
+            "COUNT and UNTIL cannot both be set"
+        );
+        assert_eq!(
+            rejection("FREQ=DAILY;UNTIL=2026-12-31"),
+            "UNTIL must be a date (YYYYMMDD) or date-time (YYYYMMDDTHHMMSS[Z])"
+        );
+        assert_eq!(rejection("FREQ=DAILY;BYHOUR=9"), "RRULE contains an unsupported part");
+        assert_eq!(rejection("FREQ=DAILY;FREQ=WEEKLY"), "RRULE parts may not repeat");
+        assert_eq!(
+            rejection("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30"),
+            "BYMONTHDAY never falls in a BYMONTH month"
+        );
+        assert_eq!(
+            rejection("FREQ=MONTHLY;BYDAY=5MO;BYMONTHDAY=1"),
+            "RRULE never produces an occurrence"
+        );
+        assert_eq!(rejection("fortnightly"), "recur_rule must be a preset keyword or an RRULE");
+    }
+
+    #[test]
+    fn rrule_sparse_leap_day_weekday_is_found_within_the_scan_horizon() {
+        // Feb 29 on a Monday: 2016, then not again until 2044.
+        let leap_monday = "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=29;BYDAY=MO";
+        assert_eq!(next(leap_monday, "2016-02-29"), Some(date("2044-02-29")));
+    }
+
+    #[test]
+    fn rrule_until_accepts_utc_and_floating_date_times() {
+        assert!(RecurRule::parse("FREQ=DAILY;UNTIL=20261231T235959Z").is_ok());
+        assert!(RecurRule::parse("FREQ=DAILY;UNTIL=20261231T235959").is_ok());
+        assert_eq!(
+            RecurRule::parse("FREQ=DAILY;UNTIL=20261231T235959"),
+            RecurRule::parse("FREQ=DAILY;UNTIL=20261231")
+        );
+        for until in ["20261231T2359", "20261231T235959ZZ", "20261231T"] {
+            assert!(RecurRule::parse(&format!("FREQ=DAILY;UNTIL={until}")).is_err(), "{until}");
+        }
+    }
+
+    #[test]
+    fn normalize_recur_rule_trims_blanks_and_rejects_unknown() {
+        assert_eq!(normalize_recur_rule(None), Ok(None));
+        assert_eq!(normalize_recur_rule(Some("  ".into())), Ok(None));
+        assert_eq!(normalize_recur_rule(Some(" weekly ".into())), Ok(Some("weekly".into())));
+        assert_eq!(
+            normalize_recur_rule(Some(" RRULE:FREQ=DAILY ".into())),
+            Ok(Some("RRULE:FREQ=DAILY".into()))
+        );
+        assert!(normalize_recur_rule(Some("fortnightly".into())).is_err());
+    }
+
+    #[test]
+    fn next_due_after_current_anchors_on_punted_from_date() {
+        // Punted from Feb 5 to Feb 6: completing lands on Feb 12, not Feb 13.
+        let weekly = rule("weekly");
+        let next = next_due_after_current(
+            &weekly,
+            Some("2026-02-06"),
+            Some("2026-02-05"),
+            0,
+            date("2026-02-06"),
+        );
+        assert_eq!(next, Some(date("2026-02-12")));
+    }
+
+    #[test]
+    fn next_due_after_current_always_lands_after_the_current_due_date() {
+        // A daily task punted from Feb 5 and now due Feb 6 must not land on Feb 6 again.
+        let daily = rule("daily");
+        let next = next_due_after_current(
+            &daily,
+            Some("2026-02-06"),
+            Some("2026-02-05"),
+            0,
+            date("2026-02-06"),
+        );
+        assert_eq!(next, Some(date("2026-02-07")));
+    }
+
+    #[test]
+    fn next_due_after_current_falls_back_to_today_without_a_due_date() {
+        let next = next_due_after_current(&rule("daily"), None, None, 0, date("2026-03-01"));
+        assert_eq!(next, Some(date("2026-03-02")));
+        let weekly_friday = rule("FREQ=WEEKLY;BYDAY=FR");
+        // 2026-03-01 is a Sunday.
+        let next = next_due_after_current(&weekly_friday, None, None, 0, date("2026-03-01"));
+        assert_eq!(next, Some(date("2026-03-06")));
+    }
+}
@@ -0,0 +1,40 @@
+//! Password reset token lifetime and storage.
+//!
+//! Free of web-framework types so the `reset_token` binary can include it
+//! (`#[path]`) and issue tokens exactly as `password_reset.rs` does.
+
+use sqlx::SqliteConnection;
+use uuid::Uuid;
+
+use super::opaque_token::{new_opaque_token, opaque_token_hash};
+
+/// One hour.
+pub(super) const PASSWORD_RESET_TTL_MS: i64 = 60 * 60 * 1000;
+
+/// Replaces any unused token of `user_id` with a fresh one; returns it and
+/// its expiry.
+pub(super) async fn insert_reset_token(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    space_id: &str,
+) -> Result<(String, i64), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let token = new_opaque_token("pr_");
+    let expires_ts = now + PASSWORD_RESET_TTL_MS;
+    sqlx::query("delete from password_reset where user_id = ?1 and used_ts is null")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        "insert into password_reset (id, user_id, space_id, token_hash, created_ts, expires_ts) values (?1, ?2, ?3, ?4, ?5, ?6)",
+    )
+    .bind(format!("pr-{}", Uuid::new_v4()))
+    .bind(user_id)
+    .bind(space_id)
+    .bind(opaque_token_hash(&token))
+    .bind(now)
+    .bind(expires_ts)
+    .execute(&mut *conn)
+    .await?;
+    Ok((token, expires_ts))
+}
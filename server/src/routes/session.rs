@@ -0,0 +1,475 @@
+//! Refresh-token sessions and per-device session management.
+//!
+//! Access JWTs (`issue_token`) are short-lived; a client keeps itself signed
+//! in by trading its opaque refresh token at `POST /auth/refresh` for a new
+//! access token and a new refresh token. Each login starts a device session
+//! (`device_session`, one per signed-in device) whose refresh tokens form a
+//! family in `session`; every refresh rotates the token within it. Only a
+//! hash of the refresh token is stored (`session.token_hash`). Presenting a
+//! token that was already rotated means two parties hold it, so the device
+//! session is revoked and the event is audited.
+//!
+//! Access tokens carry the device session as their `sid` claim and
+//! `ctx_from_headers` refuses them once it is revoked, so signing out one
+//! device (`DELETE /auth/sessions/:id`, or an admin's
+//! `DELETE /auth/members/:user_id/sessions/:id`) takes effect on that
+//! device's next request. `auth_revoke_sessions`, `auth_change_password`,
+//! `auth_set_member_password` and `auth_delete_member` revoke every session
+//! of the affected user alongside their `token_version` checks.
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, State},
+    http::{header::USER_AGENT, request::Parts, HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+use std::convert::Infallible;
+use uuid::Uuid;
+
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::auth::TokenResponse;
+use super::opaque_token::{new_opaque_token, opaque_token_hash};
+use super::types::{begin_write, ctx_from_headers, issue_token, AdminError, AppState, RequestCtx};
+
+/// Lifetime of one refresh token. Each rotation starts a fresh window, so a
+/// device that opens the app at least this often stays signed in.
+const REFRESH_TOKEN_TTL_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+const MAX_DEVICE_LABEL_CHARS: usize = 80;
+const MAX_USER_AGENT_CHARS: usize = 256;
+
+#[derive(Deserialize)]
+pub(super) struct RefreshBody {
+    pub(super) refresh_token: String,
+}
+
+/// The request's `User-Agent`, recorded on the device session at sign-in.
+pub(super) struct UserAgent(pub(super) Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for UserAgent {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent =
+            parts.headers.get(USER_AGENT).and_then(|value| value.to_str().ok()).map(str::to_string);
+        Ok(UserAgent(user_agent))
+    }
+}
+
+/// What the client told us about the device signing in.
+pub(super) struct DeviceInfo<'a> {
+    pub(super) label: Option<&'a str>,
+    pub(super) user_agent: Option<&'a str>,
+}
+
+/// Trimmed and capped; blank becomes `None`.
+fn clean_text(value: Option<&str>, max_chars: usize) -> Option<String> {
+    let value = value?.trim();
+    (!value.is_empty()).then(|| value.chars().take(max_chars).collect())
+}
+
+/// A fresh sign-in: the device session and its first token pair.
+pub(super) struct StartedSession {
+    pub(super) session_id: String,
+    pub(super) refresh_token: String,
+}
+
+#[derive(FromRow)]
+struct SessionRow {
+    id: String,
+    family_id: String,
+    user_id: String,
+    space_id: String,
+    expires_ts: i64,
+    rotated_ts: Option<i64>,
+    revoked_ts: Option<i64>,
+}
+
+#[derive(Serialize, FromRow)]
+pub(super) struct DeviceSessionRow {
+    pub(super) id: String,
+    pub(super) device_label: Option<String>,
+    pub(super) user_agent: Option<String>,
+    pub(super) created_ts: i64,
+    pub(super) last_seen_ts: i64,
+    pub(super) expires_ts: i64,
+    /// True for the session the request itself was made with.
+    #[sqlx(skip)]
+    pub(super) current: bool,
+}
+
+async fn insert_refresh_token(
+    conn: &mut SqliteConnection,
+    session_id: &str,
+    user_id: &str,
+    space_id: &str,
+    now: i64,
+) -> Result<String, StatusCode> {
+    let token = new_opaque_token("rt_");
+    let expires_ts = now + REFRESH_TOKEN_TTL_MS;
+    sqlx::query(
+        "insert into session (id, family_id, user_id, space_id, token_hash, created_ts, expires_ts) values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )
+    .bind(format!("sess-{}", Uuid::new_v4()))
+    .bind(session_id)
+    .bind(user_id)
+    .bind(space_id)
+    .bind(opaque_token_hash(&token))
+    .bind(now)
+    .bind(expires_ts)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query("update device_session set last_seen_ts = ?1, expires_ts = ?2 where id = ?3")
+        .bind(now)
+        .bind(expires_ts)
+        .bind(session_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(token)
+}
+
+/// Starts a device session for a fresh sign-in.
+pub(super) async fn start_session(
+    pool: &SqlitePool,
+    user_id: &str,
+    space_id: &str,
+    device: DeviceInfo<'_>,
+) -> Result<StartedSession, StatusCode> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let session_id = format!("fam-{}", Uuid::new_v4());
+    let mut tx = begin_write(pool).await?;
+    sqlx::query(
+        "insert into device_session (id, user_id, space_id, device_label, user_agent, created_ts, last_seen_ts, expires_ts) values (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?6)",
+    )
+    .bind(&session_id)
+    .bind(user_id)
+    .bind(space_id)
+    .bind(clean_text(device.label, MAX_DEVICE_LABEL_CHARS))
+    .bind(clean_text(device.user_agent, MAX_USER_AGENT_CHARS))
+    .bind(now)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let refresh_token = insert_refresh_token(&mut tx, &session_id, user_id, space_id, now).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StartedSession { session_id, refresh_token })
+}
+
+/// After a sign-out-everywhere or password change: starts a new device
+/// session for the acting device, carrying over the label and user agent of
+/// the (now revoked) session its request was made with.
+pub(super) async fn restart_current_session(
+    state: &AppState,
+    ctx: &RequestCtx,
+    token_version: i64,
+) -> Result<TokenResponse, StatusCode> {
+    let previous: Option<(Option<String>, Option<String>)> = match &ctx.session_id {
+        Some(session_id) => sqlx::query_as(
+            "select device_label, user_agent from device_session where id = ?1 and user_id = ?2",
+        )
+        .bind(session_id)
+        .bind(&ctx.user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => None,
+    };
+    let (label, user_agent) = previous.unwrap_or_default();
+    let device = DeviceInfo { label: label.as_deref(), user_agent: user_agent.as_deref() };
+    let session = start_session(&state.pool, &ctx.user_id, &ctx.space_id, device).await?;
+    let token = issue_token(
+        &ctx.user_id,
+        &ctx.space_id,
+        token_version,
+        Some(&session.session_id),
+        &state.jwt_secret,
+    )?;
+    Ok(TokenResponse { token, refresh_token: session.refresh_token })
+}
+
+async fn revoke_device_session_for_conn(
+    conn: &mut SqliteConnection,
+    session_id: &str,
+    now: i64,
+) -> Result<u64, StatusCode> {
+    let revoked = sqlx::query(
+        "update device_session set revoked_ts = ?1 where id = ?2 and revoked_ts is null",
+    )
+    .bind(now)
+    .bind(session_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .rows_affected();
+    sqlx::query("update session set revoked_ts = ?1 where family_id = ?2 and revoked_ts is null")
+        .bind(now)
+        .bind(session_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(revoked)
+}
+
+/// Revokes every live session of `user_id`, in one space or (`None`) in all
+/// of them; `token_version` is per user, so self-service revocation spans
+/// spaces too. Returns the number of device sessions revoked.
+pub(super) async fn revoke_user_sessions(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    space_id: Option<&str>,
+) -> Result<u64, StatusCode> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let session_ids: Vec<String> = sqlx::query_scalar(
+        "select id from device_session where user_id = ?1 and (?2 is null or space_id = ?2) and revoked_ts is null",
+    )
+    .bind(user_id)
+    .bind(space_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut revoked = 0;
+    for session_id in &session_ids {
+        revoked += revoke_device_session_for_conn(&mut *conn, session_id, now).await?;
+    }
+    Ok(revoked)
+}
+
+/// Unauthenticated (the access token may already have expired): the refresh
+/// token is the credential. `401` for an unknown, expired or revoked token,
+/// for a reused one (which also revokes its device session) and when the
+/// member has since been removed from the space.
+pub(super) async fn auth_refresh(
+    State(state): State<AppState>,
+    Json(body): Json<RefreshBody>,
+) -> Result<Json<TokenResponse>, StatusCode> {
+    let presented = body.refresh_token.trim();
+    if presented.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut tx = begin_write(&state.pool).await?;
+    let session = sqlx::query_as::<_, SessionRow>(
+        "select id, family_id, user_id, space_id, expires_ts, rotated_ts, revoked_ts from session where token_hash = ?1",
+    )
+    .bind(opaque_token_hash(presented))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::UNAUTHORIZED)?;
+    if session.revoked_ts.is_some() || session.expires_ts <= now {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    if session.rotated_ts.is_some() {
+        let revoked = revoke_device_session_for_conn(&mut tx, &session.family_id, now).await?;
+        record_audit(
+            &mut tx,
+            AuditEntry {
+                space_id: &session.space_id,
+                actor_user_id: None,
+                action: AuditAction::RefreshTokenReused,
+                target_user_id: Some(&session.user_id),
+                detail: json!({ "session_id": session.family_id, "sessions_revoked": revoked }),
+            },
+        )
+        .await?;
+        tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tracing::warn!(
+            user_id = %session.user_id,
+            session_id = %session.family_id,
+            "rotated refresh token reused; device session revoked"
+        );
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let token_version: i64 = sqlx::query_scalar(
+        "select u.token_version from membership m join user u on u.id = m.user_id where m.space_id = ?1 and m.user_id = ?2 limit 1",
+    )
+    .bind(&session.space_id)
+    .bind(&session.user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    sqlx::query("update session set rotated_ts = ?1 where id = ?2")
+        .bind(now)
+        .bind(&session.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let refresh_token =
+        insert_refresh_token(&mut tx, &session.family_id, &session.user_id, &session.space_id, now)
+            .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let token = issue_token(
+        &session.user_id,
+        &session.space_id,
+        token_version,
+        Some(&session.family_id),
+        &state.jwt_secret,
+    )?;
+    Ok(Json(TokenResponse { token, refresh_token }))
+}
+
+/// Unauthenticated sign-out: revokes the device session the refresh token
+/// belongs to, so neither it nor the access tokens carrying its `sid` work
+/// any more. Always `204` (`400` for a blank token), whether or not the
+/// token was known, rotated or already revoked.
+pub(super) async fn auth_logout(
+    State(state): State<AppState>,
+    Json(body): Json<RefreshBody>,
+) -> Result<StatusCode, StatusCode> {
+    let presented = body.refresh_token.trim();
+    if presented.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut tx = begin_write(&state.pool).await?;
+    let family_id: Option<String> =
+        sqlx::query_scalar("select family_id from session where token_hash = ?1")
+            .bind(opaque_token_hash(presented))
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Some(family_id) = family_id {
+        revoke_device_session_for_conn(&mut tx, &family_id, now).await?;
+    }
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Live (unrevoked, unexpired) sessions of `user_id` in `space_id`, most
+/// recently seen first.
+async fn list_device_sessions(
+    pool: &SqlitePool,
+    user_id: &str,
+    space_id: &str,
+    current_session_id: Option<&str>,
+) -> Result<Vec<DeviceSessionRow>, StatusCode> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut rows = sqlx::query_as::<_, DeviceSessionRow>(
+        "select id, device_label, user_agent, created_ts, last_seen_ts, expires_ts from device_session where user_id = ?1 and space_id = ?2 and revoked_ts is null and expires_ts > ?3 order by last_seen_ts desc, id asc",
+    )
+    .bind(user_id)
+    .bind(space_id)
+    .bind(now)
+    .fetch_all(pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    for row in &mut rows {
+        row.current = current_session_id == Some(row.id.as_str());
+    }
+    Ok(rows)
+}
+
+/// Revokes one of `user_id`'s sessions in `space_id`; `404` if there is no
+/// such live session.
+async fn revoke_device_session(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    space_id: &str,
+    session_id: &str,
+) -> Result<DeviceSessionRow, StatusCode> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let session = sqlx::query_as::<_, DeviceSessionRow>(
+        "select id, device_label, user_agent, created_ts, last_seen_ts, expires_ts from device_session where id = ?1 and user_id = ?2 and space_id = ?3 and revoked_ts is null",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .bind(space_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    revoke_device_session_for_conn(&mut *conn, session_id, now).await?;
+    Ok(session)
+}
+
+pub(super) async fn auth_sessions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<DeviceSessionRow>>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let rows =
+        list_device_sessions(&state.pool, &ctx.user_id, &ctx.space_id, ctx.session_id.as_deref())
+            .await?;
+    Ok(Json(rows))
+}
+
+/// Signs out one of the caller's devices. Revoking the current session is
+/// allowed and signs this device out.
+pub(super) async fn auth_revoke_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    revoke_device_session(&mut tx, &ctx.user_id, &ctx.space_id, &session_id).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Admin-only; `404` unless `user_id` is a member of the caller's space.
+async fn require_member_of_admin_space(
+    state: &AppState,
+    headers: &HeaderMap,
+    user_id: &str,
+) -> Result<RequestCtx, AdminError> {
+    let ctx = ctx_from_headers(headers, state).await?;
+    ctx.require_admin()?;
+    let member: Option<i64> =
+        sqlx::query_scalar("select 1 from membership where space_id = ?1 and user_id = ?2")
+            .bind(&ctx.space_id)
+            .bind(user_id)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if member.is_none() {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    Ok(ctx)
+}
+
+pub(super) async fn auth_member_sessions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<DeviceSessionRow>>, AdminError> {
+    let ctx = require_member_of_admin_space(&state, &headers, &user_id).await?;
+    let rows =
+        list_device_sessions(&state.pool, &user_id, &ctx.space_id, ctx.session_id.as_deref())
+            .await?;
+    Ok(Json(rows))
+}
+
+pub(super) async fn auth_revoke_member_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((user_id, session_id)): Path<(String, String)>,
+) -> Result<StatusCode, AdminError> {
+    let ctx = require_member_of_admin_space(&state, &headers, &user_id).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    let session = revoke_device_session(&mut tx, &user_id, &ctx.space_id, &session_id).await?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::SessionRevoked,
+            target_user_id: Some(&user_id),
+            detail: json!({ "session_id": session.id, "device_label": session.device_label }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
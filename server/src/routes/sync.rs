@@ -1,50 +1,179 @@
 use axum::{
     extract::State,
     http::{HeaderMap, StatusCode},
-    routing::post,
+    routing::{get, post},
     Json, Router,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqliteConnection;
 
-use super::lists::{get_lists_for_ctx, ListRow};
+use super::conflict::{merge_with_base, MergeOutcome};
+use super::lists::{
+    create_list_for_ctx, delete_list_for_ctx, get_lists_changed_for_ctx, get_lists_for_ctx,
+    update_list_for_ctx, CreateList, DeletedListRow, ListRow, UpdateList,
+};
+use super::sync_compaction::prune_watermark_for_ctx;
+use super::sync_events::sync_events;
+use super::sync_ops::{prune_expired_ops, record_outcome, recorded_outcome, PushOutcome};
+use super::sync_page::{read_pull_page, PullPosition, MAX_PULL_PAGE_SIZE};
+use super::sync_ws::sync_ws;
 use super::tasks::{
-    create_task_for_ctx, get_tasks_for_ctx, update_task_meta_for_ctx, update_task_status_for_ctx,
-    CreateTask, DeletedTaskRow, TaskRow, UpdateTaskMeta, UpdateTaskStatus,
+    create_task_for_ctx, delete_task_for_ctx, get_tasks_changed_for_ctx, update_task_meta_for_ctx,
+    update_task_status_for_ctx, CreateTask, DeletedTaskRow, TaskError, TaskRow, UpdateTaskMeta,
+    UpdateTaskStatus,
 };
-use super::types::{app_state, ctx_from_headers, AppState, Role};
+use super::types::{app_state, begin_write, ctx_from_headers, AppState, RequestCtx, Role};
+
+pub(super) const PROTOCOL_V1: &str = "delta-v1";
+pub(super) const PROTOCOL_V2: &str = "delta-v2";
 
 #[derive(Deserialize)]
 pub(super) struct SyncPullBody {
     pub(super) since_ts: Option<i64>,
+    /// `"delta-v2"` selects the change-sequence cursor (`since_seq` /
+    /// `cursor_seq`); absent means `delta-v1` for clients that predate it.
+    pub(super) protocol: Option<String>,
+    pub(super) since_seq: Option<i64>,
+    /// `delta-v1` only: caps tasks plus tombstones per response (clamped to
+    /// `MAX_PULL_PAGE_SIZE`). Without it the pull is returned in one page.
+    pub(super) limit: Option<i64>,
+    /// `next_page_token` from the previous page of the same pull.
+    pub(super) page_token: Option<String>,
 }
 
 #[derive(Serialize)]
 pub(super) struct SyncPullResponse {
     pub(super) protocol: &'static str,
     pub(super) cursor_ts: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) cursor_seq: Option<i64>,
     pub(super) lists: Vec<ListRow>,
     pub(super) tasks: Vec<TaskRow>,
     pub(super) deleted_tasks: Vec<DeletedTaskRow>,
+    pub(super) deleted_lists: Vec<DeletedListRow>,
+    /// Present when more rows remain below `cursor_ts`; pass it back as
+    /// `page_token`. Every page of one pull reports the same `cursor_ts`, and
+    /// `lists`/`deleted_lists` are only sent on the first page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) next_page_token: Option<String>,
+    /// The client's cursor predates pruned tombstones, so this is a full
+    /// snapshot instead of the requested delta: replace the local cache
+    /// rather than merging into it. `delta-v1` checks every page against the
+    /// `since_ts` its pull started from; a continuation page that trips it is
+    /// answered with the first page of a full snapshot instead.
+    pub(super) full_resync_required: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub(super) enum SyncPushChange {
-    CreateTask { op_id: String, body: CreateTask },
-    UpdateTask { op_id: String, task_id: String, body: UpdateTaskMeta },
-    UpdateTaskStatus { op_id: String, task_id: String, status: String },
+    CreateTask {
+        op_id: String,
+        body: CreateTask,
+    },
+    /// `base_updated_ts` opts into field-level conflict detection: the body
+    /// is then a patch of the fields edited since the client last saw the
+    /// task at that `updated_ts` (see `conflict::merge_with_base`). Without
+    /// it the push is applied arrival-order, as before.
+    UpdateTask {
+        op_id: String,
+        task_id: String,
+        body: UpdateTaskMeta,
+        base_updated_ts: Option<i64>,
+    },
+    UpdateTaskStatus {
+        op_id: String,
+        task_id: String,
+        status: String,
+    },
+    /// Same ownership rules and tombstone as `DELETE /tasks/:id`; applied
+    /// deletions produce no row.
+    DeleteTask {
+        op_id: String,
+        task_id: String,
+    },
+    CreateList {
+        op_id: String,
+        body: CreateList,
+    },
+    UpdateList {
+        op_id: String,
+        list_id: String,
+        body: UpdateList,
+    },
+    DeleteList {
+        op_id: String,
+        list_id: String,
+    },
+}
+
+impl SyncPushChange {
+    fn op_id(&self) -> &str {
+        match self {
+            SyncPushChange::CreateTask { op_id, .. }
+            | SyncPushChange::UpdateTask { op_id, .. }
+            | SyncPushChange::UpdateTaskStatus { op_id, .. }
+            | SyncPushChange::DeleteTask { op_id, .. }
+            | SyncPushChange::CreateList { op_id, .. }
+            | SyncPushChange::UpdateList { op_id, .. }
+            | SyncPushChange::DeleteList { op_id, .. } => op_id,
+        }
+    }
+
+    /// Fingerprint of the change as pushed (kind, target id and body), stored
+    /// next to its `op_id` so a reused op_id carrying a different edit is not
+    /// mistaken for a replay.
+    fn payload_hash(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(Sha256::digest(json))
+    }
 }
 
 #[derive(Deserialize)]
 pub(super) struct SyncPushBody {
     pub(super) changes: Vec<SyncPushChange>,
+    /// Apply the whole batch in one transaction: if any change is rejected,
+    /// none are applied and `rejected` holds only the failing change.
+    #[serde(default)]
+    pub(super) atomic: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub(super) struct SyncPushRejected {
     pub(super) op_id: String,
     pub(super) status: u16,
     pub(super) error: String,
+    /// Set on a `409` conflict: the fields both sides changed since the
+    /// push's `base_updated_ts`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) conflicting_fields: Vec<String>,
+    /// Set on a `409` conflict: the server's current row to reconcile against.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) current: Option<TaskRow>,
+}
+
+impl SyncPushRejected {
+    fn new(op_id: String, err: TaskError) -> Self {
+        SyncPushRejected {
+            op_id,
+            status: err.status().as_u16(),
+            error: err.reason().to_string(),
+            conflicting_fields: Vec::new(),
+            current: None,
+        }
+    }
+
+    fn conflict(op_id: String, fields: Vec<&'static str>, current: TaskRow) -> Self {
+        SyncPushRejected {
+            op_id,
+            status: StatusCode::CONFLICT.as_u16(),
+            error: "concurrent edit conflict".to_string(),
+            conflicting_fields: fields.into_iter().map(str::to_string).collect(),
+            current: Some(current),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -52,28 +181,39 @@ pub(super) struct SyncPushResponse {
     pub(super) protocol: &'static str,
     pub(super) cursor_ts: i64,
     pub(super) applied: Vec<TaskRow>,
+    /// Resulting rows of applied `create_list`/`update_list` changes, in push
+    /// order. Applied deletions produce no row.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) applied_lists: Vec<ListRow>,
     pub(super) rejected: Vec<SyncPushRejected>,
 }
 
 async fn sync_cursor_for_ctx(
-    state: &AppState,
-    ctx: &super::types::RequestCtx,
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
 ) -> Result<i64, StatusCode> {
     if ctx.role == Role::Admin {
         let task_cursor: i64 =
             sqlx::query_scalar("select coalesce(max(updated_ts), 0) from task where space_id = ?1")
                 .bind(&ctx.space_id)
-                .fetch_one(&state.pool)
+                .fetch_one(&mut *conn)
                 .await
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         let tombstone_cursor: i64 = sqlx::query_scalar(
             "select coalesce(max(deleted_ts), 0) from task_tombstone where space_id = ?1",
         )
         .bind(&ctx.space_id)
-        .fetch_one(&state.pool)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        return Ok(task_cursor.max(tombstone_cursor));
+        let list_cursor: i64 =
+            sqlx::query_scalar("select coalesce(max(updated_ts), 0) from list where space_id = ?1")
+                .bind(&ctx.space_id)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let list_tombstone_cursor = list_tombstone_cursor_for_ctx(conn, ctx).await?;
+        return Ok(task_cursor.max(tombstone_cursor).max(list_cursor).max(list_tombstone_cursor));
     }
 
     let task_cursor: i64 = sqlx::query_scalar(
@@ -81,7 +221,7 @@ async fn sync_cursor_for_ctx(
     )
     .bind(&ctx.space_id)
     .bind(&ctx.user_id)
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let tombstone_cursor: i64 = sqlx::query_scalar(
@@ -89,31 +229,121 @@ async fn sync_cursor_for_ctx(
     )
     .bind(&ctx.space_id)
     .bind(&ctx.user_id)
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(task_cursor.max(tombstone_cursor))
+    let list_cursor: i64 = sqlx::query_scalar(
+        "select coalesce(max(l.updated_ts), 0) from list l join list_grant g on g.list_id = l.id and g.space_id = l.space_id where l.space_id = ?1 and g.user_id = ?2",
+    )
+    .bind(&ctx.space_id)
+    .bind(&ctx.user_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let list_tombstone_cursor = list_tombstone_cursor_for_ctx(conn, ctx).await?;
+    Ok(task_cursor.max(tombstone_cursor).max(list_cursor).max(list_tombstone_cursor))
+}
+
+/// List tombstones are scoped by the grants snapshotted at deletion (see
+/// `delete_list_for_ctx`), like live lists.
+async fn list_tombstone_cursor_for_ctx(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+) -> Result<i64, StatusCode> {
+    let sql = if ctx.role == Role::Admin {
+        "select coalesce(max(deleted_ts), 0) from list_tombstone where space_id = ?1"
+    } else {
+        "select coalesce(max(t.deleted_ts), 0) from list_tombstone t join list_tombstone_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2"
+    };
+    sqlx::query_scalar(sql)
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .fetch_one(conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn deleted_lists_since_ts(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    since_ts: i64,
+    cursor_ts: i64,
+) -> Result<Vec<DeletedListRow>, StatusCode> {
+    let sql = if ctx.role == Role::Admin {
+        "select list_id as id, deleted_ts from list_tombstone where space_id = ?1 and deleted_ts >= ?3 and deleted_ts <= ?4 order by deleted_ts asc, list_id asc"
+    } else {
+        "select t.list_id as id, t.deleted_ts from list_tombstone t join list_tombstone_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2 and t.deleted_ts >= ?3 and t.deleted_ts <= ?4 order by t.deleted_ts asc, t.list_id asc"
+    };
+    sqlx::query_as::<_, DeletedListRow>(sql)
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .bind(since_ts)
+        .bind(cursor_ts)
+        .fetch_all(conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn deleted_lists_changed_for_ctx(
+    state: &AppState,
+    ctx: &RequestCtx,
+    since_seq: i64,
+    cursor_seq: i64,
+) -> Result<Vec<DeletedListRow>, StatusCode> {
+    let sql = if ctx.role == Role::Admin {
+        "select list_id as id, deleted_ts from list_tombstone where space_id = ?1 and change_seq > ?3 and change_seq <= ?4 order by change_seq asc"
+    } else {
+        "select t.list_id as id, t.deleted_ts from list_tombstone t join list_tombstone_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2 and t.change_seq > ?3 and t.change_seq <= ?4 order by t.change_seq asc"
+    };
+    sqlx::query_as::<_, DeletedListRow>(sql)
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .bind(since_seq)
+        .bind(cursor_seq)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// The space's current change sequence. Every write that commits at or
+/// below this value is already visible, because the counter bump and the row
+/// stamp happen in the same trigger.
+pub(super) async fn sync_seq_for_ctx(
+    state: &AppState,
+    ctx: &RequestCtx,
+) -> Result<i64, StatusCode> {
+    sqlx::query_scalar("select coalesce((select seq from sync_counter where space_id = ?1), 0)")
+        .bind(&ctx.space_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
 
-async fn deleted_tasks_for_ctx(
+async fn deleted_tasks_changed_for_ctx(
     state: &AppState,
-    ctx: &super::types::RequestCtx,
+    ctx: &RequestCtx,
+    since_seq: i64,
+    cursor_seq: i64,
 ) -> Result<Vec<DeletedTaskRow>, StatusCode> {
     if ctx.role == Role::Admin {
         return sqlx::query_as::<_, DeletedTaskRow>(
-            "select task_id as id, deleted_ts from task_tombstone where space_id = ?1 order by deleted_ts asc",
+            "select task_id as id, deleted_ts from task_tombstone where space_id = ?1 and change_seq > ?2 and change_seq <= ?3 order by change_seq asc",
         )
         .bind(&ctx.space_id)
+        .bind(since_seq)
+        .bind(cursor_seq)
         .fetch_all(&state.pool)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
     }
 
     sqlx::query_as::<_, DeletedTaskRow>(
-        "select t.task_id as id, t.deleted_ts from task_tombstone t join list_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2 order by t.deleted_ts asc",
+        "select t.task_id as id, t.deleted_ts from task_tombstone t join list_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2 and t.change_seq > ?3 and t.change_seq <= ?4 order by t.change_seq asc",
     )
     .bind(&ctx.space_id)
     .bind(&ctx.user_id)
+    .bind(since_seq)
+    .bind(cursor_seq)
     .fetch_all(&state.pool)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
@@ -125,17 +355,108 @@ pub(super) async fn sync_pull(
     Json(body): Json<SyncPullBody>,
 ) -> Result<Json<SyncPullResponse>, StatusCode> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-
-    let lists = get_lists_for_ctx(&state, &ctx).await?;
-    let mut tasks = get_tasks_for_ctx(&state, &ctx).await?;
-    let mut deleted_tasks = deleted_tasks_for_ctx(&state, &ctx).await?;
-    if let Some(since_ts) = body.since_ts {
-        tasks.retain(|task| task.updated_ts >= since_ts);
-        deleted_tasks.retain(|entry| entry.deleted_ts >= since_ts);
+    match body.protocol.as_deref() {
+        None | Some(PROTOCOL_V1) => {}
+        Some(PROTOCOL_V2) => return sync_pull_v2(&state, &ctx, body.since_seq).await.map(Json),
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
     }
 
-    let cursor_ts = sync_cursor_for_ctx(&state, &ctx).await?;
-    Ok(Json(SyncPullResponse { protocol: "delta-v1", cursor_ts, lists, tasks, deleted_tasks }))
+    sync_pull_v1(&state, &ctx, body).await.map(Json)
+}
+
+/// `delta-v1` pull: tasks and tombstones with `since_ts <= ts <= cursor_ts`,
+/// filtered and paged in SQL (see `sync_page`). The cursor and the rows are
+/// read in one transaction, so a page never holds a row newer than the
+/// cursor it reports.
+async fn sync_pull_v1(
+    state: &AppState,
+    ctx: &RequestCtx,
+    body: SyncPullBody,
+) -> Result<SyncPullResponse, StatusCode> {
+    let limit = match (body.limit, &body.page_token) {
+        (Some(limit), _) if limit <= 0 => return Err(StatusCode::BAD_REQUEST),
+        (Some(limit), _) => Some(limit.min(MAX_PULL_PAGE_SIZE)),
+        (None, Some(_)) => Some(MAX_PULL_PAGE_SIZE),
+        (None, None) => None,
+    };
+
+    let resumed = match &body.page_token {
+        Some(token) => Some(PullPosition::decode(token).ok_or(StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+    // A continuation token pins the `since_ts` the pull started from, so a
+    // compaction that ran between pages is caught on the page that follows it.
+    let since_ts = match &resumed {
+        Some(position) => position.requested_since_ts(),
+        None => body.since_ts,
+    };
+
+    let mut tx = state.pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let watermark = prune_watermark_for_ctx(&mut tx, ctx).await?;
+    let full_resync_required = since_ts.is_some_and(|since_ts| {
+        watermark.is_some_and(|watermark| watermark.requires_resync_from_ts(since_ts))
+    });
+    let position = match resumed {
+        Some(position) if !full_resync_required => position,
+        _ => {
+            let cursor_ts = sync_cursor_for_ctx(&mut tx, ctx).await?;
+            let since_ts = since_ts.filter(|_| !full_resync_required);
+            PullPosition::start(since_ts.unwrap_or(i64::MIN), cursor_ts)
+        }
+    };
+    let page = read_pull_page(&mut tx, ctx, &position, limit).await?;
+    let deleted_lists = if position.is_start() {
+        deleted_lists_since_ts(&mut tx, ctx, position.since_ts, position.cursor_ts).await?
+    } else {
+        Vec::new()
+    };
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let lists = if position.is_start() { get_lists_for_ctx(state, ctx).await? } else { Vec::new() };
+    Ok(SyncPullResponse {
+        protocol: PROTOCOL_V1,
+        cursor_ts: position.cursor_ts,
+        cursor_seq: None,
+        lists,
+        tasks: page.tasks,
+        deleted_tasks: page.deleted_tasks,
+        deleted_lists,
+        next_page_token: page.next.as_ref().map(PullPosition::encode),
+        full_resync_required,
+    })
+}
+
+/// `delta-v2` pull: list, task and tombstone changes in `(since_seq, cursor_seq]`,
+/// filtered in SQL. The cursor is read first and bounds the query, so a write
+/// landing mid-pull is deferred to the next pull instead of being sent twice.
+pub(super) async fn sync_pull_v2(
+    state: &AppState,
+    ctx: &RequestCtx,
+    since_seq: Option<i64>,
+) -> Result<SyncPullResponse, StatusCode> {
+    let cursor_seq = sync_seq_for_ctx(state, ctx).await?;
+    let mut conn = state.pool.acquire().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let watermark = prune_watermark_for_ctx(&mut conn, ctx).await?;
+    let full_resync_required = since_seq.is_some_and(|since_seq| {
+        watermark.is_some_and(|watermark| watermark.requires_resync_from_seq(since_seq))
+    });
+    let since_seq = since_seq.filter(|_| !full_resync_required).unwrap_or(0);
+    let lists = get_lists_changed_for_ctx(state, ctx, since_seq, cursor_seq).await?;
+    let deleted_lists = deleted_lists_changed_for_ctx(state, ctx, since_seq, cursor_seq).await?;
+    let tasks = get_tasks_changed_for_ctx(state, ctx, since_seq, cursor_seq).await?;
+    let deleted_tasks = deleted_tasks_changed_for_ctx(state, ctx, since_seq, cursor_seq).await?;
+    let cursor_ts = sync_cursor_for_ctx(&mut conn, ctx).await?;
+    Ok(SyncPullResponse {
+        protocol: PROTOCOL_V2,
+        cursor_ts,
+        cursor_seq: Some(cursor_seq),
+        lists,
+        tasks,
+        deleted_tasks,
+        deleted_lists,
+        next_page_token: None,
+        full_resync_required,
+    })
 }
 
 pub(super) async fn sync_push(
@@ -143,61 +464,188 @@ pub(super) async fn sync_push(
     headers: HeaderMap,
     Json(body): Json<SyncPushBody>,
 ) -> Result<Json<SyncPushResponse>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    sync_push_for_ctx(&state, &ctx, body).await.map(Json)
+}
+
+/// Applies `changes` in order. Each change's outcome is recorded in
+/// `sync_op` in the same transaction as the change (see `sync_ops`), so a
+/// replayed `op_id` with the same payload returns its original outcome
+/// instead of re-applying.
+/// Without `atomic`, every change commits on its own; with it, the batch
+/// commits or rolls back as a unit.
+pub(super) async fn sync_push_for_ctx(
+    state: &AppState,
+    ctx: &RequestCtx,
+    body: SyncPushBody,
+) -> Result<SyncPushResponse, StatusCode> {
     if body.changes.len() > 500 {
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    let ctx = ctx_from_headers(&headers, &state).await?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut conn = state.pool.acquire().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    prune_expired_ops(&mut conn, ctx, now).await?;
+    drop(conn);
+
+    let outcomes = if body.atomic {
+        push_atomic(state, ctx, body.changes, now).await?
+    } else {
+        push_each(state, ctx, body.changes, now).await?
+    };
+
     let mut applied = Vec::new();
+    let mut applied_lists = Vec::new();
     let mut rejected = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            PushOutcome::Task { task } => applied.push(task),
+            PushOutcome::List { list } => applied_lists.push(list),
+            PushOutcome::Deleted => {}
+            PushOutcome::Rejected { rejected: entry } => rejected.push(entry),
+        }
+    }
+
+    let mut conn = state.pool.acquire().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let cursor_ts = sync_cursor_for_ctx(&mut conn, ctx).await?;
+    Ok(SyncPushResponse { protocol: PROTOCOL_V1, cursor_ts, applied, applied_lists, rejected })
+}
+
+/// One write transaction per change. A rejected change is rolled back before
+/// its rejection is recorded, so it never leaves a partial write behind.
+async fn push_each(
+    state: &AppState,
+    ctx: &RequestCtx,
+    changes: Vec<SyncPushChange>,
+    now: i64,
+) -> Result<Vec<PushOutcome>, StatusCode> {
+    let mut outcomes = Vec::with_capacity(changes.len());
+    for change in changes {
+        let op_id = change.op_id().to_string();
+        let payload_hash = change.payload_hash();
+        let mut tx = begin_write(&state.pool).await?;
+        if let Some(outcome) = recorded_outcome(&mut tx, ctx, &op_id, &payload_hash, now).await? {
+            outcomes.push(outcome);
+            continue;
+        }
+        let outcome = apply_change(&mut tx, ctx, change).await?;
+        if matches!(outcome, PushOutcome::Rejected { .. }) {
+            tx.rollback().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            tx = begin_write(&state.pool).await?;
+        }
+        record_outcome(&mut tx, ctx, &op_id, &payload_hash, &outcome, now).await?;
+        tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
 
-    for change in body.changes {
-        match change {
-            SyncPushChange::CreateTask { op_id, body } => {
-                match create_task_for_ctx(&state, &ctx, body).await {
-                    Ok((_status, task)) => applied.push(task),
-                    Err(status) => rejected.push(SyncPushRejected {
-                        op_id,
-                        status: status.as_u16(),
-                        error: status.canonical_reason().unwrap_or("request failed").to_string(),
-                    }),
-                }
+/// One write transaction for the whole batch. The first rejection (fresh or
+/// replayed) rolls everything back and is returned alone.
+async fn push_atomic(
+    state: &AppState,
+    ctx: &RequestCtx,
+    changes: Vec<SyncPushChange>,
+    now: i64,
+) -> Result<Vec<PushOutcome>, StatusCode> {
+    let mut outcomes = Vec::with_capacity(changes.len());
+    let mut tx = begin_write(&state.pool).await?;
+    for change in changes {
+        let op_id = change.op_id().to_string();
+        let payload_hash = change.payload_hash();
+        let outcome = match recorded_outcome(&mut tx, ctx, &op_id, &payload_hash, now).await? {
+            Some(outcome) => outcome,
+            None => {
+                let outcome = apply_change(&mut tx, ctx, change).await?;
+                record_outcome(&mut tx, ctx, &op_id, &payload_hash, &outcome, now).await?;
+                outcome
             }
-            SyncPushChange::UpdateTask { op_id, task_id, body } => {
-                match update_task_meta_for_ctx(&state, &ctx, task_id, body).await {
-                    Ok(task) => applied.push(task),
-                    Err(status) => rejected.push(SyncPushRejected {
-                        op_id,
-                        status: status.as_u16(),
-                        error: status.canonical_reason().unwrap_or("request failed").to_string(),
-                    }),
-                }
+        };
+        if matches!(outcome, PushOutcome::Rejected { .. }) {
+            tx.rollback().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            return Ok(vec![outcome]);
+        }
+        outcomes.push(outcome);
+    }
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(outcomes)
+}
+
+async fn apply_change(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    change: SyncPushChange,
+) -> Result<PushOutcome, StatusCode> {
+    let outcome = match change {
+        SyncPushChange::CreateTask { op_id, body } => {
+            match create_task_for_ctx(conn, ctx, body).await {
+                Ok((_status, task)) => PushOutcome::Task { task },
+                Err(err) => PushOutcome::rejected(op_id, err),
             }
-            SyncPushChange::UpdateTaskStatus { op_id, task_id, status: next_status } => {
-                match update_task_status_for_ctx(
-                    &state,
-                    &ctx,
-                    task_id,
-                    UpdateTaskStatus { status: next_status },
-                )
-                .await
-                {
-                    Ok(task) => applied.push(task),
-                    Err(status) => rejected.push(SyncPushRejected {
-                        op_id,
-                        status: status.as_u16(),
-                        error: status.canonical_reason().unwrap_or("request failed").to_string(),
-                    }),
-                }
+        }
+        SyncPushChange::UpdateTask { op_id, task_id, body, base_updated_ts } => {
+            let body = match base_updated_ts {
+                Some(base) => match merge_with_base(&mut *conn, ctx, &task_id, body, base).await? {
+                    MergeOutcome::Apply(patch) => patch,
+                    MergeOutcome::Conflict { fields, current } => {
+                        let rejected = SyncPushRejected::conflict(op_id, fields, current);
+                        return Ok(PushOutcome::Rejected { rejected });
+                    }
+                },
+                None => body,
+            };
+            match update_task_meta_for_ctx(conn, ctx, task_id, body).await {
+                Ok(task) => PushOutcome::Task { task },
+                Err(err) => PushOutcome::rejected(op_id, err),
             }
         }
-    }
+        SyncPushChange::UpdateTaskStatus { op_id, task_id, status: next_status } => {
+            let body = UpdateTaskStatus { status: next_status };
+            match update_task_status_for_ctx(conn, ctx, task_id, body).await {
+                Ok(task) => PushOutcome::Task { task },
+                Err(status) => PushOutcome::rejected(op_id, status.into()),
+            }
+        }
+        SyncPushChange::DeleteTask { op_id, task_id } => {
+            match delete_task_for_ctx(conn, ctx, task_id).await {
+                Ok(_) => PushOutcome::Deleted,
+                Err(status) => PushOutcome::rejected(op_id, status.into()),
+            }
+        }
+        SyncPushChange::CreateList { op_id, body } => {
+            match create_list_for_ctx(conn, ctx, body).await {
+                Ok((_status, list)) => PushOutcome::List { list },
+                Err(status) => PushOutcome::rejected(op_id, status.into()),
+            }
+        }
+        SyncPushChange::UpdateList { op_id, list_id, body } => {
+            match update_list_for_ctx(conn, ctx, list_id, body).await {
+                Ok(list) => PushOutcome::List { list },
+                Err(status) => PushOutcome::rejected(op_id, status.into()),
+            }
+        }
+        SyncPushChange::DeleteList { op_id, list_id } => {
+            match delete_list_for_ctx(conn, ctx, list_id).await {
+                Ok(_) => PushOutcome::Deleted,
+                Err(status) => PushOutcome::rejected(op_id, status.into()),
+            }
+        }
+    };
+    Ok(outcome)
+}
 
-    let cursor_ts = sync_cursor_for_ctx(&state, &ctx).await?;
-    Ok(Json(SyncPushResponse { protocol: "delta-v1", cursor_ts, applied, rejected }))
+impl PushOutcome {
+    fn rejected(op_id: String, err: TaskError) -> Self {
+        PushOutcome::Rejected { rejected: SyncPushRejected::new(op_id, err) }
+    }
 }
 
 pub fn sync_routes(pool: &sqlx::SqlitePool) -> Router {
     let state = app_state(pool);
-    Router::new().route("/pull", post(sync_pull)).route("/push", post(sync_push)).with_state(state)
+    Router::new()
+        .route("/pull", post(sync_pull))
+        .route("/push", post(sync_push))
+        .route("/events", get(sync_events))
+        .route("/ws", get(sync_ws))
+        .with_state(state)
 }
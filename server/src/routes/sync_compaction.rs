@@ -0,0 +1,144 @@
+//! Tombstone retention for delta sync.
+//!
+//! Task and list tombstones only exist so that clients with a cached copy can
+//! drop deleted rows. A background job deletes tombstones older than the
+//! retention window (`TOMBSTONE_RETENTION_DAYS`, default 90) and records per
+//! space the newest `deleted_ts` and `change_seq` it removed in
+//! `sync_compaction`. A pull whose cursor is at or below that watermark may
+//! have missed a deletion, so instead of a delta it gets a full snapshot with
+//! `full_resync_required` set; the client replaces its cache rather than
+//! merging into it.
+//!
+//! The watermark is per space, not per grant: a contributor may be asked to
+//! resync for a pruned tombstone in a list they cannot see, which costs one
+//! full pull and is never wrong.
+
+use axum::http::StatusCode;
+use sqlx::{SqliteConnection, SqlitePool};
+use std::time::Duration;
+
+use super::types::{parse_retention_days, RequestCtx};
+
+const DEFAULT_TOMBSTONE_RETENTION_DAYS: i64 = 90;
+
+/// How often the compaction job runs; the first pass runs at boot.
+const TOMBSTONE_COMPACTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Parses `TOMBSTONE_RETENTION_DAYS` (see `parse_retention_days`).
+pub fn parse_tombstone_retention(raw: Option<String>) -> Result<i64, String> {
+    parse_retention_days("TOMBSTONE_RETENTION_DAYS", raw, DEFAULT_TOMBSTONE_RETENTION_DAYS)
+}
+
+/// The newest tombstone the compaction job removed from a space.
+#[derive(sqlx::FromRow, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct PruneWatermark {
+    pub(super) pruned_ts: i64,
+    pub(super) pruned_seq: i64,
+}
+
+impl PruneWatermark {
+    /// A `delta-v1` pull from `since_ts` returns tombstones with
+    /// `deleted_ts >= since_ts`, so it misses pruned ones unless it starts
+    /// after the watermark.
+    pub(super) fn requires_resync_from_ts(&self, since_ts: i64) -> bool {
+        since_ts <= self.pruned_ts
+    }
+
+    /// A `delta-v2` pull returns changes with `change_seq > since_seq`; a
+    /// client that already saw `pruned_seq` missed nothing.
+    pub(super) fn requires_resync_from_seq(&self, since_seq: i64) -> bool {
+        since_seq < self.pruned_seq
+    }
+}
+
+/// `None` until the compaction job has pruned something in the space.
+pub(super) async fn prune_watermark_for_ctx(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+) -> Result<Option<PruneWatermark>, StatusCode> {
+    sqlx::query_as::<_, PruneWatermark>(
+        "select pruned_ts, pruned_seq from sync_compaction where space_id = ?1",
+    )
+    .bind(&ctx.space_id)
+    .fetch_optional(conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Deletes task and list tombstones with `deleted_ts < horizon_ts` across all
+/// spaces, advancing each affected space's watermark in the same transaction.
+/// Returns the number of tombstones removed.
+pub(super) async fn compact_tombstones(
+    pool: &SqlitePool,
+    horizon_ts: i64,
+) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    // `where true` keeps SQLite from parsing `on conflict` as a join clause.
+    sqlx::query(
+        "insert into sync_compaction (space_id, pruned_ts, pruned_seq)
+         select space_id, max(deleted_ts), max(change_seq) from (
+             select space_id, deleted_ts, change_seq from task_tombstone where deleted_ts < ?1
+             union all
+             select space_id, deleted_ts, change_seq from list_tombstone where deleted_ts < ?1
+         ) where true group by space_id
+         on conflict(space_id) do update set
+             pruned_ts = max(pruned_ts, excluded.pruned_ts),
+             pruned_seq = max(pruned_seq, excluded.pruned_seq)",
+    )
+    .bind(horizon_ts)
+    .execute(&mut *tx)
+    .await?;
+    let tasks = sqlx::query("delete from task_tombstone where deleted_ts < ?1")
+        .bind(horizon_ts)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    let lists = sqlx::query("delete from list_tombstone where deleted_ts < ?1")
+        .bind(horizon_ts)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(tasks + lists)
+}
+
+/// Spawns the compaction job. Failures are logged and retried on the next
+/// tick; they never take the server down.
+pub fn spawn_tombstone_compaction(pool: &SqlitePool, retention_ms: i64) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(TOMBSTONE_COMPACTION_INTERVAL);
+        loop {
+            tick.tick().await;
+            let horizon_ts = chrono::Utc::now().timestamp_millis() - retention_ms;
+            match compact_tombstones(&pool, horizon_ts).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!(pruned, horizon_ts, "compacted sync tombstones"),
+                Err(err) => tracing::warn!(error = %err, "tombstone compaction failed"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+    #[test]
+    fn retention_defaults_when_unset_or_blank() {
+        let default_ms = DEFAULT_TOMBSTONE_RETENTION_DAYS * DAY_MS;
+        assert_eq!(parse_tombstone_retention(None), Ok(default_ms));
+        assert_eq!(parse_tombstone_retention(Some("  ".into())), Ok(default_ms));
+        assert_eq!(parse_tombstone_retention(Some(" 30 ".into())), Ok(30 * DAY_MS));
+    }
+
+    #[test]
+    fn retention_rejects_non_positive_or_malformed_days() {
+        for raw in ["0", "-5", "7d", "1.5", "99999999999999999"] {
+            let err = parse_tombstone_retention(Some(raw.into())).unwrap_err();
+            assert!(err.contains(raw), "{err}");
+        }
+    }
+}
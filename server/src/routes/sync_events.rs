@@ -0,0 +1,106 @@
+//! `GET /sync/events`: a Server-Sent Events stream that tells a connected
+//! client when something it can see has changed, so it can pull right away
+//! instead of on a timer.
+//!
+//! Each subscriber polls the space's `sync_counter` (a primary-key lookup).
+//! Only when that moves does it re-check the session and recompute the
+//! caller's visible cursor: the space sequence for admins, and for
+//! contributors the highest `change_seq` among granted tasks, lists and task
+//! tombstones plus list tombstones scoped by the grants held when the list
+//! was deleted (`list_tombstone_grant`). A `changed` event is sent when the
+//! visible cursor moves, so contributors are not woken by writes to lists
+//! they cannot see. Because the sequence is stamped by
+//! triggers, every write path (REST, sync, API token, restore) is covered.
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+use std::{convert::Infallible, time::Duration};
+
+use super::sync::sync_seq_for_ctx;
+use super::types::{ctx_from_headers, AppState, RequestCtx, Role};
+
+/// How often each subscriber checks the space sequence.
+pub(super) const SYNC_EVENTS_POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+#[derive(Serialize)]
+struct ChangedEvent {
+    /// The caller's visible change cursor; pull when it exceeds the last
+    /// `cursor_seq` the client has seen.
+    cursor_seq: i64,
+}
+
+struct Subscriber {
+    state: AppState,
+    headers: HeaderMap,
+    ctx: RequestCtx,
+    space_seq: Option<i64>,
+    sent_seq: Option<i64>,
+}
+
+/// Opens the stream. The first event carries the current cursor, so a client
+/// that reconnects after a gap learns immediately whether it missed changes.
+/// The stream ends when the session is revoked or the database errors; the
+/// client reconnects with a fresh token.
+pub(super) async fn sync_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let subscriber = Subscriber { state, headers, ctx, space_seq: None, sent_seq: None };
+    let events = stream::unfold(subscriber, |mut subscriber| async move {
+        let event = subscriber.next_event().await?;
+        Some((Ok(event), subscriber))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+impl Subscriber {
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if self.space_seq.is_some() {
+                tokio::time::sleep(SYNC_EVENTS_POLL_INTERVAL).await;
+            }
+            let space_seq = sync_seq_for_ctx(&self.state, &self.ctx).await.ok()?;
+            if self.space_seq == Some(space_seq) {
+                continue;
+            }
+            self.space_seq = Some(space_seq);
+            self.ctx = ctx_from_headers(&self.headers, &self.state).await.ok()?;
+            let visible_seq = visible_seq_for_ctx(&self.state, &self.ctx, space_seq).await.ok()?;
+            if self.sent_seq == Some(visible_seq) {
+                continue;
+            }
+            self.sent_seq = Some(visible_seq);
+            let data = serde_json::to_string(&ChangedEvent { cursor_seq: visible_seq }).ok()?;
+            return Some(Event::default().event("changed").id(visible_seq.to_string()).data(data));
+        }
+    }
+}
+
+async fn visible_seq_for_ctx(
+    state: &AppState,
+    ctx: &RequestCtx,
+    space_seq: i64,
+) -> Result<i64, StatusCode> {
+    if ctx.role == Role::Admin {
+        return Ok(space_seq);
+    }
+    sqlx::query_scalar(
+        "select max(
+            coalesce((select max(t.change_seq) from task t join list_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2), 0),
+            coalesce((select max(l.change_seq) from list l join list_grant g on g.list_id = l.id and g.space_id = l.space_id where l.space_id = ?1 and g.user_id = ?2), 0),
+            coalesce((select max(t.change_seq) from task_tombstone t join list_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2), 0),
+            coalesce((select max(t.change_seq) from list_tombstone t join list_tombstone_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2), 0)
+        )",
+    )
+    .bind(&ctx.space_id)
+    .bind(&ctx.user_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
@@ -0,0 +1,110 @@
+//! Processed-op log that makes `sync_push` replays idempotent.
+//!
+//! Every change a push settles is recorded under `(space, user, op_id)` with
+//! the outcome it produced and a hash of the change's payload, in the same
+//! transaction as the change itself. A client that retries a batch after a
+//! timeout gets the recorded outcomes back for ops the server already
+//! settled, and only the rest are applied. A known op_id arriving with a
+//! different payload is a different edit, so it is applied and replaces the
+//! record. Records expire after `SYNC_OP_RETENTION_MS`; a replay older than
+//! that is applied again as a new op.
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+
+use super::lists::ListRow;
+use super::sync::SyncPushRejected;
+use super::tasks::TaskRow;
+use super::types::RequestCtx;
+
+/// How long a processed op_id is remembered: long enough to cover an offline
+/// client's retry queue, short enough to keep `sync_op` small.
+pub(super) const SYNC_OP_RETENTION_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// What a single push change produced, as returned in the push response.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(super) enum PushOutcome {
+    Task { task: TaskRow },
+    List { list: ListRow },
+    Deleted,
+    Rejected { rejected: SyncPushRejected },
+}
+
+impl PushOutcome {
+    /// Internal errors are transient: leave them unrecorded so a replay
+    /// retries the op instead of repeating the failure.
+    pub(super) fn is_replayable(&self) -> bool {
+        match self {
+            PushOutcome::Rejected { rejected } => rejected.status < 500,
+            _ => true,
+        }
+    }
+}
+
+pub(super) async fn recorded_outcome(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    op_id: &str,
+    payload_hash: &str,
+    now: i64,
+) -> Result<Option<PushOutcome>, StatusCode> {
+    let outcome: Option<String> = sqlx::query_scalar(
+        "select outcome from sync_op where space_id = ?1 and user_id = ?2 and op_id = ?3 and payload_hash = ?4 and created_ts > ?5",
+    )
+    .bind(&ctx.space_id)
+    .bind(&ctx.user_id)
+    .bind(op_id)
+    .bind(payload_hash)
+    .bind(now - SYNC_OP_RETENTION_MS)
+    .fetch_optional(conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    outcome
+        .map(|raw| serde_json::from_str(&raw).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR))
+        .transpose()
+}
+
+pub(super) async fn record_outcome(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    op_id: &str,
+    payload_hash: &str,
+    outcome: &PushOutcome,
+    now: i64,
+) -> Result<(), StatusCode> {
+    if !outcome.is_replayable() {
+        return Ok(());
+    }
+    let raw = serde_json::to_string(outcome).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query(
+        "insert or replace into sync_op (space_id, user_id, op_id, payload_hash, outcome, created_ts) values (?1, ?2, ?3, ?4, ?5, ?6)",
+    )
+    .bind(&ctx.space_id)
+    .bind(&ctx.user_id)
+    .bind(op_id)
+    .bind(payload_hash)
+    .bind(&raw)
+    .bind(now)
+    .execute(conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(())
+}
+
+/// Drops the caller's expired records; run at the start of each push.
+pub(super) async fn prune_expired_ops(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    now: i64,
+) -> Result<(), StatusCode> {
+    sqlx::query("delete from sync_op where space_id = ?1 and user_id = ?2 and created_ts <= ?3")
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .bind(now - SYNC_OP_RETENTION_MS)
+        .execute(conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(())
+}
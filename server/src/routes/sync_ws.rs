@@ -0,0 +1,155 @@
+//! `GET /sync/ws`: a bidirectional sync channel for always-online clients.
+//!
+//! The socket speaks JSON text frames tagged by `type`. A client sends
+//! `push` messages carrying the same body as `POST /sync/push`, answered with
+//! a `push_result`, and a `pull` message with a `delta-v2` `since_seq` that
+//! starts (or rewinds) a live feed: the server answers with a `delta` right
+//! away, then sends another whenever rows visible to the caller change, using
+//! the same polling and scoping as `/sync/events` and `delta-v2` pulls. The
+//! client's own pushes come back through the feed like anyone else's.
+//!
+//! The session is re-checked before every push and whenever the space
+//! sequence moves; a revoked session gets a `401` `error` and the socket is
+//! closed. Offline-capable clients keep using the HTTP endpoints.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+
+use super::sync::{
+    sync_pull_v2, sync_push_for_ctx, sync_seq_for_ctx, SyncPullResponse, SyncPushBody,
+    SyncPushResponse,
+};
+use super::sync_events::SYNC_EVENTS_POLL_INTERVAL;
+use super::types::{ctx_from_headers, AppState, RequestCtx};
+
+/// Mirrors axum's default JSON body limit for `POST /sync/push`.
+const MAX_MESSAGE_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Push(SyncPushBody),
+    Pull { since_seq: Option<i64> },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    PushResult(SyncPushResponse),
+    Delta(SyncPullResponse),
+    Error { status: u16, error: String },
+}
+
+impl ServerMessage {
+    fn error(status: StatusCode) -> Self {
+        let error = status.canonical_reason().unwrap_or("error").to_string();
+        ServerMessage::Error { status: status.as_u16(), error }
+    }
+}
+
+pub(super) async fn sync_ws(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let session = SyncSocket { state, headers, ctx, cursor_seq: None, space_seq: None };
+    Ok(ws.max_message_size(MAX_MESSAGE_BYTES).on_upgrade(move |socket| session.run(socket)))
+}
+
+struct SyncSocket {
+    state: AppState,
+    headers: HeaderMap,
+    ctx: RequestCtx,
+    /// Set by the first `pull`; no deltas are streamed before it.
+    cursor_seq: Option<i64>,
+    space_seq: Option<i64>,
+}
+
+impl SyncSocket {
+    async fn run(mut self, mut socket: WebSocket) {
+        let mut poll = tokio::time::interval(SYNC_EVENTS_POLL_INTERVAL);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let reply = tokio::select! {
+                incoming = socket.recv() => match incoming {
+                    Some(Ok(Message::Text(text))) => Some(self.handle(&text).await),
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => None,
+                },
+                _ = poll.tick() => self.poll_delta().await,
+            };
+            let Some(reply) = reply else { continue };
+            let closing = matches!(reply, ServerMessage::Error { status: 401, .. });
+            let Ok(text) = serde_json::to_string(&reply) else { return };
+            if socket.send(Message::Text(text)).await.is_err() || closing {
+                return;
+            }
+        }
+    }
+
+    async fn handle(&mut self, text: &str) -> ServerMessage {
+        let Ok(message) = serde_json::from_str::<ClientMessage>(text) else {
+            return ServerMessage::error(StatusCode::BAD_REQUEST);
+        };
+        if let Err(status) = self.refresh_session().await {
+            return ServerMessage::error(status);
+        }
+        match message {
+            ClientMessage::Push(body) => {
+                match sync_push_for_ctx(&self.state, &self.ctx, body).await {
+                    Ok(response) => ServerMessage::PushResult(response),
+                    Err(status) => ServerMessage::error(status),
+                }
+            }
+            ClientMessage::Pull { since_seq } => {
+                match sync_pull_v2(&self.state, &self.ctx, since_seq).await {
+                    Ok(delta) => {
+                        self.cursor_seq = delta.cursor_seq;
+                        ServerMessage::Delta(delta)
+                    }
+                    Err(status) => ServerMessage::error(status),
+                }
+            }
+        }
+    }
+
+    /// A `delta` when the space moved and something visible changed since
+    /// the feed cursor; `None` otherwise.
+    async fn poll_delta(&mut self) -> Option<ServerMessage> {
+        let cursor_seq = self.cursor_seq?;
+        let space_seq = match sync_seq_for_ctx(&self.state, &self.ctx).await {
+            Ok(space_seq) => space_seq,
+            Err(status) => return Some(ServerMessage::error(status)),
+        };
+        if self.space_seq.replace(space_seq) == Some(space_seq) {
+            return None;
+        }
+        if let Err(status) = self.refresh_session().await {
+            return Some(ServerMessage::error(status));
+        }
+        let delta = match sync_pull_v2(&self.state, &self.ctx, Some(cursor_seq)).await {
+            Ok(delta) => delta,
+            Err(status) => return Some(ServerMessage::error(status)),
+        };
+        self.cursor_seq = delta.cursor_seq;
+        let empty = delta.lists.is_empty()
+            && delta.tasks.is_empty()
+            && delta.deleted_tasks.is_empty()
+            && delta.deleted_lists.is_empty();
+        (!empty).then_some(ServerMessage::Delta(delta))
+    }
+
+    async fn refresh_session(&mut self) -> Result<(), StatusCode> {
+        self.ctx = ctx_from_headers(&self.headers, &self.state).await?;
+        Ok(())
+    }
+}
@@ -1,19 +1,25 @@
 use axum::{
     extract::{Path, State},
     http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, patch, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, SqliteConnection};
 use uuid::Uuid;
 
+use super::history::{get_task_history, record_task_event, TaskEventKind};
+use super::recurrence::{
+    format_due_date, next_due_after_current, normalize_recur_rule, RecurRule, RecurRuleError,
+};
+use super::trash::{get_trash, move_to_trash, restore_task};
 use super::types::{
-    app_state, ctx_from_headers, is_unique_violation, is_valid_task_status,
+    app_state, begin_write, ctx_from_headers, is_unique_violation, is_valid_task_status,
     normalize_task_priority, AppState, RequestCtx, Role,
 };
 
-#[derive(Serialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow)]
 pub(super) struct TaskRow {
     pub(super) id: String,
     pub(super) space_id: String,
@@ -43,7 +49,7 @@ pub(super) struct DeletedTaskRow {
     pub(super) deleted_ts: i64,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub(super) struct CreateTask {
     pub(super) id: Option<String>,
     pub(super) title: String,
@@ -66,7 +72,7 @@ pub(super) struct UpdateTaskStatus {
     pub(super) status: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub(super) struct UpdateTaskMeta {
     pub(super) title: Option<String>,
     pub(super) status: Option<String>,
@@ -84,6 +90,53 @@ pub(super) struct UpdateTaskMeta {
     pub(super) assignee_user_id: Option<String>,
 }
 
+/// Failure from the task write paths. Every failure renders as a bare status
+/// (the browser routes' contract) except an invalid `recur_rule`, which
+/// carries a coded body naming the rejected RRULE part.
+#[derive(Debug)]
+pub(super) enum TaskError {
+    Status(StatusCode),
+    InvalidRecurRule(RecurRuleError),
+}
+
+impl TaskError {
+    pub(super) fn status(&self) -> StatusCode {
+        match self {
+            TaskError::Status(status) => *status,
+            TaskError::InvalidRecurRule(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Human-readable reason, used for sync push rejections.
+    pub(super) fn reason(&self) -> &'static str {
+        match self {
+            TaskError::Status(status) => status.canonical_reason().unwrap_or("request failed"),
+            TaskError::InvalidRecurRule(err) => err.message,
+        }
+    }
+}
+
+impl From<StatusCode> for TaskError {
+    fn from(status: StatusCode) -> Self {
+        TaskError::Status(status)
+    }
+}
+
+impl From<RecurRuleError> for TaskError {
+    fn from(err: RecurRuleError) -> Self {
+        TaskError::InvalidRecurRule(err)
+    }
+}
+
+impl IntoResponse for TaskError {
+    fn into_response(self) -> Response {
+        match self {
+            TaskError::Status(status) => status.into_response(),
+            TaskError::InvalidRecurRule(err) => err.into_response(),
+        }
+    }
+}
+
 pub(super) async fn get_tasks(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -118,31 +171,114 @@ pub(super) async fn get_tasks_for_ctx(
     Ok(rows)
 }
 
+/// Tasks in the caller's read scope whose `change_seq` falls in
+/// `(since_seq, cursor_seq]`, in sequence order — the `delta-v2` pull set.
+pub(super) async fn get_tasks_changed_for_ctx(
+    state: &AppState,
+    ctx: &RequestCtx,
+    since_seq: i64,
+    cursor_seq: i64,
+) -> Result<Vec<TaskRow>, StatusCode> {
+    let rows = if ctx.role == Role::Admin {
+        sqlx::query_as::<_, TaskRow>(
+            "select id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id from task where space_id = ?1 and change_seq > ?2 and change_seq <= ?3 order by change_seq asc",
+        )
+        .bind(&ctx.space_id)
+        .bind(since_seq)
+        .bind(cursor_seq)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        sqlx::query_as::<_, TaskRow>(
+            "select t.id, t.space_id, t.title, t.status, t.list_id, t.my_day, t.priority, t.task_order as \"order\", t.updated_ts, t.created_ts, t.url, t.recur_rule, t.due_date, t.punted_from_due_date, t.punted_on_date, t.occurrences_completed, t.completed_ts, t.notes, t.assignee_user_id, t.created_by_user_id from task t join list_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2 and t.change_seq > ?3 and t.change_seq <= ?4 order by t.change_seq asc",
+        )
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .bind(since_seq)
+        .bind(cursor_seq)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+    Ok(rows)
+}
+
+/// A single task within the caller's read scope (the same role/grant rules
+/// as `get_tasks_for_ctx`), or `None` when it does not exist or is not
+/// visible to the caller.
+pub(super) async fn get_task_for_ctx(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    id: &str,
+) -> Result<Option<TaskRow>, StatusCode> {
+    let row = if ctx.role == Role::Admin {
+        sqlx::query_as::<_, TaskRow>(
+            "select id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id from task where id = ?1 and space_id = ?2 limit 1",
+        )
+        .bind(id)
+        .bind(&ctx.space_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        sqlx::query_as::<_, TaskRow>(
+            "select t.id, t.space_id, t.title, t.status, t.list_id, t.my_day, t.priority, t.task_order as \"order\", t.updated_ts, t.created_ts, t.url, t.recur_rule, t.due_date, t.punted_from_due_date, t.punted_on_date, t.occurrences_completed, t.completed_ts, t.notes, t.assignee_user_id, t.created_by_user_id from task t join list_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.id = ?1 and t.space_id = ?2 and g.user_id = ?3 limit 1",
+        )
+        .bind(id)
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+    Ok(row)
+}
+
+/// The stored row regardless of the caller's read scope; write paths load
+/// it after their own permission checks to diff against.
+async fn load_task_row(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    id: &str,
+) -> Result<Option<TaskRow>, StatusCode> {
+    sqlx::query_as::<_, TaskRow>(
+        "select id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id from task where id = ?1 and space_id = ?2 limit 1",
+    )
+    .bind(id)
+    .bind(&ctx.space_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 pub(super) async fn create_task(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<CreateTask>,
-) -> Result<(StatusCode, Json<TaskRow>), StatusCode> {
+) -> Result<(StatusCode, Json<TaskRow>), TaskError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    let (status, rec) = create_task_for_ctx(&state, &ctx, body).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    let (status, rec) = create_task_for_ctx(&mut tx, &ctx, body).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok((status, Json(rec)))
 }
 
 pub(super) async fn create_task_for_ctx(
-    state: &AppState,
+    conn: &mut SqliteConnection,
     ctx: &RequestCtx,
     body: CreateTask,
-) -> Result<(StatusCode, TaskRow), StatusCode> {
+) -> Result<(StatusCode, TaskRow), TaskError> {
     // ensure list belongs to space
     let list_exists: Option<i64> =
         sqlx::query_scalar("select 1 from list where id = ?1 and space_id = ?2")
             .bind(&body.list_id)
             .bind(&ctx.space_id)
-            .fetch_optional(&state.pool)
+            .fetch_optional(&mut *conn)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     if list_exists.is_none() {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(StatusCode::NOT_FOUND.into());
     }
 
     if ctx.role == Role::Contributor {
@@ -152,11 +288,11 @@ pub(super) async fn create_task_for_ctx(
         .bind(&body.list_id)
         .bind(&ctx.user_id)
         .bind(&ctx.space_id)
-        .fetch_one(&state.pool)
+        .fetch_one(&mut *conn)
         .await
         .unwrap_or(0);
         if allowed == 0 {
-            return Err(StatusCode::FORBIDDEN);
+            return Err(StatusCode::FORBIDDEN.into());
         }
     }
 
@@ -180,6 +316,7 @@ pub(super) async fn create_task_for_ctx(
     };
     let priority = normalize_task_priority(body.priority)?;
     let priority = priority.unwrap_or(0);
+    let recur_rule = normalize_recur_rule(body.recur_rule)?;
     let punted_from_due_date = body.punted_from_due_date.clone();
     let punted_on_date = body.punted_on_date.clone();
     let insert_result = sqlx::query_as::<_, TaskRow>(
@@ -194,27 +331,31 @@ pub(super) async fn create_task_for_ctx(
 	.bind(&order)
 	.bind(now)
 	.bind(&body.url)
-	.bind(&body.recur_rule)
+	.bind(&recur_rule)
 	.bind(&body.due_date)
 	.bind(&punted_from_due_date)
 	.bind(&punted_on_date)
 	.bind(&body.notes)
     .bind(&assignee_user_id)
     .bind(&ctx.user_id)
-	.fetch_one(&state.pool)
+	.fetch_one(&mut *conn)
 	.await;
     let (status, rec) = match insert_result {
-        Ok(inserted) => (StatusCode::CREATED, inserted),
+        Ok(inserted) => {
+            record_task_event(&mut *conn, ctx, TaskEventKind::Created, None, &inserted, now)
+                .await?;
+            (StatusCode::CREATED, inserted)
+        }
         Err(err) => {
             if !is_unique_violation(&err) {
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
             }
             let existing = sqlx::query_as::<_, TaskRow>(
 				"select id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id from task where id = ?1 and space_id = ?2 limit 1",
 			)
 			.bind(&id)
 			.bind(&ctx.space_id)
-			.fetch_optional(&state.pool)
+			.fetch_optional(&mut *conn)
 			.await
 			.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
             .ok_or(StatusCode::CONFLICT)?;
@@ -225,13 +366,83 @@ pub(super) async fn create_task_for_ctx(
     sqlx::query("delete from task_tombstone where task_id = ?1 and space_id = ?2")
         .bind(&rec.id)
         .bind(&ctx.space_id)
-        .execute(&state.pool)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // A live task with this id supersedes any trashed copy.
+    sqlx::query("delete from task_trash where task_id = ?1 and space_id = ?2")
+        .bind(&rec.id)
+        .bind(&ctx.space_id)
+        .execute(&mut *conn)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok((status, rec))
 }
 
+#[derive(FromRow)]
+struct RecurrenceState {
+    status: String,
+    recur_rule: Option<String>,
+    due_date: Option<String>,
+    punted_from_due_date: Option<String>,
+    occurrences_completed: i64,
+}
+
+async fn load_recurrence_state(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    id: &str,
+) -> Result<Option<RecurrenceState>, StatusCode> {
+    sqlx::query_as::<_, RecurrenceState>(
+        "select status, recur_rule, due_date, punted_from_due_date, occurrences_completed from task where id = ?1 and space_id = ?2 limit 1",
+    )
+    .bind(id)
+    .bind(&ctx.space_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Due date a recurring task rolls forward to when its current occurrence is
+/// completed, or `None` when the task does not recur, is already done, or
+/// its RRULE series has ended (`COUNT`/`UNTIL`) — all of which complete
+/// normally.
+fn next_recurring_due(current: &RecurrenceState) -> Option<String> {
+    if current.status == "done" {
+        return None;
+    }
+    let rule = current.recur_rule.as_deref().and_then(|raw| RecurRule::parse(raw).ok())?;
+    let today = chrono::Utc::now().date_naive();
+    let next = next_due_after_current(
+        &rule,
+        current.due_date.as_deref(),
+        current.punted_from_due_date.as_deref(),
+        current.occurrences_completed,
+        today,
+    )?;
+    Some(format_due_date(next))
+}
+
+async fn roll_forward_recurring_task(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    id: &str,
+    next_due: String,
+    now: i64,
+) -> Result<TaskRow, StatusCode> {
+    sqlx::query_as::<_, TaskRow>(
+        "update task set status = 'pending', due_date = ?1, punted_from_due_date = null, punted_on_date = null, occurrences_completed = occurrences_completed + 1, completed_ts = ?2, updated_ts = ?2 where id = ?3 and space_id = ?4 returning id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id",
+    )
+    .bind(&next_due)
+    .bind(now)
+    .bind(id)
+    .bind(&ctx.space_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::NOT_FOUND)
+}
+
 pub(super) async fn update_task_status(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -239,12 +450,14 @@ pub(super) async fn update_task_status(
     Json(body): Json<UpdateTaskStatus>,
 ) -> Result<Json<TaskRow>, StatusCode> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    let rec = update_task_status_for_ctx(&state, &ctx, id, body).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    let rec = update_task_status_for_ctx(&mut tx, &ctx, id, body).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(Json(rec))
 }
 
 pub(super) async fn update_task_status_for_ctx(
-    state: &AppState,
+    conn: &mut SqliteConnection,
     ctx: &RequestCtx,
     id: String,
     body: UpdateTaskStatus,
@@ -259,7 +472,7 @@ pub(super) async fn update_task_status_for_ctx(
         )
         .bind(&id)
         .bind(&ctx.space_id)
-        .fetch_optional(&state.pool)
+        .fetch_optional(&mut *conn)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         let Some(created_by_user_id) = created_by_user_id else {
@@ -271,6 +484,17 @@ pub(super) async fn update_task_status_for_ctx(
     }
 
     let now = chrono::Utc::now().timestamp_millis();
+    let before = load_task_row(&mut *conn, ctx, &id).await?.ok_or(StatusCode::NOT_FOUND)?;
+    if body.status == "done" {
+        let current =
+            load_recurrence_state(&mut *conn, ctx, &id).await?.ok_or(StatusCode::NOT_FOUND)?;
+        if let Some(next_due) = next_recurring_due(&current) {
+            let rec = roll_forward_recurring_task(&mut *conn, ctx, &id, next_due, now).await?;
+            record_task_event(&mut *conn, ctx, TaskEventKind::Updated, Some(&before), &rec, now)
+                .await?;
+            return Ok(rec);
+        }
+    }
     let rec = sqlx::query_as::<_, TaskRow>(
 		"update task set status = ?1, completed_ts = case when ?1 = 'done' then coalesce(completed_ts, ?2) else null end, updated_ts = ?2 where id = ?3 and space_id = ?4 returning id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id",
 	)
@@ -278,9 +502,10 @@ pub(super) async fn update_task_status_for_ctx(
 	.bind(now)
 	.bind(&id)
 	.bind(&ctx.space_id)
-	.fetch_one(&state.pool)
+	.fetch_one(&mut *conn)
 	.await
 	.map_err(|_| StatusCode::NOT_FOUND)?;
+    record_task_event(&mut *conn, ctx, TaskEventKind::Updated, Some(&before), &rec, now).await?;
 
     Ok(rec)
 }
@@ -291,14 +516,27 @@ pub(super) async fn delete_task(
     Path(id): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
     let ctx = ctx_from_headers(&headers, &state).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    let status = delete_task_for_ctx(&mut tx, &ctx, id).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(status)
+}
 
+/// Deletes a task, records its `task_tombstone` and moves it to the trash;
+/// run inside the caller's write transaction so all three land together.
+/// Contributors may only delete tasks they created.
+pub(super) async fn delete_task_for_ctx(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    id: String,
+) -> Result<StatusCode, StatusCode> {
     if ctx.role == Role::Contributor {
         let created_by_user_id: Option<String> = sqlx::query_scalar(
             "select created_by_user_id from task where id = ?1 and space_id = ?2 limit 1",
         )
         .bind(&id)
         .bind(&ctx.space_id)
-        .fetch_optional(&state.pool)
+        .fetch_optional(&mut *conn)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         let Some(created_by_user_id) = created_by_user_id else {
@@ -310,29 +548,29 @@ pub(super) async fn delete_task(
     }
 
     let now = chrono::Utc::now().timestamp_millis();
-    let mut tx = state.pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let deleted_list_id: Option<String> =
-        sqlx::query_scalar("delete from task where id = ?1 and space_id = ?2 returning list_id")
-            .bind(&id)
-            .bind(&ctx.space_id)
-            .fetch_optional(&mut *tx)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let Some(list_id) = deleted_list_id else {
+    let deleted = sqlx::query_as::<_, TaskRow>(
+        "delete from task where id = ?1 and space_id = ?2 returning id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id",
+    )
+    .bind(&id)
+    .bind(&ctx.space_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(deleted) = deleted else {
         return Err(StatusCode::NOT_FOUND);
     };
+    move_to_trash(&mut *conn, ctx, &deleted, now).await?;
+    record_task_event(&mut *conn, ctx, TaskEventKind::Deleted, None, &deleted, now).await?;
     sqlx::query(
         "insert into task_tombstone (task_id, space_id, list_id, deleted_ts) values (?1, ?2, ?3, ?4) on conflict(task_id, space_id) do update set list_id = excluded.list_id, deleted_ts = excluded.deleted_ts",
     )
     .bind(&id)
     .bind(&ctx.space_id)
-    .bind(&list_id)
+    .bind(&deleted.list_id)
     .bind(now)
-    .execute(&mut *tx)
+    .execute(&mut *conn)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -341,35 +579,38 @@ pub(super) async fn update_task_meta(
     headers: HeaderMap,
     Path(id): Path<String>,
     Json(body): Json<UpdateTaskMeta>,
-) -> Result<Json<TaskRow>, StatusCode> {
+) -> Result<Json<TaskRow>, TaskError> {
     let ctx = ctx_from_headers(&headers, &state).await?;
-    let rec = update_task_meta_for_ctx(&state, &ctx, id, body).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    let rec = update_task_meta_for_ctx(&mut tx, &ctx, id, body).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(Json(rec))
 }
 
 pub(super) async fn update_task_meta_for_ctx(
-    state: &AppState,
+    conn: &mut SqliteConnection,
     ctx: &RequestCtx,
     id: String,
     body: UpdateTaskMeta,
-) -> Result<TaskRow, StatusCode> {
+) -> Result<TaskRow, TaskError> {
     if let Some(status) = body.status.as_deref() {
         if !is_valid_task_status(status) {
-            return Err(StatusCode::BAD_REQUEST);
+            return Err(StatusCode::BAD_REQUEST.into());
         }
     }
     let priority = normalize_task_priority(body.priority)?;
+    let recur_rule = normalize_recur_rule(body.recur_rule.clone())?;
 
     if let Some(list_id) = &body.list_id {
         let exists: Option<i64> =
             sqlx::query_scalar("select 1 from list where id = ?1 and space_id = ?2")
                 .bind(list_id)
                 .bind(&ctx.space_id)
-                .fetch_optional(&state.pool)
+                .fetch_optional(&mut *conn)
                 .await
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         if exists.is_none() {
-            return Err(StatusCode::NOT_FOUND);
+            return Err(StatusCode::NOT_FOUND.into());
         }
     }
     let mut my_day = body.my_day.map(|value| if value { 1_i64 } else { 0_i64 });
@@ -387,23 +628,23 @@ pub(super) async fn update_task_meta_for_ctx(
         )
         .bind(&id)
         .bind(&ctx.space_id)
-        .fetch_optional(&state.pool)
+        .fetch_optional(&mut *conn)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
         if existing.created_by_user_id.as_deref() != Some(ctx.user_id.as_str()) {
-            return Err(StatusCode::FORBIDDEN);
+            return Err(StatusCode::FORBIDDEN.into());
         }
 
         if body.my_day == Some(true) {
-            return Err(StatusCode::FORBIDDEN);
+            return Err(StatusCode::FORBIDDEN.into());
         }
         my_day = None;
 
         if let Some(next_assignee) = &body.assignee_user_id {
             if existing.assignee_user_id.as_deref() != Some(next_assignee.as_str()) {
-                return Err(StatusCode::FORBIDDEN);
+                return Err(StatusCode::FORBIDDEN.into());
             }
         }
         assignee_user_id = None;
@@ -415,11 +656,11 @@ pub(super) async fn update_task_meta_for_ctx(
             .bind(&ctx.space_id)
             .bind(&ctx.user_id)
             .bind(list_id)
-            .fetch_optional(&state.pool)
+            .fetch_optional(&mut *conn)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
             if grant_exists.is_none() {
-                return Err(StatusCode::FORBIDDEN);
+                return Err(StatusCode::FORBIDDEN.into());
             }
         }
     }
@@ -429,38 +670,67 @@ pub(super) async fn update_task_meta_for_ctx(
             sqlx::query_scalar("select 1 from membership where space_id = ?1 and user_id = ?2")
                 .bind(&ctx.space_id)
                 .bind(assignee_user_id)
-                .fetch_optional(&state.pool)
+                .fetch_optional(&mut *conn)
                 .await
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         if exists.is_none() {
-            return Err(StatusCode::NOT_FOUND);
+            return Err(StatusCode::NOT_FOUND.into());
         }
     }
 
     let now = chrono::Utc::now().timestamp_millis();
+    let before = load_task_row(&mut *conn, ctx, &id).await?.ok_or(StatusCode::NOT_FOUND)?;
+    let mut status = body.status.clone();
+    let mut due_date = body.due_date.clone();
+    let mut punted_from_due_date = body.punted_from_due_date.clone();
+    let mut punted_on_date = body.punted_on_date.clone();
+    let mut occurrences_completed = body.occurrences_completed;
+    let mut completed_ts = body.completed_ts;
+    // Clients that already rolled the occurrence forward send `pending` plus
+    // the next due date; a `done` that leaves the due date unchanged (or
+    // omits it) is rolled forward here.
+    if status.as_deref() == Some("done") {
+        let current = load_recurrence_state(&mut *conn, ctx, &id).await?;
+        if let Some(mut current) =
+            current.filter(|current| due_date.is_none() || due_date == current.due_date)
+        {
+            if recur_rule.is_some() {
+                current.recur_rule = recur_rule.clone();
+            }
+            if let Some(next_due) = next_recurring_due(&current) {
+                status = Some("pending".to_string());
+                due_date = Some(next_due);
+                punted_from_due_date = None;
+                punted_on_date = None;
+                occurrences_completed = Some(current.occurrences_completed + 1);
+                completed_ts = Some(completed_ts.unwrap_or(now));
+            }
+        }
+    }
     let rec = sqlx::query_as::<_, TaskRow>(
         "update task set title = coalesce(?1, title), status = coalesce(?2, status), list_id = coalesce(?3, list_id), my_day = coalesce(?4, my_day), priority = coalesce(?5, priority), url = coalesce(?6, url), recur_rule = coalesce(?7, recur_rule), due_date = coalesce(?8, due_date), punted_from_due_date = ?9, punted_on_date = ?10, occurrences_completed = coalesce(?11, occurrences_completed), completed_ts = case when ?12 is not null then ?12 when ?2 is null then completed_ts when ?2 = 'done' then coalesce(completed_ts, ?15) else null end, notes = coalesce(?13, notes), assignee_user_id = coalesce(?14, assignee_user_id), updated_ts = ?15 where id = ?16 and space_id = ?17 returning id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id",
     )
     .bind(&body.title)
-    .bind(&body.status)
+    .bind(&status)
     .bind(&body.list_id)
     .bind(my_day)
     .bind(priority)
     .bind(&body.url)
-    .bind(&body.recur_rule)
-    .bind(&body.due_date)
-    .bind(&body.punted_from_due_date)
-    .bind(&body.punted_on_date)
-    .bind(body.occurrences_completed)
-    .bind(body.completed_ts)
+    .bind(&recur_rule)
+    .bind(&due_date)
+    .bind(&punted_from_due_date)
+    .bind(&punted_on_date)
+    .bind(occurrences_completed)
+    .bind(completed_ts)
     .bind(&body.notes)
     .bind(&assignee_user_id)
     .bind(now)
     .bind(&id)
     .bind(&ctx.space_id)
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|_| StatusCode::NOT_FOUND)?;
+    record_task_event(&mut *conn, ctx, TaskEventKind::Updated, Some(&before), &rec, now).await?;
 
     Ok(rec)
 }
@@ -469,7 +739,10 @@ pub fn task_routes(pool: &sqlx::SqlitePool) -> Router {
     let state = app_state(pool);
     Router::new()
         .route("/", get(get_tasks).post(create_task))
+        .route("/trash", get(get_trash))
         .route("/:id", patch(update_task_meta).delete(delete_task))
         .route("/:id/status", post(update_task_status))
+        .route("/:id/restore", post(restore_task))
+        .route("/:id/history", get(get_task_history))
         .with_state(state)
 }
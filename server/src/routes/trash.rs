@@ -0,0 +1,263 @@
+//! Task trash: deleted tasks stay recoverable until purged.
+//!
+//! `delete_task_for_ctx` snapshots the row into `task_trash` in the same
+//! transaction as its tombstone, so every delete path (REST, sync push) is
+//! covered. `POST /tasks/:id/restore` re-inserts the snapshot with a fresh
+//! `updated_ts` and clears the tombstone, so sync clients see the task come
+//! back as an ordinary change. Entries older than `TASK_TRASH_RETENTION_DAYS`
+//! (default 30) are purged by a background job.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::Serialize;
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+use std::time::Duration;
+
+use super::history::{record_task_event, TaskEventKind};
+use super::tasks::TaskRow;
+use super::types::{
+    begin_write, ctx_from_headers, is_unique_violation, parse_retention_days, AppState, RequestCtx,
+    Role,
+};
+
+const DEFAULT_TASK_TRASH_RETENTION_DAYS: i64 = 30;
+
+/// How often the purge job runs; the first pass runs at boot.
+const TASK_TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Parses `TASK_TRASH_RETENTION_DAYS` (see `parse_retention_days`).
+pub fn parse_trash_retention(raw: Option<String>) -> Result<i64, String> {
+    parse_retention_days("TASK_TRASH_RETENTION_DAYS", raw, DEFAULT_TASK_TRASH_RETENTION_DAYS)
+}
+
+/// A trashed task: the row as it was when deleted, plus who deleted it.
+#[derive(Serialize)]
+pub(super) struct TrashedTaskRow {
+    #[serde(flatten)]
+    pub(super) task: TaskRow,
+    pub(super) deleted_ts: i64,
+    pub(super) deleted_by_user_id: String,
+}
+
+#[derive(FromRow)]
+struct TrashEntry {
+    task: String,
+    deleted_ts: i64,
+    deleted_by_user_id: String,
+}
+
+impl TrashEntry {
+    fn into_row(self) -> Result<TrashedTaskRow, StatusCode> {
+        let task =
+            serde_json::from_str(&self.task).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(TrashedTaskRow {
+            task,
+            deleted_ts: self.deleted_ts,
+            deleted_by_user_id: self.deleted_by_user_id,
+        })
+    }
+}
+
+/// Snapshots a just-deleted task; run in the delete's transaction. A second
+/// delete of a re-created id replaces the older snapshot.
+pub(super) async fn move_to_trash(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    task: &TaskRow,
+    deleted_ts: i64,
+) -> Result<(), StatusCode> {
+    let snapshot = serde_json::to_string(task).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query(
+        "insert or replace into task_trash (task_id, space_id, list_id, created_by_user_id, task, deleted_ts, deleted_by_user_id) values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )
+    .bind(&task.id)
+    .bind(&ctx.space_id)
+    .bind(&task.list_id)
+    .bind(&task.created_by_user_id)
+    .bind(&snapshot)
+    .bind(deleted_ts)
+    .bind(&ctx.user_id)
+    .execute(conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(())
+}
+
+pub(super) async fn get_trash(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<TrashedTaskRow>>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let rows = get_trash_for_ctx(&state, &ctx).await?;
+    Ok(Json(rows))
+}
+
+/// Newest deletions first. Admins see the whole space's trash; contributors
+/// see the tasks they could restore — ones they created, in granted lists.
+pub(super) async fn get_trash_for_ctx(
+    state: &AppState,
+    ctx: &RequestCtx,
+) -> Result<Vec<TrashedTaskRow>, StatusCode> {
+    let entries = if ctx.role == Role::Admin {
+        sqlx::query_as::<_, TrashEntry>(
+            "select task, deleted_ts, deleted_by_user_id from task_trash where space_id = ?1 order by deleted_ts desc, task_id asc",
+        )
+        .bind(&ctx.space_id)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        sqlx::query_as::<_, TrashEntry>(
+            "select t.task, t.deleted_ts, t.deleted_by_user_id from task_trash t join list_grant g on g.list_id = t.list_id and g.space_id = t.space_id where t.space_id = ?1 and g.user_id = ?2 and t.created_by_user_id = ?2 order by t.deleted_ts desc, t.task_id asc",
+        )
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+    entries.into_iter().map(TrashEntry::into_row).collect()
+}
+
+pub(super) async fn restore_task(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<TaskRow>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    let rec = restore_task_for_ctx(&mut tx, &ctx, &id).await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(rec))
+}
+
+/// Revives a trashed task into its original list. Contributors follow the
+/// delete rules: only tasks they created, and only in granted lists. `409`
+/// when the list has since been deleted or the id is live again.
+pub(super) async fn restore_task_for_ctx(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    id: &str,
+) -> Result<TaskRow, StatusCode> {
+    let entry = sqlx::query_as::<_, TrashEntry>(
+        "select task, deleted_ts, deleted_by_user_id from task_trash where task_id = ?1 and space_id = ?2",
+    )
+    .bind(id)
+    .bind(&ctx.space_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    let task = entry.into_row()?.task;
+
+    if ctx.role == Role::Contributor {
+        let granted: Option<i64> = sqlx::query_scalar(
+            "select 1 from list_grant where space_id = ?1 and user_id = ?2 and list_id = ?3 limit 1",
+        )
+        .bind(&ctx.space_id)
+        .bind(&ctx.user_id)
+        .bind(&task.list_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if granted.is_none() {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        if task.created_by_user_id.as_deref() != Some(ctx.user_id.as_str()) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    let list_exists: Option<i64> =
+        sqlx::query_scalar("select 1 from list where id = ?1 and space_id = ?2")
+            .bind(&task.list_id)
+            .bind(&ctx.space_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if list_exists.is_none() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let rec = sqlx::query_as::<_, TaskRow>(
+        "insert into task (id, space_id, title, status, list_id, my_day, priority, task_order, updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20) returning id, space_id, title, status, list_id, my_day, priority, task_order as \"order\", updated_ts, created_ts, url, recur_rule, due_date, punted_from_due_date, punted_on_date, occurrences_completed, completed_ts, notes, assignee_user_id, created_by_user_id",
+    )
+    .bind(&task.id)
+    .bind(&ctx.space_id)
+    .bind(&task.title)
+    .bind(&task.status)
+    .bind(&task.list_id)
+    .bind(task.my_day)
+    .bind(task.priority)
+    .bind(&task.order)
+    .bind(now)
+    .bind(task.created_ts)
+    .bind(&task.url)
+    .bind(&task.recur_rule)
+    .bind(&task.due_date)
+    .bind(&task.punted_from_due_date)
+    .bind(&task.punted_on_date)
+    .bind(task.occurrences_completed)
+    .bind(task.completed_ts)
+    .bind(&task.notes)
+    .bind(&task.assignee_user_id)
+    .bind(&task.created_by_user_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|err| {
+        if is_unique_violation(&err) {
+            StatusCode::CONFLICT
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+
+    record_task_event(&mut *conn, ctx, TaskEventKind::Restored, None, &rec, now).await?;
+    sqlx::query("delete from task_tombstone where task_id = ?1 and space_id = ?2")
+        .bind(id)
+        .bind(&ctx.space_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query("delete from task_trash where task_id = ?1 and space_id = ?2")
+        .bind(id)
+        .bind(&ctx.space_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(rec)
+}
+
+/// Deletes trash entries with `deleted_ts < horizon_ts` across all spaces.
+pub(super) async fn purge_expired_trash(
+    pool: &SqlitePool,
+    horizon_ts: i64,
+) -> Result<u64, sqlx::Error> {
+    let purged = sqlx::query("delete from task_trash where deleted_ts < ?1")
+        .bind(horizon_ts)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(purged)
+}
+
+/// Spawns the purge job. Failures are logged and retried on the next tick.
+pub fn spawn_trash_purge(pool: &SqlitePool, retention_ms: i64) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(TASK_TRASH_PURGE_INTERVAL);
+        loop {
+            tick.tick().await;
+            let horizon_ts = chrono::Utc::now().timestamp_millis() - retention_ms;
+            match purge_expired_trash(&pool, horizon_ts).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, horizon_ts, "purged expired task trash"),
+                Err(err) => tracing::warn!(error = %err, "task trash purge failed"),
+            }
+        }
+    });
+}
@@ -0,0 +1,533 @@
+//! TOTP two-factor authentication (RFC 6238: HMAC-SHA1, 6 digits, 30-second
+//! steps).
+//!
+//! Enrollment is two calls: `POST /auth/2fa/setup` (re-checks the password)
+//! stores a pending secret and returns it with an `otpauth://` URI for the
+//! authenticator app; `POST /auth/2fa/verify` enables 2FA once a code from
+//! that app matches, and returns ten single-use recovery codes (only their
+//! hashes are stored). From then on `login` asks for `two_factor_code`, which
+//! takes either a current TOTP code or a recovery code.
+//!
+//! `PUT /auth/2fa/policy` lets an admin require 2FA for every admin of the
+//! space. An admin who has not enrolled keeps their role, so the board and
+//! task writes work as before, but admin-only endpoints (backup restore,
+//! member management) answer `403` with code `two_factor_enrollment_required`
+//! (`RequestCtx::require_admin`), so they stay out of reach of a password
+//! alone while enrollment still works.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, SqlitePool};
+use uuid::Uuid;
+
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::auth::password_matches_for_user;
+use super::opaque_token::opaque_token_hash;
+use super::types::{begin_write, constant_time_eq, ctx_from_headers, AdminError, AppState};
+
+const TOTP_STEP_SECS: i64 = 30;
+const TOTP_DIGITS: usize = 6;
+/// Codes from one step either side are accepted too, for clock drift.
+const TOTP_SKEW_STEPS: i64 = 1;
+const TOTP_ISSUER: &str = "TaskSync";
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Deserialize)]
+pub(super) struct TwoFactorPasswordBody {
+    pub(super) current_password: String,
+}
+
+#[derive(Deserialize)]
+pub(super) struct TwoFactorCodeBody {
+    pub(super) code: String,
+}
+
+#[derive(Deserialize)]
+pub(super) struct TwoFactorPolicyBody {
+    pub(super) require_for_admins: bool,
+}
+
+#[derive(Serialize)]
+pub(super) struct TwoFactorStatusResponse {
+    pub(super) enabled: bool,
+    /// Setup was started but not yet verified.
+    pub(super) pending: bool,
+    pub(super) recovery_codes_remaining: i64,
+    /// The space requires 2FA for admins.
+    pub(super) required_for_admins: bool,
+}
+
+#[derive(Serialize)]
+pub(super) struct TwoFactorSetupResponse {
+    /// Base32, for manual entry.
+    pub(super) secret: String,
+    pub(super) otpauth_uri: String,
+}
+
+#[derive(Serialize)]
+pub(super) struct RecoveryCodesResponse {
+    /// Shown once; only hashes are kept.
+    pub(super) recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub(super) struct TwoFactorPolicyResponse {
+    pub(super) require_for_admins: bool,
+}
+
+/// Outcome of the second login step for a user whose password matched.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum SecondFactor {
+    /// The user has not enabled 2FA.
+    NotEnrolled,
+    /// 2FA is enabled but no code was sent.
+    Missing,
+    Totp,
+    RecoveryCode,
+    Invalid,
+}
+
+/// 160 bits, the RFC 4226 recommendation, hashed down from two v4 UUIDs.
+fn new_totp_secret() -> Vec<u8> {
+    let mut seed = Uuid::new_v4().as_bytes().to_vec();
+    seed.extend_from_slice(Uuid::new_v4().as_bytes());
+    Sha256::digest(&seed)[..20].to_vec()
+}
+
+pub(super) fn totp_code(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = usize::from(digest[digest.len() - 1] & 0x0f);
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS as u32), width = TOTP_DIGITS)
+}
+
+/// The step whose code matches `code`, searching around `now_secs`.
+fn matching_step(secret: &[u8], code: &str, now_secs: i64) -> Option<i64> {
+    let current = now_secs / TOTP_STEP_SECS;
+    (current - TOTP_SKEW_STEPS..=current + TOTP_SKEW_STEPS)
+        .find(|step| constant_time_eq(&totp_code(secret, *step), code))
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn otpauth_uri(secret_base32: &str, email: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret_base32}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_STEP_SECS}",
+        issuer = percent_encode(TOTP_ISSUER),
+        account = percent_encode(email),
+    )
+}
+
+/// `xxxx-xxxx-xxxx`, 48 random hex bits.
+fn new_recovery_code() -> String {
+    let hex = Uuid::new_v4().simple().to_string();
+    format!("{}-{}-{}", &hex[..4], &hex[4..8], &hex[8..12])
+}
+
+/// Case, spaces and dashes are ignored so a hand-typed code still matches.
+fn recovery_code_hash(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    opaque_token_hash(&normalized)
+}
+
+/// Checks the second login step. An accepted TOTP code advances
+/// `last_used_step`, so the same code cannot sign in twice; an accepted
+/// recovery code is spent.
+pub(super) async fn check_second_factor(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    code: Option<&str>,
+) -> Result<SecondFactor, StatusCode> {
+    let secret: Option<String> = sqlx::query_scalar(
+        "select secret from user_totp where user_id = ?1 and enabled_ts is not null",
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(secret) = secret else {
+        return Ok(SecondFactor::NotEnrolled);
+    };
+    let code: String = code.unwrap_or_default().chars().filter(|c| !c.is_whitespace()).collect();
+    if code.is_empty() {
+        return Ok(SecondFactor::Missing);
+    }
+    let now = chrono::Utc::now();
+
+    if is_totp_code(&code) {
+        let secret = BASE32_NOPAD
+            .decode(secret.as_bytes())
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let Some(step) = matching_step(&secret, &code, now.timestamp()) else {
+            return Ok(SecondFactor::Invalid);
+        };
+        let advanced = sqlx::query(
+            "update user_totp set last_used_step = ?1 where user_id = ?2 and last_used_step < ?1",
+        )
+        .bind(step)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .rows_affected();
+        return Ok(if advanced == 1 { SecondFactor::Totp } else { SecondFactor::Invalid });
+    }
+
+    let spent = sqlx::query(
+        "update recovery_code set used_ts = ?1 where user_id = ?2 and code_hash = ?3 and used_ts is null",
+    )
+    .bind(now.timestamp_millis())
+    .bind(user_id)
+    .bind(recovery_code_hash(&code))
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .rows_affected();
+    Ok(if spent == 1 { SecondFactor::RecoveryCode } else { SecondFactor::Invalid })
+}
+
+pub(super) async fn auth_two_factor_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<TwoFactorStatusResponse>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let enabled_ts: Option<Option<i64>> =
+        sqlx::query_scalar("select enabled_ts from user_totp where user_id = ?1")
+            .bind(&ctx.user_id)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let recovery_codes_remaining: i64 = sqlx::query_scalar(
+        "select count(*) from recovery_code where user_id = ?1 and used_ts is null",
+    )
+    .bind(&ctx.user_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let required_for_admins = admin_two_factor_required(&state.pool, &ctx.space_id).await?;
+    Ok(Json(TwoFactorStatusResponse {
+        enabled: matches!(enabled_ts, Some(Some(_))),
+        pending: matches!(enabled_ts, Some(None)),
+        recovery_codes_remaining,
+        required_for_admins,
+    }))
+}
+
+/// Starts (or restarts) enrollment with a new secret. `409` once 2FA is
+/// enabled; disable it first to move to a new authenticator.
+pub(super) async fn auth_two_factor_setup(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<TwoFactorPasswordBody>,
+) -> Result<Json<TwoFactorSetupResponse>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let current_password = body.current_password.trim();
+    if current_password.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !password_matches_for_user(&state, &ctx.user_id, current_password).await? {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let email: String = sqlx::query_scalar("select email from user where id = ?1")
+        .bind(&ctx.user_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let secret = BASE32_NOPAD.encode(&new_totp_secret());
+    let stored = sqlx::query(
+        "insert into user_totp (user_id, secret, created_ts) values (?1, ?2, ?3) on conflict(user_id) do update set secret = excluded.secret, created_ts = excluded.created_ts, last_used_step = 0 where user_totp.enabled_ts is null",
+    )
+    .bind(&ctx.user_id)
+    .bind(&secret)
+    .bind(chrono::Utc::now().timestamp_millis())
+    .execute(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .rows_affected();
+    if stored == 0 {
+        return Err(StatusCode::CONFLICT);
+    }
+    let otpauth_uri = otpauth_uri(&secret, &email);
+    Ok(Json(TwoFactorSetupResponse { secret, otpauth_uri }))
+}
+
+/// Completes enrollment. `404` without a pending setup, `409` if already
+/// enabled, `400` for a code that does not match.
+pub(super) async fn auth_two_factor_verify(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<TwoFactorCodeBody>,
+) -> Result<Json<RecoveryCodesResponse>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let code: String = body.code.chars().filter(|c| !c.is_whitespace()).collect();
+    if !is_totp_code(&code) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let now = chrono::Utc::now();
+    let mut tx = begin_write(&state.pool).await?;
+    let (secret, enabled_ts): (String, Option<i64>) =
+        sqlx::query_as("select secret, enabled_ts from user_totp where user_id = ?1")
+            .bind(&ctx.user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+    if enabled_ts.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+    let secret =
+        BASE32_NOPAD.decode(secret.as_bytes()).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let step = matching_step(&secret, &code, now.timestamp()).ok_or(StatusCode::BAD_REQUEST)?;
+
+    sqlx::query("update user_totp set enabled_ts = ?1, last_used_step = ?2 where user_id = ?3")
+        .bind(now.timestamp_millis())
+        .bind(step)
+        .bind(&ctx.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query("delete from recovery_code where user_id = ?1")
+        .bind(&ctx.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let recovery_codes: Vec<String> =
+        (0..RECOVERY_CODE_COUNT).map(|_| new_recovery_code()).collect();
+    for recovery_code in &recovery_codes {
+        sqlx::query(
+            "insert into recovery_code (id, user_id, code_hash, created_ts) values (?1, ?2, ?3, ?4)",
+        )
+        .bind(format!("rc-{}", Uuid::new_v4()))
+        .bind(&ctx.user_id)
+        .bind(recovery_code_hash(recovery_code))
+        .bind(now.timestamp_millis())
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::TwoFactorEnabled,
+            target_user_id: Some(&ctx.user_id),
+            detail: json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+async fn remove_two_factor(conn: &mut SqliteConnection, user_id: &str) -> Result<bool, StatusCode> {
+    let removed = sqlx::query("delete from user_totp where user_id = ?1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .rows_affected();
+    sqlx::query("delete from recovery_code where user_id = ?1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(removed > 0)
+}
+
+/// Turns 2FA off (or abandons a pending setup). Re-checks the password.
+pub(super) async fn auth_two_factor_disable(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<TwoFactorPasswordBody>,
+) -> Result<StatusCode, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let current_password = body.current_password.trim();
+    if current_password.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !password_matches_for_user(&state, &ctx.user_id, current_password).await? {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let mut tx = begin_write(&state.pool).await?;
+    if !remove_two_factor(&mut tx, &ctx.user_id).await? {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::TwoFactorDisabled,
+            target_user_id: Some(&ctx.user_id),
+            detail: json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Admin-only: removes a member's 2FA when they have lost both their
+/// authenticator and their recovery codes.
+pub(super) async fn auth_reset_member_two_factor(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, AdminError> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    ctx.require_admin()?;
+    let mut tx = begin_write(&state.pool).await?;
+    let member: Option<i64> =
+        sqlx::query_scalar("select 1 from membership where space_id = ?1 and user_id = ?2")
+            .bind(&ctx.space_id)
+            .bind(&user_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if member.is_none() || !remove_two_factor(&mut tx, &user_id).await? {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::TwoFactorDisabled,
+            target_user_id: Some(&user_id),
+            detail: json!({}),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn admin_two_factor_required(pool: &SqlitePool, space_id: &str) -> Result<bool, StatusCode> {
+    let required: Option<bool> =
+        sqlx::query_scalar("select require_admin_two_factor from space where id = ?1")
+            .bind(space_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(required.unwrap_or(false))
+}
+
+/// Admin-only. Turning the requirement on is `409` unless the caller has
+/// 2FA enabled, so an admin cannot demote themselves by accident.
+pub(super) async fn auth_set_two_factor_policy(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<TwoFactorPolicyBody>,
+) -> Result<Json<TwoFactorPolicyResponse>, AdminError> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    ctx.require_admin()?;
+    if body.require_for_admins {
+        let enabled: Option<i64> = sqlx::query_scalar(
+            "select 1 from user_totp where user_id = ?1 and enabled_ts is not null",
+        )
+        .bind(&ctx.user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if enabled.is_none() {
+            return Err(StatusCode::CONFLICT.into());
+        }
+    }
+    let mut tx = begin_write(&state.pool).await?;
+    sqlx::query("update space set require_admin_two_factor = ?1 where id = ?2")
+        .bind(body.require_for_admins)
+        .bind(&ctx.space_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::TwoFactorPolicyChanged,
+            target_user_id: None,
+            detail: json!({ "require_for_admins": body.require_for_admins }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(TwoFactorPolicyResponse { require_for_admins: body.require_for_admins }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totp_matches_rfc_6238_sha1_vectors() {
+        // RFC 6238 appendix B, truncated to six digits.
+        let secret = b"12345678901234567890";
+        assert_eq!(totp_code(secret, 59 / 30), "287082");
+        assert_eq!(totp_code(secret, 1_111_111_109 / 30), "081804");
+        assert_eq!(totp_code(secret, 1_234_567_890 / 30), "005924");
+        assert_eq!(totp_code(secret, 2_000_000_000 / 30), "279037");
+    }
+
+    #[test]
+    fn matching_step_allows_one_step_of_drift() {
+        let secret = b"12345678901234567890";
+        let now = 1_111_111_109;
+        let step = now / TOTP_STEP_SECS;
+        assert_eq!(matching_step(secret, &totp_code(secret, step), now), Some(step));
+        assert_eq!(matching_step(secret, &totp_code(secret, step - 1), now), Some(step - 1));
+        assert_eq!(matching_step(secret, &totp_code(secret, step + 1), now), Some(step + 1));
+        assert_eq!(matching_step(secret, &totp_code(secret, step + 2), now), None);
+    }
+
+    #[test]
+    fn recovery_codes_match_regardless_of_case_spacing_and_dashes() {
+        assert_eq!(recovery_code_hash("ab12-cd34-ef56"), recovery_code_hash(" AB12 CD34EF56 "));
+        assert_ne!(recovery_code_hash("ab12-cd34-ef56"), recovery_code_hash("ab12-cd34-ef57"));
+    }
+
+    #[test]
+    fn otpauth_uri_encodes_the_account_label() {
+        assert_eq!(
+            otpauth_uri("JBSWY3DPEHPK3PXP", "a+b@example.com"),
+            "otpauth://totp/TaskSync:a%2Bb%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=TaskSync&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use axum::http::{header::AUTHORIZATION, HeaderMap, HeaderName, StatusCode};
+use axum::{
+    http::{header::AUTHORIZATION, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
@@ -6,9 +10,17 @@ use sha2::{Digest, Sha256};
 use sqlx::SqlitePool;
 use std::{
     env,
+    sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
+use super::api_tokens::ctx_from_stored_api_token;
+use super::login_throttle::{parse_login_throttle, LoginThrottleConfig};
+use super::mailer::{parse_mailer, Mailer};
+use super::oidc::{parse_oidc, OidcProvider};
+use super::webauthn::{parse_webauthn, WebauthnConfig};
+use super::webhooks::parse_webhook_private_targets;
+
 /// Request header carrying the programmatic API token (F-B). Read by
 /// `ctx_from_api_token` only — `ctx_from_headers` never inspects it, and the
 /// verifier never inspects `Authorization`. The two auth paths are disjoint
@@ -24,10 +36,22 @@ pub(super) const API_TOKEN_MIN_LEN: usize = 24;
 pub(super) struct AppState {
     pub(super) pool: SqlitePool,
     pub(super) jwt_secret: String,
-    /// Optional static token for the programmatic task-creation API (F-B).
-    /// `None` when `TASK_API_TOKEN` is unset — the feature is off and
-    /// `ctx_from_api_token` rejects every request regardless of header.
+    /// Optional legacy static token for the programmatic task-creation API
+    /// (F-B), resolving to the owner. `None` when `TASK_API_TOKEN` is unset;
+    /// named tokens minted at `POST /auth/api-tokens` work either way (see
+    /// `api_tokens.rs`).
     pub(super) api_token: Option<String>,
+    /// Failure limits and lockout durations for login/setup (#048).
+    pub(super) login_throttle: LoginThrottleConfig,
+    /// `None` when `MAILER` is unset (see `mailer.rs`).
+    pub(super) mailer: Option<Arc<dyn Mailer>>,
+    /// `None` when `OIDC_ISSUER` is unset (see `oidc.rs`).
+    pub(super) oidc: Option<Arc<OidcProvider>>,
+    /// `None` when `WEBAUTHN_RP_ID` is unset (see `webauthn.rs`).
+    pub(super) webauthn: Option<WebauthnConfig>,
+    /// `WEBHOOK_ALLOW_PRIVATE_TARGETS` (default false): accept webhook URLs
+    /// on loopback and private addresses (see `webhooks.rs`).
+    pub(super) allow_private_webhook_targets: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -43,6 +67,11 @@ pub(super) struct AuthClaims {
     /// bump.
     #[serde(default)]
     pub(super) tv: i64,
+    /// Device session (`device_session.id`) the token was issued to. Absent
+    /// on tokens minted before per-device sessions; those are only subject
+    /// to the `tv` check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) sid: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -52,15 +81,132 @@ pub(super) enum Role {
 }
 
 /// What kind of caller resolved this `RequestCtx`. Carried as
-/// defense-in-depth alongside `role`: a session login and the programmatic
-/// API token both resolve to `Role::Admin` for the owner, but `scope`
-/// distinguishes "a real session" from "the create-task-only API token" so
-/// `create_task_via_api_token` can assert the narrower scope even though the
-/// two paths already resolve disjoint identities.
+/// defense-in-depth alongside `role`: a session login and an API token can
+/// both resolve to `Role::Admin`, but `scope` distinguishes "a real
+/// session" from "an API token limited to these `ApiScopes`", and every
+/// `/api` route asserts the one scope it needs (`ApiCtx`).
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(super) enum AuthScope {
     Session,
-    ApiTaskCreate,
+    ApiToken(ApiScopes),
+}
+
+impl AuthScope {
+    /// Stable name stored in history records. Events written before scoped
+    /// tokens, as `api_task_create`, are renamed by migration `0041`.
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            AuthScope::Session => "session",
+            AuthScope::ApiToken(_) => "api_token",
+        }
+    }
+
+    /// Sessions hold no API scopes; they never reach the `/api` routes.
+    pub(super) fn allows(self, scope: ApiScope) -> bool {
+        match self {
+            AuthScope::Session => false,
+            AuthScope::ApiToken(scopes) => scopes.contains(scope),
+        }
+    }
+}
+
+/// One permission an API token can be granted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ApiScope {
+    TasksRead,
+    TasksWrite,
+    TasksComplete,
+    ListsRead,
+    BackupExport,
+}
+
+impl ApiScope {
+    const ALL: [ApiScope; 5] = [
+        ApiScope::TasksRead,
+        ApiScope::TasksWrite,
+        ApiScope::TasksComplete,
+        ApiScope::ListsRead,
+        ApiScope::BackupExport,
+    ];
+
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            ApiScope::TasksRead => "tasks:read",
+            ApiScope::TasksWrite => "tasks:write",
+            ApiScope::TasksComplete => "tasks:complete",
+            ApiScope::ListsRead => "lists:read",
+            ApiScope::BackupExport => "backup:export",
+        }
+    }
+
+    pub(super) fn parse(raw: &str) -> Option<Self> {
+        ApiScope::ALL.into_iter().find(|scope| scope.as_str() == raw.trim())
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The set of `ApiScope`s granted to a token. Stored space-separated in
+/// `api_token.scopes` and serialized as a list of names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) struct ApiScopes(u8);
+
+impl ApiScopes {
+    pub(super) fn contains(self, scope: ApiScope) -> bool {
+        self.0 & scope.bit() != 0
+    }
+
+    pub(super) fn with(self, scope: ApiScope) -> Self {
+        ApiScopes(self.0 | scope.bit())
+    }
+
+    pub(super) fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// `None` if any name is not a known scope.
+    pub(super) fn parse<'a>(names: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        names.into_iter().try_fold(ApiScopes::default(), |scopes, name| {
+            Some(scopes.with(ApiScope::parse(name)?))
+        })
+    }
+
+    pub(super) fn names(self) -> Vec<&'static str> {
+        ApiScope::ALL
+            .into_iter()
+            .filter(|scope| self.contains(*scope))
+            .map(ApiScope::as_str)
+            .collect()
+    }
+}
+
+impl From<ApiScope> for ApiScopes {
+    fn from(scope: ApiScope) -> Self {
+        ApiScopes::default().with(scope)
+    }
+}
+
+impl std::fmt::Display for ApiScopes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.names().join(" "))
+    }
+}
+
+impl TryFrom<String> for ApiScopes {
+    type Error = String;
+
+    fn try_from(column: String) -> Result<Self, Self::Error> {
+        ApiScopes::parse(column.split_whitespace())
+            .ok_or_else(|| format!("unknown API scope in {column:?}"))
+    }
+}
+
+impl Serialize for ApiScopes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.names())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -69,6 +215,89 @@ pub(super) struct RequestCtx {
     pub(super) user_id: String,
     pub(super) role: Role,
     pub(super) scope: AuthScope,
+    /// The device session of a session token carrying a `sid` claim.
+    pub(super) session_id: Option<String>,
+    /// An admin of a space that requires 2FA for admins who has not enrolled
+    /// yet: they keep their role for reads and task writes, but admin-only
+    /// routes refuse them (see `require_admin`).
+    pub(super) two_factor_enrollment_required: bool,
+}
+
+impl RequestCtx {
+    /// Gate of the admin-only routes: `403` for anyone but an admin, coded
+    /// `two_factor_enrollment_required` for an admin who still has to enroll
+    /// in 2FA.
+    pub(super) fn require_admin(&self) -> Result<(), AdminError> {
+        if self.role != Role::Admin {
+            return Err(AdminError::Status(StatusCode::FORBIDDEN));
+        }
+        if self.two_factor_enrollment_required {
+            return Err(AdminError::TwoFactorEnrollmentRequired);
+        }
+        Ok(())
+    }
+}
+
+/// Failure from the admin-only routes. Every failure renders as a bare status
+/// except the refusal of an admin who has not enrolled in the 2FA the space
+/// requires, which carries a coded body so the client can send them to
+/// enrollment.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum AdminError {
+    Status(StatusCode),
+    TwoFactorEnrollmentRequired,
+}
+
+impl AdminError {
+    pub(super) fn status(&self) -> StatusCode {
+        match self {
+            AdminError::Status(status) => *status,
+            AdminError::TwoFactorEnrollmentRequired => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+impl From<StatusCode> for AdminError {
+    fn from(status: StatusCode) -> Self {
+        AdminError::Status(status)
+    }
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        match self {
+            AdminError::Status(status) => status.into_response(),
+            AdminError::TwoFactorEnrollmentRequired => (
+                StatusCode::FORBIDDEN,
+                Json(ErrorBody::new(
+                    "two_factor_enrollment_required",
+                    "enroll in two-factor authentication to use admin features",
+                )),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// `{ "error": { "code": "...", "message": "..." } }` — namespaced under
+/// `error` so it never collides with a success shape such as `TaskRow`,
+/// which has no `error` field. Shared by every route that returns coded
+/// errors instead of a bare status.
+#[derive(Serialize)]
+pub(super) struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail {
+    code: &'static str,
+    message: &'static str,
+}
+
+impl ErrorBody {
+    pub(super) fn new(code: &'static str, message: &'static str) -> Self {
+        ErrorBody { error: ErrorDetail { code, message } }
+    }
 }
 
 pub(super) const JWT_SECRET_DENYLIST: [&str; 2] = ["tasksync-dev-secret", "change-me"];
@@ -118,10 +347,17 @@ fn validate_api_token_length(value: Option<&str>) -> Result<(), String> {
 /// would only block operators over a variable that gates nothing. A stale
 /// `DEV_LOGIN_PASSWORD` left in `.env` is simply ignored.
 ///
-/// `TASK_API_TOKEN` (F-B, programmatic task-creation API) is optional — its
-/// absence is not a failure, the feature is simply off — but when present it
-/// must meet the fail-closed minimum length so a short/guessable value can
-/// never reach production.
+/// `TASK_API_TOKEN` (F-B, the legacy static token for the programmatic
+/// task-creation API) is optional — its absence is not a failure, admins
+/// mint named tokens instead — but when present it must meet the
+/// fail-closed minimum length so a short/guessable value can never reach
+/// production.
+///
+/// The login throttle variables (`LOGIN_MAX_FAILURES_PER_ACCOUNT` etc., see
+/// `login_throttle.rs`) are optional too; a malformed value fails closed
+/// rather than silently weakening the lockout. So are `MAILER`, the
+/// `OIDC_*` single sign-on settings, the `WEBAUTHN_*` passkey settings and
+/// `WEBHOOK_ALLOW_PRIVATE_TARGETS`.
 pub fn validate_boot_secrets() -> Result<(), String> {
     let mut failures: Vec<String> = Vec::new();
     if let Err(message) =
@@ -132,6 +368,21 @@ pub fn validate_boot_secrets() -> Result<(), String> {
     if let Err(message) = validate_api_token_length(env::var("TASK_API_TOKEN").ok().as_deref()) {
         failures.push(message);
     }
+    if let Err(message) = parse_login_throttle(|name| env::var(name).ok()) {
+        failures.push(message);
+    }
+    if let Err(message) = parse_mailer(|name| env::var(name).ok()) {
+        failures.push(message);
+    }
+    if let Err(message) = parse_oidc(|name| env::var(name).ok()) {
+        failures.push(message);
+    }
+    if let Err(message) = parse_webauthn(|name| env::var(name).ok()) {
+        failures.push(message);
+    }
+    if let Err(message) = parse_webhook_private_targets(|name| env::var(name).ok()) {
+        failures.push(message);
+    }
     if failures.is_empty() {
         Ok(())
     } else {
@@ -139,6 +390,34 @@ pub fn validate_boot_secrets() -> Result<(), String> {
     }
 }
 
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Parses a `*_RETENTION_DAYS` variable into a window in milliseconds.
+/// Unset/empty/whitespace-only input yields `default_days`. Anything other
+/// than a positive whole number of days is a boot failure (`Err` naming the
+/// variable and value): a typo silently falling back to the default could
+/// delete data someone still needs.
+pub(super) fn parse_retention_days(
+    name: &str,
+    raw: Option<String>,
+    default_days: i64,
+) -> Result<i64, String> {
+    let Some(value) = raw.filter(|value| !value.trim().is_empty()) else {
+        return Ok(default_days * DAY_MS);
+    };
+    value
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|days| *days > 0)
+        .and_then(|days| days.checked_mul(DAY_MS))
+        .ok_or_else(|| {
+            format!(
+                "invalid {name} \"{value}\": expected a positive whole number of days, e.g. {default_days}"
+            )
+        })
+}
+
 pub(super) fn app_state(pool: &SqlitePool) -> AppState {
     let api_token =
         env::var("TASK_API_TOKEN").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
@@ -150,6 +429,18 @@ pub(super) fn app_state(pool: &SqlitePool) -> AppState {
         jwt_secret: env::var("JWT_SECRET")
             .expect("JWT_SECRET validated by validate_boot_secrets at boot"),
         api_token,
+        login_throttle: parse_login_throttle(|name| env::var(name).ok())
+            .expect("login throttle validated by validate_boot_secrets at boot"),
+        mailer: parse_mailer(|name| env::var(name).ok())
+            .expect("mailer validated by validate_boot_secrets at boot")
+            .build(),
+        oidc: parse_oidc(|name| env::var(name).ok())
+            .expect("OIDC settings validated by validate_boot_secrets at boot")
+            .map(|config| config.build()),
+        webauthn: parse_webauthn(|name| env::var(name).ok())
+            .expect("WebAuthn settings validated by validate_boot_secrets at boot"),
+        allow_private_webhook_targets: parse_webhook_private_targets(|name| env::var(name).ok())
+            .expect("WEBHOOK_ALLOW_PRIVATE_TARGETS validated by validate_boot_secrets at boot"),
     }
 }
 
@@ -157,17 +448,25 @@ pub(super) fn unix_now_secs() -> usize {
     SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as usize).unwrap_or(0)
 }
 
+/// Lifetime of an access JWT. Clients stay signed in past it through
+/// `POST /auth/refresh` (see `session.rs`).
+pub(super) const ACCESS_TOKEN_TTL_SECS: usize = 15 * 60;
+
+/// `session_id` binds the token to a device session (`sid`): it stops working
+/// as soon as that session is revoked.
 pub(super) fn issue_token(
     user_id: &str,
     space_id: &str,
     token_version: i64,
+    session_id: Option<&str>,
     secret: &str,
 ) -> Result<String, StatusCode> {
     let claims = AuthClaims {
         sub: user_id.to_string(),
         space_id: space_id.to_string(),
-        exp: unix_now_secs() + (60 * 60 * 24 * 30),
+        exp: unix_now_secs() + ACCESS_TOKEN_TTL_SECS,
         tv: token_version,
+        sid: session_id.map(str::to_string),
     };
     encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
@@ -188,25 +487,33 @@ pub(super) fn password_meets_policy(password: &str) -> bool {
 /// Resolves the caller's role AND current server-side `token_version` in a
 /// single joined query (membership JOIN user) — this is the one per-request
 /// DB round-trip `ctx_from_headers` performs; the `token_version` revocation
-/// check is folded into it rather than adding a second query.
+/// check is folded into it rather than adding a second query, and so is the
+/// liveness of the access token's device session (`session_id`, the `sid`
+/// claim) via a LEFT JOIN on `device_session`. A revoked or foreign session
+/// is `401`. The last value tells whether the caller is an admin who still
+/// has to enroll in the 2FA the space requires
+/// (`RequestCtx::two_factor_enrollment_required`).
 pub(super) async fn resolve_identity(
     pool: &SqlitePool,
     space_id: &str,
     user_id: &str,
-) -> Result<(Role, i64), StatusCode> {
-    let row: Option<(String, i64)> = sqlx::query_as(
-        "select m.role, u.token_version from membership m join user u on u.id = m.user_id where m.space_id = ?1 and m.user_id = ?2 limit 1",
+    session_id: Option<&str>,
+) -> Result<(Role, i64, bool), StatusCode> {
+    let row: Option<(String, i64, bool, bool)> = sqlx::query_as(
+        "select m.role, u.token_version, s.require_admin_two_factor and not exists(select 1 from user_totp t where t.user_id = u.id and t.enabled_ts is not null), ?3 is null or d.id is not null from membership m join user u on u.id = m.user_id join space s on s.id = m.space_id left join device_session d on d.id = ?3 and d.user_id = m.user_id and d.revoked_ts is null where m.space_id = ?1 and m.user_id = ?2 limit 1",
     )
     .bind(space_id)
     .bind(user_id)
+    .bind(session_id)
     .fetch_optional(pool)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     match row {
-        Some((role_str, token_version)) => match role_str.as_str() {
-            "admin" => Ok((Role::Admin, token_version)),
-            "contributor" => Ok((Role::Contributor, token_version)),
+        Some((_, _, _, false)) => Err(StatusCode::UNAUTHORIZED),
+        Some((role_str, token_version, missing_two_factor, _)) => match role_str.as_str() {
+            "admin" => Ok((Role::Admin, token_version, missing_two_factor)),
+            "contributor" => Ok((Role::Contributor, token_version, false)),
             _ => Err(StatusCode::UNAUTHORIZED),
         },
         None => Err(StatusCode::UNAUTHORIZED),
@@ -225,17 +532,20 @@ pub(super) async fn ctx_from_headers(
                 &Validation::default(),
             )
             .map_err(|_| StatusCode::UNAUTHORIZED)?;
-            let (role, token_version) =
-                resolve_identity(&state.pool, &decoded.claims.space_id, &decoded.claims.sub)
+            let claims = decoded.claims;
+            let (role, token_version, two_factor_enrollment_required) =
+                resolve_identity(&state.pool, &claims.space_id, &claims.sub, claims.sid.as_deref())
                     .await?;
-            if decoded.claims.tv != token_version {
+            if claims.tv != token_version {
                 return Err(StatusCode::UNAUTHORIZED);
             }
             return Ok(RequestCtx {
-                space_id: decoded.claims.space_id,
-                user_id: decoded.claims.sub,
+                space_id: claims.space_id,
+                user_id: claims.sub,
                 role,
                 scope: AuthScope::Session,
+                session_id: claims.sid,
+                two_factor_enrollment_required,
             });
         }
     }
@@ -260,30 +570,33 @@ pub(super) fn constant_time_eq(a: &str, b: &str) -> bool {
     digest_a == digest_b
 }
 
-/// Resolves a `RequestCtx` for the programmatic task-creation API (F-B).
+/// Resolves a `RequestCtx` for the programmatic API (F-B).
 ///
 /// Disjoint from `ctx_from_headers`: this verifier reads ONLY the
 /// `X-TaskSync-Api-Token` header and never inspects `Authorization`;
 /// `ctx_from_headers` never inspects the API-token header. A caller
 /// presenting a valid token does NOT get to pick an arbitrary `uid`/
-/// `space_id` — the identity is resolved server-side from the single owner
-/// admin membership, exactly like `auth_status`/`auth_setup` resolve
-/// "the owner" without trusting client input.
+/// `space_id` — the identity is resolved server-side: the member a named
+/// database token is bound to (see `api_tokens.rs`), or for the legacy
+/// `TASK_API_TOKEN` the single owner admin membership, exactly like
+/// `auth_status`/`auth_setup` resolve "the owner" without trusting client
+/// input. A named token carries the scopes it was minted with; the legacy
+/// token only ever has `tasks:write`.
 ///
-/// Consumed by `create_task_via_api_token` (`POST /api/tasks`,
-/// `integrations.rs`).
+/// Consumed by `api_ctx` (the `/api` routes, `integrations.rs`).
 pub(super) async fn ctx_from_api_token(
     headers: &HeaderMap,
     state: &AppState,
 ) -> Result<RequestCtx, StatusCode> {
-    let Some(configured) = state.api_token.as_deref() else {
-        return Err(StatusCode::UNAUTHORIZED);
-    };
     let Some(provided) = headers.get(API_TOKEN_HEADER).and_then(|v| v.to_str().ok()) else {
         return Err(StatusCode::UNAUTHORIZED);
     };
-    if !constant_time_eq(provided, configured) {
-        return Err(StatusCode::UNAUTHORIZED);
+    let is_legacy_token =
+        state.api_token.as_deref().is_some_and(|configured| constant_time_eq(provided, configured));
+    if !is_legacy_token {
+        return ctx_from_stored_api_token(&state.pool, provided)
+            .await?
+            .ok_or(StatusCode::UNAUTHORIZED);
     }
 
     let owner: Option<(String, String)> = sqlx::query_as(
@@ -294,7 +607,14 @@ pub(super) async fn ctx_from_api_token(
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let (space_id, user_id) = owner.ok_or(StatusCode::UNAUTHORIZED)?;
-    Ok(RequestCtx { space_id, user_id, role: Role::Admin, scope: AuthScope::ApiTaskCreate })
+    Ok(RequestCtx {
+        space_id,
+        user_id,
+        role: Role::Admin,
+        scope: AuthScope::ApiToken(ApiScope::TasksWrite.into()),
+        session_id: None,
+        two_factor_enrollment_required: false,
+    })
 }
 
 pub(super) fn normalize_avatar_icon(raw: Option<String>) -> Option<String> {
@@ -612,6 +932,15 @@ pub(super) fn normalize_streak_settings_json(
     .map_err(|_| StatusCode::BAD_REQUEST)
 }
 
+/// Opens a write transaction with `BEGIN IMMEDIATE`, taking SQLite's write
+/// lock up front (waiting out `busy_timeout`) instead of failing with
+/// `SQLITE_BUSY` when a read inside the transaction later upgrades to a write.
+pub(super) async fn begin_write(
+    pool: &SqlitePool,
+) -> Result<sqlx::Transaction<'static, sqlx::Sqlite>, StatusCode> {
+    pool.begin_with("BEGIN IMMEDIATE").await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 pub(super) fn is_unique_violation(err: &sqlx::Error) -> bool {
     match err {
         sqlx::Error::Database(db_err) => db_err.message().contains("UNIQUE constraint failed"),
@@ -0,0 +1,751 @@
+//! Passkey sign-in (WebAuthn Level 2, relying-party side).
+//!
+//! Off unless `WEBAUTHN_RP_ID` is set (see `parse_webauthn`). A signed-in
+//! user registers a passkey with `POST /auth/webauthn/register/start`, which
+//! returns creation options for `navigator.credentials.create()`, and
+//! `POST /auth/webauthn/register/finish` with the resulting credential. To
+//! sign in, `POST /auth/webauthn/login/start` returns request options for
+//! `navigator.credentials.get()` and the assertion goes to `POST /auth/login`
+//! as `passkey`, in place of the password. Passkeys are registered as
+//! discoverable and the request options never list credential ids, so the
+//! unauthenticated start reveals nothing about any account. Options and credentials use the
+//! spec's JSON encoding (camelCase, binary fields as base64url), i.e. what
+//! `PublicKeyCredential.parseCreationOptionsFromJSON()` and `toJSON()` use.
+//!
+//! Attestation is not requested or checked: any authenticator the user owns
+//! is accepted. Assertions must come from one of `WEBAUTHN_ORIGINS`, carry
+//! the user-presence flag and be signed by the registered key. A passkey
+//! assertion with user verification (PIN or biometric) also stands in for
+//! TOTP; without it, `login` still asks for `two_factor_code`.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::Value;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::types::{begin_write, ctx_from_headers, is_unique_violation, AppState};
+
+/// How long the browser has to answer a challenge.
+const CHALLENGE_TTL_MS: i64 = 5 * 60 * 1000;
+const MAX_CREDENTIAL_LABEL_CHARS: usize = 80;
+
+const COSE_ALG_ES256: i64 = -7;
+const COSE_ALG_EDDSA: i64 = -8;
+const COSE_ALG_RS256: i64 = -257;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// Read from the environment by `app_state`; validated at boot by
+/// `validate_boot_secrets`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct WebauthnConfig {
+    /// `WEBAUTHN_RP_ID`: the domain passkeys are scoped to, e.g.
+    /// `tasks.example.com` (no scheme or port).
+    pub(super) rp_id: String,
+    /// `WEBAUTHN_RP_NAME` (default "TaskSync"), shown by the authenticator.
+    pub(super) rp_name: String,
+    /// `WEBAUTHN_ORIGINS`: comma-separated origins the web client runs on
+    /// (default `https://<rp id>`).
+    pub(super) origins: Vec<String>,
+}
+
+/// Parses the `WEBAUTHN_*` variables. Unset/blank `WEBAUTHN_RP_ID` disables
+/// passkeys; the RP ID must be a bare host name and every origin a
+/// `scheme://host[:port]` with no path.
+pub(super) fn parse_webauthn(
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<Option<WebauthnConfig>, String> {
+    let value = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let Some(rp_id) = value("WEBAUTHN_RP_ID").map(|id| id.to_ascii_lowercase()) else {
+        return Ok(None);
+    };
+    let mut failures = Vec::new();
+    if rp_id.contains(['/', ':', ' ']) {
+        failures.push(format!(
+            "invalid WEBAUTHN_RP_ID \"{rp_id}\": expected a host name such as tasks.example.com"
+        ));
+    }
+    let origins: Vec<String> = match value("WEBAUTHN_ORIGINS") {
+        Some(raw) => raw
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect(),
+        None => vec![format!("https://{rp_id}")],
+    };
+    for origin in &origins {
+        let path = origin.split_once("://").map(|(_, rest)| rest.contains('/'));
+        if path != Some(false) {
+            failures.push(format!(
+                "invalid WEBAUTHN_ORIGINS entry \"{origin}\": expected scheme://host[:port]"
+            ));
+        }
+    }
+    if !failures.is_empty() {
+        return Err(failures.join("\n"));
+    }
+    Ok(Some(WebauthnConfig {
+        rp_id,
+        rp_name: value("WEBAUTHN_RP_NAME").unwrap_or_else(|| "TaskSync".to_string()),
+        origins,
+    }))
+}
+
+fn b64url_decode(raw: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(raw.trim().trim_end_matches('=')).ok()
+}
+
+fn new_challenge() -> String {
+    let mut bytes = Vec::with_capacity(32);
+    bytes.extend_from_slice(Uuid::new_v4().as_bytes());
+    bytes.extend_from_slice(Uuid::new_v4().as_bytes());
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+async fn issue_challenge(
+    pool: &SqlitePool,
+    purpose: &str,
+    user_id: Option<&str>,
+) -> Result<String, StatusCode> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let challenge = new_challenge();
+    let mut tx = begin_write(pool).await?;
+    sqlx::query("delete from webauthn_challenge where expires_ts <= ?1")
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query(
+        "insert into webauthn_challenge (challenge, purpose, user_id, expires_ts) values (?1, ?2, ?3, ?4)",
+    )
+    .bind(&challenge)
+    .bind(purpose)
+    .bind(user_id)
+    .bind(now + CHALLENGE_TTL_MS)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(challenge)
+}
+
+/// Spends a live challenge; `false` if there is none for this purpose and
+/// user.
+async fn take_challenge(
+    pool: &SqlitePool,
+    challenge: &str,
+    purpose: &str,
+    user_id: Option<&str>,
+) -> Result<bool, StatusCode> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let deleted = sqlx::query(
+        "delete from webauthn_challenge where challenge = ?1 and purpose = ?2 and user_id is ?3 and expires_ts > ?4",
+    )
+    .bind(challenge)
+    .bind(purpose)
+    .bind(user_id)
+    .bind(now)
+    .execute(pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(deleted.rows_affected() == 1)
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+/// Parses `clientDataJSON` and checks its type and origin.
+fn client_data(
+    config: &WebauthnConfig,
+    client_data_json: &[u8],
+    expected_type: &str,
+) -> Option<ClientData> {
+    let data: ClientData = serde_json::from_slice(client_data_json).ok()?;
+    let origin_ok = config.origins.contains(&data.origin);
+    (data.kind == expected_type && origin_ok).then_some(data)
+}
+
+struct AuthenticatorData {
+    flags: u8,
+    sign_count: u32,
+    /// Credential ID and COSE public key, present on registration.
+    attested: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Parses `authenticatorData` and checks that it is scoped to our RP ID
+/// and carries the user-presence flag.
+fn authenticator_data(config: &WebauthnConfig, bytes: &[u8]) -> Option<AuthenticatorData> {
+    if bytes.len() < 37 || bytes[..32] != Sha256::digest(config.rp_id.as_bytes())[..] {
+        return None;
+    }
+    let flags = bytes[32];
+    if flags & FLAG_USER_PRESENT == 0 {
+        return None;
+    }
+    let sign_count = u32::from_be_bytes(bytes[33..37].try_into().ok()?);
+    let attested = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+        // AAGUID (16 bytes), credential ID length (2), credential ID, then
+        // the CBOR-encoded public key; extensions may follow.
+        let id_len = usize::from(u16::from_be_bytes(bytes.get(53..55)?.try_into().ok()?));
+        let credential_id = bytes.get(55..55 + id_len)?.to_vec();
+        let key_start = &bytes[55 + id_len..];
+        let mut rest = key_start;
+        ciborium::de::from_reader::<Value, _>(&mut rest).ok()?;
+        let public_key = key_start[..key_start.len() - rest.len()].to_vec();
+        Some((credential_id, public_key))
+    } else {
+        None
+    };
+    Some(AuthenticatorData { flags, sign_count, attested })
+}
+
+fn cose_field(map: &[(Value, Value)], label: i64) -> Option<&Value> {
+    map.iter()
+        .find(|(key, _)| key.as_integer().is_some_and(|key| i128::from(key) == i128::from(label)))
+        .map(|(_, value)| value)
+}
+
+fn cose_int(map: &[(Value, Value)], label: i64) -> Option<i64> {
+    cose_field(map, label)?.as_integer().and_then(|value| i64::try_from(value).ok())
+}
+
+fn cose_bytes(map: &[(Value, Value)], label: i64) -> Option<&[u8]> {
+    cose_field(map, label)?.as_bytes().map(Vec::as_slice)
+}
+
+/// A COSE public key of one of the algorithms offered at registration.
+enum CoseKey {
+    Es256 { point: Vec<u8> },
+    Ed25519 { x: Vec<u8> },
+    Rs256 { n: Vec<u8>, e: Vec<u8> },
+}
+
+impl CoseKey {
+    fn parse(bytes: &[u8]) -> Option<(CoseKey, i64)> {
+        let value: Value = ciborium::de::from_reader(bytes).ok()?;
+        let map = value.as_map()?;
+        let alg = cose_int(map, 3)?;
+        let key = match (cose_int(map, 1)?, alg) {
+            // EC2 on P-256: uncompressed point 0x04 || x || y.
+            (2, COSE_ALG_ES256) if cose_int(map, -1)? == 1 => {
+                let (x, y) = (cose_bytes(map, -2)?, cose_bytes(map, -3)?);
+                if x.len() != 32 || y.len() != 32 {
+                    return None;
+                }
+                CoseKey::Es256 { point: [&[0x04], x, y].concat() }
+            }
+            // OKP on Ed25519.
+            (1, COSE_ALG_EDDSA) if cose_int(map, -1)? == 6 => {
+                CoseKey::Ed25519 { x: cose_bytes(map, -2)?.to_vec() }
+            }
+            (3, COSE_ALG_RS256) => CoseKey::Rs256 {
+                n: cose_bytes(map, -1)?.to_vec(),
+                e: cose_bytes(map, -2)?.to_vec(),
+            },
+            _ => return None,
+        };
+        Some((key, alg))
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            CoseKey::Es256 { point } => {
+                UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, point)
+                    .verify(message, signature)
+                    .is_ok()
+            }
+            CoseKey::Ed25519 { x } => {
+                UnparsedPublicKey::new(&signature::ED25519, x).verify(message, signature).is_ok()
+            }
+            CoseKey::Rs256 { n, e } => RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, signature)
+                .is_ok(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub(super) kind: &'static str,
+    pub(super) id: String,
+}
+
+#[derive(Serialize)]
+pub(super) struct RelyingParty {
+    pub(super) id: String,
+    pub(super) name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PasskeyUserEntity {
+    /// base64url of the user id; returned as `userHandle` by passkeys.
+    pub(super) id: String,
+    pub(super) name: String,
+    pub(super) display_name: String,
+}
+
+#[derive(Serialize)]
+pub(super) struct CredentialParameter {
+    #[serde(rename = "type")]
+    pub(super) kind: &'static str,
+    pub(super) alg: i64,
+}
+
+/// `PublicKeyCredentialCreationOptionsJSON`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct CreationOptions {
+    pub(super) challenge: String,
+    pub(super) rp: RelyingParty,
+    pub(super) user: PasskeyUserEntity,
+    pub(super) pub_key_cred_params: Vec<CredentialParameter>,
+    pub(super) timeout: i64,
+    pub(super) attestation: &'static str,
+    pub(super) exclude_credentials: Vec<CredentialDescriptor>,
+    pub(super) authenticator_selection: serde_json::Value,
+}
+
+/// `PublicKeyCredentialRequestOptionsJSON`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct RequestOptions {
+    pub(super) challenge: String,
+    pub(super) rp_id: String,
+    pub(super) timeout: i64,
+    pub(super) user_verification: &'static str,
+    /// Always empty: the authenticator offers its discoverable passkeys.
+    pub(super) allow_credentials: Vec<CredentialDescriptor>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub(super) client_data_json: String,
+    pub(super) attestation_object: String,
+}
+
+/// `RegistrationResponseJSON`; only the fields the server uses.
+#[derive(Deserialize)]
+pub(super) struct RegistrationCredential {
+    pub(super) id: String,
+    pub(super) response: AttestationResponse,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RegisterFinishBody {
+    /// Shown in the passkey list, e.g. "iPhone".
+    #[serde(default)]
+    pub(super) label: Option<String>,
+    pub(super) credential: RegistrationCredential,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub(super) client_data_json: String,
+    pub(super) authenticator_data: String,
+    pub(super) signature: String,
+    #[serde(default)]
+    pub(super) user_handle: Option<String>,
+}
+
+/// `AuthenticationResponseJSON`, sent to `POST /auth/login` as `passkey`.
+#[derive(Deserialize)]
+pub(super) struct PasskeyAssertion {
+    pub(super) id: String,
+    pub(super) response: AssertionResponse,
+}
+
+#[derive(Serialize, FromRow)]
+pub(super) struct PasskeyResponse {
+    pub(super) id: String,
+    pub(super) label: Option<String>,
+    pub(super) created_ts: i64,
+    pub(super) last_used_ts: Option<i64>,
+}
+
+fn webauthn_config(state: &AppState) -> Result<&WebauthnConfig, StatusCode> {
+    state.webauthn.as_ref().ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn credential_descriptors(
+    pool: &SqlitePool,
+    user_id: &str,
+) -> Result<Vec<CredentialDescriptor>, StatusCode> {
+    let ids: Vec<String> =
+        sqlx::query_scalar("select id from user_credential where user_id = ?1 order by created_ts")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(ids.into_iter().map(|id| CredentialDescriptor { kind: "public-key", id }).collect())
+}
+
+/// Creation options for a new passkey of the caller.
+pub(super) async fn auth_webauthn_register_start(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<CreationOptions>, StatusCode> {
+    let config = webauthn_config(&state)?;
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let (email, display): (String, String) =
+        sqlx::query_as("select email, display from user where id = ?1")
+            .bind(&ctx.user_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let challenge = issue_challenge(&state.pool, "register", Some(&ctx.user_id)).await?;
+    Ok(Json(CreationOptions {
+        challenge,
+        rp: RelyingParty { id: config.rp_id.clone(), name: config.rp_name.clone() },
+        user: PasskeyUserEntity {
+            id: URL_SAFE_NO_PAD.encode(ctx.user_id.as_bytes()),
+            name: email,
+            display_name: display,
+        },
+        pub_key_cred_params: [COSE_ALG_ES256, COSE_ALG_EDDSA, COSE_ALG_RS256]
+            .into_iter()
+            .map(|alg| CredentialParameter { kind: "public-key", alg })
+            .collect(),
+        timeout: CHALLENGE_TTL_MS,
+        attestation: "none",
+        exclude_credentials: credential_descriptors(&state.pool, &ctx.user_id).await?,
+        authenticator_selection: json!({
+            "residentKey": "required",
+            "requireResidentKey": true,
+            "userVerification": "preferred",
+        }),
+    }))
+}
+
+/// `400` unless the credential answers a live registration challenge of
+/// the caller from an allowed origin with a supported key; `409` if it is
+/// already registered.
+pub(super) async fn auth_webauthn_register_finish(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<RegisterFinishBody>,
+) -> Result<(StatusCode, Json<PasskeyResponse>), StatusCode> {
+    let config = webauthn_config(&state)?;
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let response = &body.credential.response;
+    let client_data_json =
+        b64url_decode(&response.client_data_json).ok_or(StatusCode::BAD_REQUEST)?;
+    let client_data =
+        client_data(config, &client_data_json, "webauthn.create").ok_or(StatusCode::BAD_REQUEST)?;
+    if !take_challenge(&state.pool, &client_data.challenge, "register", Some(&ctx.user_id)).await? {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let attestation_object =
+        b64url_decode(&response.attestation_object).ok_or(StatusCode::BAD_REQUEST)?;
+    let attestation: Value = ciborium::de::from_reader(attestation_object.as_slice())
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let auth_data = attestation
+        .as_map()
+        .and_then(|map| {
+            map.iter().find(|(key, _)| key.as_text() == Some("authData")).map(|(_, value)| value)
+        })
+        .and_then(Value::as_bytes)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let auth_data = authenticator_data(config, auth_data).ok_or(StatusCode::BAD_REQUEST)?;
+    let (credential_id, public_key) = auth_data.attested.ok_or(StatusCode::BAD_REQUEST)?;
+    let (_, algorithm) = CoseKey::parse(&public_key).ok_or(StatusCode::BAD_REQUEST)?;
+    let credential_id = URL_SAFE_NO_PAD.encode(credential_id);
+    if b64url_decode(&body.credential.id).map(|id| URL_SAFE_NO_PAD.encode(id))
+        != Some(credential_id.clone())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let label = body
+        .label
+        .map(|label| label.trim().chars().take(MAX_CREDENTIAL_LABEL_CHARS).collect::<String>())
+        .filter(|label| !label.is_empty());
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut tx = begin_write(&state.pool).await?;
+    let inserted = sqlx::query(
+        "insert into user_credential (id, user_id, public_key, algorithm, sign_count, label, created_ts) values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )
+    .bind(&credential_id)
+    .bind(&ctx.user_id)
+    .bind(URL_SAFE_NO_PAD.encode(&public_key))
+    .bind(algorithm)
+    .bind(i64::from(auth_data.sign_count))
+    .bind(&label)
+    .bind(now)
+    .execute(&mut *tx)
+    .await;
+    match inserted {
+        Ok(_) => {}
+        Err(err) if is_unique_violation(&err) => return Err(StatusCode::CONFLICT),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::PasskeyAdded,
+            target_user_id: Some(&ctx.user_id),
+            detail: json!({ "credential_id": credential_id, "label": label }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(PasskeyResponse { id: credential_id, label, created_ts: now, last_used_ts: None }),
+    ))
+}
+
+/// The caller's passkeys, oldest first.
+pub(super) async fn auth_webauthn_credentials(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<PasskeyResponse>>, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let passkeys = sqlx::query_as::<_, PasskeyResponse>(
+        "select id, label, created_ts, last_used_ts from user_credential where user_id = ?1 order by created_ts",
+    )
+    .bind(&ctx.user_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(passkeys))
+}
+
+/// `404` for an unknown id or another user's passkey.
+pub(super) async fn auth_webauthn_delete_credential(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(credential_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let ctx = ctx_from_headers(&headers, &state).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    let deleted = sqlx::query("delete from user_credential where id = ?1 and user_id = ?2")
+        .bind(&credential_id)
+        .bind(&ctx.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if deleted.rows_affected() == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::PasskeyRemoved,
+            target_user_id: Some(&ctx.user_id),
+            detail: json!({ "credential_id": credential_id }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Unauthenticated; request options for a passkey sign-in. The same for
+/// everyone: a request body (older clients sent an `email`) is ignored.
+pub(super) async fn auth_webauthn_login_start(
+    State(state): State<AppState>,
+) -> Result<Json<RequestOptions>, StatusCode> {
+    let config = webauthn_config(&state)?;
+    let challenge = issue_challenge(&state.pool, "login", None).await?;
+    Ok(Json(RequestOptions {
+        challenge,
+        rp_id: config.rp_id.clone(),
+        timeout: CHALLENGE_TTL_MS,
+        user_verification: "preferred",
+        allow_credentials: Vec::new(),
+    }))
+}
+
+/// A verified passkey assertion.
+pub(super) struct PasskeyLogin {
+    pub(super) user_id: String,
+    /// The authenticator verified the user (PIN or biometric).
+    pub(super) user_verified: bool,
+}
+
+#[derive(FromRow)]
+struct StoredCredential {
+    user_id: String,
+    public_key: String,
+    sign_count: i64,
+}
+
+/// Checks a sign-in assertion for `login`: a live login challenge, an
+/// allowed origin, our RP ID, a registered credential whose key signed it
+/// and a signature counter that moved forward. `None` when any of that
+/// fails (or passkeys are off); the challenge is spent either way.
+pub(super) async fn verify_passkey_assertion(
+    state: &AppState,
+    assertion: &PasskeyAssertion,
+) -> Result<Option<PasskeyLogin>, StatusCode> {
+    let Some(config) = state.webauthn.as_ref() else {
+        return Ok(None);
+    };
+    let response = &assertion.response;
+    let decoded = (
+        b64url_decode(&response.client_data_json),
+        b64url_decode(&response.authenticator_data),
+        b64url_decode(&response.signature),
+    );
+    let (Some(client_data_json), Some(auth_data_bytes), Some(signature)) = decoded else {
+        return Ok(None);
+    };
+    let Some(client_data) = client_data(config, &client_data_json, "webauthn.get") else {
+        return Ok(None);
+    };
+    if !take_challenge(&state.pool, &client_data.challenge, "login", None).await? {
+        return Ok(None);
+    }
+    let Some(auth_data) = authenticator_data(config, &auth_data_bytes) else {
+        return Ok(None);
+    };
+    let credential = sqlx::query_as::<_, StoredCredential>(
+        "select user_id, public_key, sign_count from user_credential where id = ?1",
+    )
+    .bind(assertion.id.trim())
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(credential) = credential else {
+        return Ok(None);
+    };
+    if let Some(handle) = response.user_handle.as_deref().filter(|handle| !handle.is_empty()) {
+        if b64url_decode(handle).as_deref() != Some(credential.user_id.as_bytes()) {
+            return Ok(None);
+        }
+    }
+    let Some((key, _)) = b64url_decode(&credential.public_key).and_then(|key| CoseKey::parse(&key))
+    else {
+        return Ok(None);
+    };
+    let signed = [auth_data_bytes.as_slice(), &Sha256::digest(&client_data_json)].concat();
+    if !key.verify(&signed, &signature) {
+        return Ok(None);
+    }
+    // Authenticators without a counter always report 0.
+    let sign_count = i64::from(auth_data.sign_count);
+    if (sign_count != 0 || credential.sign_count != 0) && sign_count <= credential.sign_count {
+        tracing::warn!(
+            user_id = %credential.user_id,
+            "passkey signature counter did not advance; possible cloned authenticator"
+        );
+        return Ok(None);
+    }
+    sqlx::query("update user_credential set sign_count = ?1, last_used_ts = ?2 where id = ?3")
+        .bind(sign_count)
+        .bind(chrono::Utc::now().timestamp_millis())
+        .bind(assertion.id.trim())
+        .execute(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Some(PasskeyLogin {
+        user_id: credential.user_id,
+        user_verified: auth_data.flags & FLAG_USER_VERIFIED != 0,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(vars: &[(&str, &str)]) -> Result<Option<WebauthnConfig>, String> {
+        let vars: HashMap<String, String> =
+            vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        parse_webauthn(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn passkeys_are_off_without_an_rp_id() {
+        assert_eq!(parse(&[]), Ok(None));
+        assert_eq!(parse(&[("WEBAUTHN_ORIGINS", "https://a.example.com")]), Ok(None));
+    }
+
+    #[test]
+    fn webauthn_config_defaults_the_origin_to_the_rp_id() {
+        assert_eq!(
+            parse(&[("WEBAUTHN_RP_ID", "Tasks.Example.com")]),
+            Ok(Some(WebauthnConfig {
+                rp_id: "tasks.example.com".to_string(),
+                rp_name: "TaskSync".to_string(),
+                origins: vec!["https://tasks.example.com".to_string()],
+            }))
+        );
+        let config = parse(&[
+            ("WEBAUTHN_RP_ID", "localhost"),
+            ("WEBAUTHN_ORIGINS", "http://localhost:5173/, capacitor://localhost"),
+        ])
+        .expect("valid")
+        .expect("enabled");
+        assert_eq!(config.origins, ["http://localhost:5173", "capacitor://localhost"]);
+    }
+
+    #[test]
+    fn webauthn_config_rejects_urls_where_hosts_belong() {
+        let message = parse(&[
+            ("WEBAUTHN_RP_ID", "https://tasks.example.com"),
+            ("WEBAUTHN_ORIGINS", "tasks.example.com,https://tasks.example.com/app"),
+        ])
+        .unwrap_err();
+        assert!(message.contains("invalid WEBAUTHN_RP_ID"));
+        assert!(message.contains("entry \"tasks.example.com\""));
+        assert!(message.contains("entry \"https://tasks.example.com/app\""));
+    }
+
+    #[test]
+    fn cose_keys_of_unsupported_types_are_refused() {
+        let encode = |entries: Vec<(i64, Value)>| {
+            let map = Value::Map(
+                entries.into_iter().map(|(k, v)| (Value::Integer(k.into()), v)).collect(),
+            );
+            let mut bytes = Vec::new();
+            ciborium::ser::into_writer(&map, &mut bytes).expect("cbor");
+            bytes
+        };
+        let p256 = |alg: i64, x_len: usize| {
+            encode(vec![
+                (1, Value::Integer(2.into())),
+                (3, Value::Integer(alg.into())),
+                (-1, Value::Integer(1.into())),
+                (-2, Value::Bytes(vec![1; x_len])),
+                (-3, Value::Bytes(vec![2; 32])),
+            ])
+        };
+        assert!(matches!(
+            CoseKey::parse(&p256(COSE_ALG_ES256, 32)),
+            Some((CoseKey::Es256 { .. }, -7))
+        ));
+        assert!(CoseKey::parse(&p256(COSE_ALG_ES256, 31)).is_none());
+        // ES384 is not offered at registration.
+        assert!(CoseKey::parse(&p256(-35, 32)).is_none());
+        assert!(CoseKey::parse(b"not cbor").is_none());
+    }
+}
@@ -0,0 +1,768 @@
+//! Outbound webhooks on task events.
+//!
+//! Admins subscribe a URL at `POST /auth/webhooks` to some of
+//! `task.created`, `task.completed` and `task.deleted`, optionally for one
+//! list only. Every task write path records its event through
+//! `record_task_event`, which calls `enqueue_task_webhooks` in the same
+//! transaction, so each matching subscription gets a durable
+//! `webhook_delivery` row. `spawn_webhook_delivery` drains the queue: it
+//! POSTs the JSON payload with an HMAC-SHA256 signature of
+//! `"{timestamp}.{body}"` keyed by the subscription's secret, retries
+//! failures with exponential backoff and keeps the outcome for
+//! `GET /auth/webhooks/:webhook_id/deliveries`. Subscriptions are served
+//! concurrently, so a slow receiver only holds up its own queue; within one
+//! subscription deliveries go out in event order, a retrying delivery
+//! holding back the ones after it until it succeeds or is given up on.
+//!
+//! Webhook URLs are chosen by admins but fetched by the server, so loopback,
+//! private, link-local and other non-public addresses are refused: IP
+//! literals and `localhost` names when the webhook is created, and every
+//! address a host name resolves to right before each delivery (the
+//! connection then uses exactly the checked addresses).
+//! `WEBHOOK_ALLOW_PRIVATE_TARGETS=true` lifts this for self-hosted setups
+//! whose receivers live on the local network.
+
+use std::error::Error;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use data_encoding::HEXLOWER;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use hmac::{Hmac, KeyInit, Mac};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+use uuid::Uuid;
+
+use super::audit::{record_audit, AuditAction, AuditEntry};
+use super::opaque_token::new_opaque_token;
+use super::tasks::TaskRow;
+use super::types::{begin_write, ctx_from_headers, AdminError, AppState, RequestCtx};
+
+pub(super) const WEBHOOK_EVENTS: [&str; 3] = ["task.created", "task.completed", "task.deleted"];
+/// Sent as `X-TaskSync-Signature: sha256=<hex>`.
+pub(super) const SIGNATURE_HEADER: &str = "x-tasksync-signature";
+/// Milliseconds since the epoch; part of the signed message.
+pub(super) const TIMESTAMP_HEADER: &str = "x-tasksync-timestamp";
+const EVENT_HEADER: &str = "x-tasksync-event";
+const DELIVERY_HEADER: &str = "x-tasksync-delivery";
+const MIN_SECRET_CHARS: usize = 16;
+const MAX_SECRET_CHARS: usize = 256;
+/// A delivery is given up (`failed`) after this many attempts.
+const MAX_DELIVERY_ATTEMPTS: i64 = 8;
+/// The first retry waits this long; each later one twice as long.
+const RETRY_BASE_MS: i64 = 30 * 1000;
+const RETRY_MAX_MS: i64 = 6 * 60 * 60 * 1000;
+/// Delivered and failed rows are purged after this long.
+const DELIVERY_RETENTION_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(10);
+const DELIVERY_BATCH_SIZE: i64 = 100;
+/// Subscriptions served at the same time in one pass.
+const MAX_CONCURRENT_WEBHOOKS: usize = 8;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_DELIVERY_PAGE_SIZE: i64 = 50;
+const MAX_DELIVERY_PAGE_SIZE: i64 = 200;
+
+#[derive(Deserialize)]
+pub(super) struct CreateWebhookBody {
+    /// `http` or `https`.
+    pub(super) url: String,
+    /// Defaults to every event.
+    #[serde(default)]
+    pub(super) events: Option<Vec<String>>,
+    /// Only events for tasks in this list.
+    #[serde(default)]
+    pub(super) list_id: Option<String>,
+    /// Signing secret, 16 to 256 characters; generated when omitted.
+    #[serde(default)]
+    pub(super) secret: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(super) struct WebhookResponse {
+    pub(super) id: String,
+    pub(super) url: String,
+    pub(super) events: Vec<String>,
+    pub(super) list_id: Option<String>,
+    pub(super) created_by: Option<String>,
+    pub(super) created_ts: i64,
+}
+
+#[derive(Serialize)]
+pub(super) struct CreatedWebhookResponse {
+    #[serde(flatten)]
+    pub(super) webhook: WebhookResponse,
+    /// Shown once; verify `X-TaskSync-Signature` with it.
+    pub(super) secret: String,
+}
+
+#[derive(FromRow)]
+struct WebhookRecord {
+    id: String,
+    url: String,
+    events: String,
+    list_id: Option<String>,
+    created_by: Option<String>,
+    created_ts: i64,
+}
+
+impl From<WebhookRecord> for WebhookResponse {
+    fn from(record: WebhookRecord) -> Self {
+        WebhookResponse {
+            id: record.id,
+            url: record.url,
+            events: record.events.split_whitespace().map(str::to_string).collect(),
+            list_id: record.list_id,
+            created_by: record.created_by,
+            created_ts: record.created_ts,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct DeliveryQuery {
+    /// Defaults to 50; clamped to 200.
+    pub(super) limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(super) struct WebhookDeliveryRow {
+    pub(super) id: i64,
+    pub(super) event: String,
+    /// `pending`, `delivered` or `failed`.
+    pub(super) status: String,
+    pub(super) attempts: i64,
+    pub(super) next_attempt_ts: i64,
+    pub(super) last_attempt_ts: Option<i64>,
+    pub(super) last_status_code: Option<i64>,
+    pub(super) last_error: Option<String>,
+    pub(super) created_ts: i64,
+    pub(super) payload: Value,
+}
+
+#[derive(FromRow)]
+struct WebhookDeliveryRecord {
+    id: i64,
+    event: String,
+    status: String,
+    attempts: i64,
+    next_attempt_ts: i64,
+    last_attempt_ts: Option<i64>,
+    last_status_code: Option<i64>,
+    last_error: Option<String>,
+    created_ts: i64,
+    payload: String,
+}
+
+/// Parses `WEBHOOK_ALLOW_PRIVATE_TARGETS` via `var`: unset/blank or `false`
+/// keeps webhooks to public addresses, `true` allows any; anything else is
+/// a boot failure.
+pub(super) fn parse_webhook_private_targets(
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<bool, String> {
+    match var("WEBHOOK_ALLOW_PRIVATE_TARGETS")
+        .map(|value| value.trim().to_ascii_lowercase())
+        .as_deref()
+    {
+        None | Some("") | Some("false") => Ok(false),
+        Some("true") => Ok(true),
+        Some(other) => Err(format!(
+            "invalid WEBHOOK_ALLOW_PRIVATE_TARGETS \"{other}\": expected true or false"
+        )),
+    }
+}
+
+/// Whether `ip` is reachable on the public internet, i.e. not loopback,
+/// private, link-local, shared (CGNAT), unspecified, broadcast, multicast,
+/// documentation or reserved. IPv4-mapped and NAT64 IPv6 addresses are
+/// judged by the IPv4 address they carry.
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ipv4(mapped);
+            }
+            let segments = ip.segments();
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [.., a, b, c, d] = ip.octets();
+                return is_public_ipv4(Ipv4Addr::new(a, b, c, d));
+            }
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local fc00::/7, link-local fe80::/10, the old
+                // site-local fec0::/10 and documentation 2001:db8::/32.
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || (segments[0] & 0xffc0) == 0xfec0
+                || segments[..2] == [0x2001, 0x0db8])
+        }
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network" 0.0.0.0/8, shared 100.64.0.0/10, protocol
+        // assignments 192.0.0.0/24, benchmarking 198.18.0.0/15 and the
+        // reserved 240.0.0.0/4.
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b & 0xfe) == 18)
+        || a >= 240)
+}
+
+/// Whether a URL host is refused without `WEBHOOK_ALLOW_PRIVATE_TARGETS`: a
+/// non-public IP literal or a `localhost` name. Other names are checked
+/// once resolved (see `PublicResolver`).
+fn is_private_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => !is_public_address(ip),
+        Err(_) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host == "localhost" || host.ends_with(".localhost")
+        }
+    }
+}
+
+/// `sha256=` plus the hex HMAC-SHA256 of `"{timestamp}.{body}"`. Binding the
+/// timestamp lets receivers refuse replays of old deliveries.
+pub(super) fn webhook_signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    format!("sha256={}", HEXLOWER.encode(&mac.finalize().into_bytes()))
+}
+
+/// Wait before the attempt after `attempts` failed ones.
+fn retry_delay_ms(attempts: i64) -> i64 {
+    let doublings = (attempts - 1).clamp(0, 20) as u32;
+    (RETRY_BASE_MS << doublings).min(RETRY_MAX_MS)
+}
+
+async fn require_admin(headers: &HeaderMap, state: &AppState) -> Result<RequestCtx, AdminError> {
+    let ctx = ctx_from_headers(headers, state).await?;
+    ctx.require_admin()?;
+    Ok(ctx)
+}
+
+/// Admin-only. `400` for a URL that is not `http(s)` or points at a
+/// private address (see the module doc), an empty or unknown event or a
+/// secret of the wrong length, `404` for a `list_id` outside the space.
+pub(super) async fn auth_create_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<CreateWebhookBody>,
+) -> Result<(StatusCode, Json<CreatedWebhookResponse>), AdminError> {
+    let ctx = require_admin(&headers, &state).await?;
+    let url = Url::parse(body.url.trim()).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let Some(host) = url.host_str().filter(|_| matches!(url.scheme(), "http" | "https")) else {
+        return Err(StatusCode::BAD_REQUEST.into());
+    };
+    if !state.allow_private_webhook_targets && is_private_host(host) {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    let requested: Vec<&str> = match &body.events {
+        Some(events) => events.iter().map(|event| event.trim()).collect(),
+        None => WEBHOOK_EVENTS.to_vec(),
+    };
+    if requested.is_empty() || !requested.iter().all(|event| WEBHOOK_EVENTS.contains(event)) {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    let events: Vec<String> = WEBHOOK_EVENTS
+        .iter()
+        .filter(|event| requested.contains(event))
+        .map(|event| event.to_string())
+        .collect();
+    let secret = body.secret.unwrap_or_else(|| new_opaque_token("whsec_"));
+    if !(MIN_SECRET_CHARS..=MAX_SECRET_CHARS).contains(&secret.chars().count()) {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    if let Some(list_id) = &body.list_id {
+        let exists: bool =
+            sqlx::query_scalar("select exists(select 1 from list where id = ?1 and space_id = ?2)")
+                .bind(list_id)
+                .bind(&ctx.space_id)
+                .fetch_one(&state.pool)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if !exists {
+            return Err(StatusCode::NOT_FOUND.into());
+        }
+    }
+
+    let webhook = WebhookResponse {
+        id: format!("wh-{}", Uuid::new_v4()),
+        url: url.to_string(),
+        events,
+        list_id: body.list_id,
+        created_by: Some(ctx.user_id.clone()),
+        created_ts: chrono::Utc::now().timestamp_millis(),
+    };
+    let mut tx = begin_write(&state.pool).await?;
+    sqlx::query(
+        "insert into webhook (id, space_id, url, events, list_id, secret, created_by, created_ts) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )
+    .bind(&webhook.id)
+    .bind(&ctx.space_id)
+    .bind(&webhook.url)
+    .bind(webhook.events.join(" "))
+    .bind(&webhook.list_id)
+    .bind(&secret)
+    .bind(&ctx.user_id)
+    .bind(webhook.created_ts)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::WebhookCreated,
+            target_user_id: None,
+            detail: json!({
+                "webhook_id": webhook.id,
+                "url": webhook.url,
+                "events": webhook.events,
+                "list_id": webhook.list_id,
+            }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((StatusCode::CREATED, Json(CreatedWebhookResponse { webhook, secret })))
+}
+
+/// Admin-only; the space's subscriptions, newest first, without secrets.
+pub(super) async fn auth_webhooks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<WebhookResponse>>, AdminError> {
+    let ctx = require_admin(&headers, &state).await?;
+    let webhooks = sqlx::query_as::<_, WebhookRecord>(
+        "select id, url, events, list_id, created_by, created_ts from webhook where space_id = ?1 order by created_ts desc, id",
+    )
+    .bind(&ctx.space_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(webhooks.into_iter().map(WebhookResponse::from).collect()))
+}
+
+/// Admin-only; also drops the subscription's pending deliveries and log.
+/// `404` for an unknown webhook or one of another space.
+pub(super) async fn auth_delete_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(webhook_id): Path<String>,
+) -> Result<StatusCode, AdminError> {
+    let ctx = require_admin(&headers, &state).await?;
+    let mut tx = begin_write(&state.pool).await?;
+    let deleted: Option<String> =
+        sqlx::query_scalar("delete from webhook where id = ?1 and space_id = ?2 returning url")
+            .bind(&webhook_id)
+            .bind(&ctx.space_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(url) = deleted else {
+        return Err(StatusCode::NOT_FOUND.into());
+    };
+    record_audit(
+        &mut tx,
+        AuditEntry {
+            space_id: &ctx.space_id,
+            actor_user_id: Some(&ctx.user_id),
+            action: AuditAction::WebhookDeleted,
+            target_user_id: None,
+            detail: json!({ "webhook_id": webhook_id, "url": url }),
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Admin-only delivery log of one subscription, newest first. `404` for an
+/// unknown webhook or one of another space.
+pub(super) async fn auth_webhook_deliveries(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(webhook_id): Path<String>,
+    Query(query): Query<DeliveryQuery>,
+) -> Result<Json<Vec<WebhookDeliveryRow>>, AdminError> {
+    let ctx = require_admin(&headers, &state).await?;
+    let limit = match query.limit {
+        Some(limit) if limit <= 0 => return Err(StatusCode::BAD_REQUEST.into()),
+        Some(limit) => limit.min(MAX_DELIVERY_PAGE_SIZE),
+        None => DEFAULT_DELIVERY_PAGE_SIZE,
+    };
+    let exists: bool =
+        sqlx::query_scalar("select exists(select 1 from webhook where id = ?1 and space_id = ?2)")
+            .bind(&webhook_id)
+            .bind(&ctx.space_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !exists {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let records = sqlx::query_as::<_, WebhookDeliveryRecord>(
+        "select id, event, status, attempts, next_attempt_ts, last_attempt_ts, last_status_code, last_error, created_ts, payload from webhook_delivery where webhook_id = ?1 order by id desc limit ?2",
+    )
+    .bind(&webhook_id)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let rows = records
+        .into_iter()
+        .map(|record| {
+            let payload = serde_json::from_str(&record.payload)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            Ok(WebhookDeliveryRow {
+                id: record.id,
+                event: record.event,
+                status: record.status,
+                attempts: record.attempts,
+                next_attempt_ts: record.next_attempt_ts,
+                last_attempt_ts: record.last_attempt_ts,
+                last_status_code: record.last_status_code,
+                last_error: record.last_error,
+                created_ts: record.created_ts,
+                payload,
+            })
+        })
+        .collect::<Result<Vec<_>, StatusCode>>()?;
+    Ok(Json(rows))
+}
+
+/// Queues `event` for every subscription of the space that wants it and
+/// whose list filter (if any) matches the task. Runs inside the task
+/// write's transaction.
+pub(super) async fn enqueue_task_webhooks(
+    conn: &mut SqliteConnection,
+    ctx: &RequestCtx,
+    event: &'static str,
+    task: &TaskRow,
+    ts: i64,
+) -> Result<(), StatusCode> {
+    let payload = json!({
+        "event": event,
+        "ts": ts,
+        "space_id": ctx.space_id,
+        "actor_user_id": ctx.user_id,
+        "task": task,
+    });
+    sqlx::query(
+        "insert into webhook_delivery (webhook_id, event, payload, status, next_attempt_ts, created_ts) select id, ?3, ?4, 'pending', ?5, ?5 from webhook where space_id = ?1 and (list_id is null or list_id = ?2) and instr(' ' || events || ' ', ' ' || ?3 || ' ') > 0",
+    )
+    .bind(&ctx.space_id)
+    .bind(&task.list_id)
+    .bind(event)
+    .bind(payload.to_string())
+    .bind(ts)
+    .execute(conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct DueDelivery {
+    id: i64,
+    webhook_id: String,
+    event: String,
+    payload: String,
+    attempts: i64,
+    url: String,
+    secret: String,
+}
+
+/// A refused target; recorded as the delivery's `last_error`.
+#[derive(Debug)]
+struct BlockedAddress;
+
+impl fmt::Display for BlockedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("blocked address")
+    }
+}
+
+impl Error for BlockedAddress {}
+
+/// Resolves webhook hosts, refusing a name if any of its addresses is not
+/// public. The connection only ever uses the addresses returned here, so a
+/// name cannot pass the check and then connect somewhere else.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if addrs.iter().any(|addr| !is_public_address(addr.ip())) {
+                return Err(Box::new(BlockedAddress) as Box<dyn Error + Send + Sync>);
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// The delivery worker's HTTP client: times out and follows no redirects.
+/// Unless private targets are allowed it also bypasses proxies, resolves
+/// through `PublicResolver` and checks IP literals before sending.
+pub(super) struct WebhookClient {
+    http: reqwest::Client,
+    allow_private_targets: bool,
+}
+
+impl WebhookClient {
+    pub(super) fn new(allow_private_targets: bool) -> Self {
+        let mut builder = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none());
+        if !allow_private_targets {
+            builder = builder.no_proxy().dns_resolver(Arc::new(PublicResolver));
+        }
+        WebhookClient {
+            http: builder.build().expect("HTTP client for webhook deliveries"),
+            allow_private_targets,
+        }
+    }
+}
+
+fn is_blocked(err: &reqwest::Error) -> bool {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if err.is::<BlockedAddress>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// What one attempt ended with: when it was sent, the response status, if
+/// any, and an error for anything but a 2xx. The signed timestamp is taken
+/// right before sending, however long the delivery waited for its turn.
+async fn attempt_delivery(
+    client: &WebhookClient,
+    delivery: &DueDelivery,
+) -> (i64, Option<i64>, Option<String>) {
+    let blocked = !client.allow_private_targets
+        && Url::parse(&delivery.url).is_ok_and(|url| url.host_str().is_some_and(is_private_host));
+    let sent_ts = chrono::Utc::now().timestamp_millis();
+    if blocked {
+        return (sent_ts, None, Some(BlockedAddress.to_string()));
+    }
+    let response = client
+        .http
+        .post(&delivery.url)
+        .header("content-type", "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, sent_ts.to_string())
+        .header(SIGNATURE_HEADER, webhook_signature(&delivery.secret, sent_ts, &delivery.payload))
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+    let (status_code, error) = match response {
+        Ok(response) if response.status().is_success() => {
+            (Some(i64::from(response.status().as_u16())), None)
+        }
+        Ok(response) => (
+            Some(i64::from(response.status().as_u16())),
+            Some(format!("HTTP {}", response.status().as_u16())),
+        ),
+        Err(err) if is_blocked(&err) => (None, Some(BlockedAddress.to_string())),
+        Err(err) if err.is_timeout() => (None, Some("timed out".to_string())),
+        Err(err) if err.is_connect() => (None, Some("connection failed".to_string())),
+        Err(_) => (None, Some("request failed".to_string())),
+    };
+    (sent_ts, status_code, error)
+}
+
+/// Attempts one subscription's due deliveries in order, stopping at the
+/// first failure so a dead receiver costs one timeout per pass; the rest
+/// wait for the next one. Returns how many were attempted.
+async fn deliver_queue(
+    pool: &SqlitePool,
+    client: &WebhookClient,
+    queue: Vec<DueDelivery>,
+    now: i64,
+) -> Result<usize, sqlx::Error> {
+    let mut attempted = 0;
+    for delivery in &queue {
+        let (sent_ts, status_code, error) = attempt_delivery(client, delivery).await;
+        attempted += 1;
+        let attempts = delivery.attempts + 1;
+        let status = match &error {
+            None => "delivered",
+            Some(_) if attempts >= MAX_DELIVERY_ATTEMPTS => "failed",
+            Some(_) => "pending",
+        };
+        if let Some(error) = &error {
+            tracing::warn!(delivery_id = delivery.id, attempts, status, "webhook delivery {error}");
+        }
+        sqlx::query(
+            "update webhook_delivery set status = ?1, attempts = ?2, next_attempt_ts = ?3, last_attempt_ts = ?4, last_status_code = ?5, last_error = ?6 where id = ?7",
+        )
+        .bind(status)
+        .bind(attempts)
+        .bind(now + retry_delay_ms(attempts))
+        .bind(sent_ts)
+        .bind(status_code)
+        .bind(&error)
+        .bind(delivery.id)
+        .execute(pool)
+        .await?;
+        if error.is_some() {
+            break;
+        }
+    }
+    Ok(attempted)
+}
+
+/// One pass of the delivery worker: attempts the pending deliveries due at
+/// `now` (up to a batch, oldest first), serving up to
+/// `MAX_CONCURRENT_WEBHOOKS` subscriptions at once, and purges finished ones
+/// past retention. A delivery waiting on a retry holds back the later ones
+/// of its subscription, so receivers see events in order. Retries are
+/// scheduled from `now`. Returns how many were attempted.
+pub(super) async fn deliver_due_webhooks(
+    pool: &SqlitePool,
+    client: &WebhookClient,
+    now: i64,
+) -> Result<usize, sqlx::Error> {
+    sqlx::query("delete from webhook_delivery where status != 'pending' and created_ts < ?1")
+        .bind(now - DELIVERY_RETENTION_MS)
+        .execute(pool)
+        .await?;
+    let due = sqlx::query_as::<_, DueDelivery>(
+        "select d.id, d.webhook_id, d.event, d.payload, d.attempts, w.url, w.secret from webhook_delivery d join webhook w on w.id = d.webhook_id where d.status = 'pending' and d.next_attempt_ts <= ?1 and not exists (select 1 from webhook_delivery e where e.webhook_id = d.webhook_id and e.status = 'pending' and e.id < d.id and e.next_attempt_ts > ?1) order by d.id asc limit ?2",
+    )
+    .bind(now)
+    .bind(DELIVERY_BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+    let mut queues: Vec<Vec<DueDelivery>> = Vec::new();
+    for delivery in due {
+        match queues.iter_mut().find(|queue| queue[0].webhook_id == delivery.webhook_id) {
+            Some(queue) => queue.push(delivery),
+            None => queues.push(vec![delivery]),
+        }
+    }
+    let attempted: Vec<usize> = stream::iter(queues)
+        .map(|queue| deliver_queue(pool, client, queue, now))
+        .buffer_unordered(MAX_CONCURRENT_WEBHOOKS)
+        .try_collect()
+        .await?;
+    Ok(attempted.into_iter().sum())
+}
+
+/// Spawns the delivery worker. Failures are logged and retried on the next
+/// tick.
+pub fn spawn_webhook_delivery(pool: &SqlitePool) {
+    let pool = pool.clone();
+    let client = WebhookClient::new(
+        parse_webhook_private_targets(|name| std::env::var(name).ok())
+            .expect("WEBHOOK_ALLOW_PRIVATE_TARGETS validated by validate_boot_secrets at boot"),
+    );
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(DELIVERY_INTERVAL);
+        loop {
+            tick.tick().await;
+            let now = chrono::Utc::now().timestamp_millis();
+            if let Err(err) = deliver_due_webhooks(&pool, &client, now).await {
+                tracing::warn!(error = %err, "webhook delivery pass failed");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_exponentially_up_to_the_cap() {
+        assert_eq!(retry_delay_ms(1), 30 * 1000);
+        assert_eq!(retry_delay_ms(2), 60 * 1000);
+        assert_eq!(retry_delay_ms(4), 4 * 60 * 1000);
+        assert_eq!(retry_delay_ms(40), RETRY_MAX_MS);
+    }
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let signature = webhook_signature("whsec_test", 1_700_000_000_000, "{}");
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_ne!(signature, webhook_signature("whsec_test", 1_700_000_000_001, "{}"));
+        assert_ne!(signature, webhook_signature("whsec_other", 1_700_000_000_000, "{}"));
+    }
+
+    #[test]
+    fn only_public_addresses_are_webhook_targets() {
+        for host in [
+            "example.com",
+            "93.184.215.14",
+            "[2606:2800:21f:cb07:6820:80da:af6b:8b2c]",
+            "[::ffff:93.184.215.14]",
+        ] {
+            assert!(!is_private_host(host), "{host}");
+        }
+        for host in [
+            "localhost",
+            "api.localhost.",
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "[::1]",
+            "[::]",
+            "[fd00::1]",
+            "[fe80::1]",
+            "[::ffff:127.0.0.1]",
+            "[64:ff9b::a9fe:a9fe]",
+        ] {
+            assert!(is_private_host(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn private_targets_are_opt_in() {
+        let parse = |value: &str| {
+            let value = value.to_string();
+            parse_webhook_private_targets(move |_| Some(value.clone()))
+        };
+        assert_eq!(parse_webhook_private_targets(|_| None), Ok(false));
+        assert_eq!(parse(" "), Ok(false));
+        assert_eq!(parse("TRUE"), Ok(true));
+        assert!(parse("yes").is_err());
+    }
+}
@@ -2,6 +2,11 @@ export interface AuthLoginRequest {
 	email: string;
 	password: string;
 	space_id?: string;
+	device_label?: string;
+	/** TOTP or recovery code; required once the account has 2FA enabled. */
+	two_factor_code?: string;
+	/** Replaces `password`; `email` may then be empty. */
+	passkey?: PasskeyAssertion;
 }
 
 export interface AuthUser {
@@ -15,6 +20,7 @@ export interface AuthUser {
 
 export interface AuthLoginResponse extends AuthUser {
 	token: string;
+	refresh_token: string;
 }
 
 export interface SpaceMember extends AuthUser {}
@@ -31,6 +37,86 @@ export interface AuthChangePasswordRequest {
 
 export interface AuthStatusResponse {
 	owner_exists: boolean;
+	/** Single sign-on is configured; see `GET /auth/oidc/start`. */
+	oidc_enabled: boolean;
+	/** Passkeys are configured; see `POST /auth/webauthn/login/start`. */
+	webauthn_enabled: boolean;
+}
+
+export interface OidcStartResponse {
+	authorization_url: string;
+	/** Echoed by the provider's redirect back to the app. */
+	state: string;
+	/** Kept by this browser only and sent with the callback. */
+	binding: string;
+}
+
+/**
+ * `code` and `state` from the identity provider's redirect back to the app,
+ * with the `binding` kept since the start. Repeated with `two_factor_code`
+ * and no `code` after a `two_factor_required` answer.
+ */
+export interface OidcCallbackRequest {
+	code?: string;
+	state: string;
+	binding: string;
+	device_label?: string;
+	two_factor_code?: string;
+}
+
+/** `PublicKeyCredentialDescriptorJSON`; ids are base64url throughout. */
+export interface PasskeyDescriptor {
+	type: 'public-key';
+	id: string;
+}
+
+/** `PublicKeyCredentialCreationOptionsJSON` from `POST /auth/webauthn/register/start`. */
+export interface PasskeyCreationOptions {
+	challenge: string;
+	rp: { id: string; name: string };
+	user: { id: string; name: string; displayName: string };
+	pubKeyCredParams: { type: 'public-key'; alg: number }[];
+	timeout: number;
+	attestation: 'none';
+	excludeCredentials: PasskeyDescriptor[];
+	authenticatorSelection: Record<string, string | boolean>;
+}
+
+/** `PublicKeyCredentialRequestOptionsJSON` from `POST /auth/webauthn/login/start`. */
+export interface PasskeyRequestOptions {
+	challenge: string;
+	rpId: string;
+	timeout: number;
+	userVerification: 'preferred';
+	/** Always empty: passkeys are discoverable, so no ids are given out. */
+	allowCredentials: PasskeyDescriptor[];
+}
+
+export interface PasskeyRegistration {
+	id: string;
+	response: { clientDataJSON: string; attestationObject: string };
+}
+
+export interface PasskeyRegisterFinishRequest {
+	label?: string;
+	credential: PasskeyRegistration;
+}
+
+export interface PasskeyAssertion {
+	id: string;
+	response: {
+		clientDataJSON: string;
+		authenticatorData: string;
+		signature: string;
+		userHandle?: string;
+	};
+}
+
+export interface Passkey {
+	id: string;
+	label: string | null;
+	created_ts: number;
+	last_used_ts: number | null;
 }
 
 export interface AuthSetupRequest {
@@ -39,10 +125,159 @@ export interface AuthSetupRequest {
 	password: string;
 	avatar_icon?: string;
 	space_id?: string;
+	device_label?: string;
 }
 
 export interface AuthTokenResponse {
 	token: string;
+	refresh_token: string;
+}
+
+// Body of `POST /auth/refresh` and `POST /auth/logout`.
+export interface AuthRefreshRequest {
+	refresh_token: string;
+}
+
+export interface DeviceSession {
+	id: string;
+	device_label: string | null;
+	user_agent: string | null;
+	created_ts: number;
+	last_seen_ts: number;
+	expires_ts: number;
+	current: boolean;
+}
+
+/** What an API token may do under `/api`. */
+export type ApiScope =
+	| 'tasks:read'
+	| 'tasks:write'
+	| 'tasks:complete'
+	| 'lists:read'
+	| 'backup:export';
+
+export interface ApiToken {
+	id: string;
+	name: string;
+	/** Member the token acts as. */
+	user_id: string;
+	/** First characters of the token, e.g. `tsk_1a2b3c4d5e6f`. */
+	token_prefix: string;
+	scopes: ApiScope[];
+	created_by: string | null;
+	created_ts: number;
+	expires_ts: number;
+	last_used_ts: number | null;
+}
+
+export interface CreateApiTokenRequest {
+	name: string;
+	/** Defaults to the calling admin. */
+	user_id?: string;
+	/** Defaults to 365; 1 to 3650. */
+	expires_in_days?: number;
+	/** Defaults to `['tasks:write']`; must not be empty. */
+	scopes?: ApiScope[];
+}
+
+/** `token` is shown once; send it as `X-TaskSync-Api-Token`. */
+export interface CreatedApiToken extends ApiToken {
+	token: string;
+}
+
+export type WebhookEvent = 'task.created' | 'task.completed' | 'task.deleted';
+
+export interface Webhook {
+	id: string;
+	url: string;
+	events: WebhookEvent[];
+	/** Only tasks in this list, when set. */
+	list_id: string | null;
+	created_by: string | null;
+	created_ts: number;
+}
+
+export interface CreateWebhookRequest {
+	/** `http` or `https`. */
+	url: string;
+	/** Defaults to every event. */
+	events?: WebhookEvent[];
+	list_id?: string;
+	/** 16 to 256 characters; generated when omitted. */
+	secret?: string;
+}
+
+/**
+ * `secret` is shown once. Deliveries carry `X-TaskSync-Signature:
+ * sha256=<hex>`, the HMAC-SHA256 of `${X-TaskSync-Timestamp}.${body}`.
+ */
+export interface CreatedWebhook extends Webhook {
+	secret: string;
+}
+
+export interface WebhookDelivery {
+	id: number;
+	event: WebhookEvent;
+	status: 'pending' | 'delivered' | 'failed';
+	attempts: number;
+	next_attempt_ts: number;
+	last_attempt_ts: number | null;
+	last_status_code: number | null;
+	last_error: string | null;
+	created_ts: number;
+	payload: {
+		event: WebhookEvent;
+		ts: number;
+		space_id: string;
+		actor_user_id: string;
+		task: Record<string, unknown>;
+	};
+}
+
+export interface TwoFactorStatus {
+	enabled: boolean;
+	pending: boolean;
+	recovery_codes_remaining: number;
+	/**
+	 * While set, admin-only routes answer an unenrolled admin with a `403`
+	 * coded `two_factor_enrollment_required`.
+	 */
+	required_for_admins: boolean;
+}
+
+export interface TwoFactorPasswordRequest {
+	current_password: string;
+}
+
+export interface TwoFactorSetupResponse {
+	secret: string;
+	otpauth_uri: string;
+}
+
+export interface TwoFactorVerifyRequest {
+	code: string;
+}
+
+export interface TwoFactorRecoveryCodes {
+	recovery_codes: string[];
+}
+
+export interface TwoFactorPolicy {
+	require_for_admins: boolean;
+}
+
+/** Body of the unauthenticated `POST /auth/reset`. */
+export interface AuthResetPasswordRequest {
+	token: string;
+	new_password: string;
+}
+
+export interface ResetTokenResponse {
+	expires_ts: number;
+	/** The token was mailed to the member. */
+	delivered: boolean;
+	/** Only set when not delivered, for the admin to pass on. */
+	reset_token: string | null;
 }
 
 export interface AuthCreateMemberRequest {
@@ -67,3 +302,44 @@ export interface SetListGrantRequest {
 	list_id: string;
 	granted: boolean;
 }
+
+export type AuditAction =
+	| 'member_created'
+	| 'member_deleted'
+	| 'member_password_reset'
+	| 'grant_changed'
+	| 'sessions_revoked'
+	| 'backup_exported'
+	| 'backup_restored'
+	| 'login_failed'
+	| 'lockout_cleared'
+	| 'refresh_token_reused'
+	| 'session_revoked'
+	| 'two_factor_enabled'
+	| 'two_factor_disabled'
+	| 'two_factor_policy_changed'
+	| 'recovery_code_used'
+	| 'password_reset_issued'
+	| 'password_reset_completed'
+	| 'passkey_added'
+	| 'passkey_removed'
+	| 'api_token_created'
+	| 'api_token_revoked'
+	| 'webhook_created'
+	| 'webhook_deleted';
+
+export interface AuditLogEntry {
+	id: number;
+	ts: number;
+	actor_user_id: string | null;
+	action: AuditAction;
+	target_user_id: string | null;
+	detail: Record<string, unknown>;
+}
+
+export interface AuditLogQuery {
+	from_ts?: number;
+	to_ts?: number;
+	actor_user_id?: string;
+	limit?: number;
+}
@@ -7,6 +7,7 @@ export interface SyncList {
 	icon?: string;
 	color?: string;
 	order: string;
+	updated_ts: number;
 }
 
 export interface SyncTask {
@@ -32,8 +33,29 @@ export interface SyncTask {
 	created_by_user_id?: string;
 }
 
+export interface TrashedTask extends SyncTask {
+	deleted_ts: number;
+	deleted_by_user_id: string;
+}
+
+export interface TaskEvent {
+	id: number;
+	task_id: string;
+	kind: 'created' | 'updated' | 'deleted' | 'restored';
+	actor_user_id: string;
+	scope: 'session' | 'api_token';
+	changes: Record<string, { from: unknown; to: unknown }>;
+	ts: number;
+}
+
+export type SyncProtocol = 'delta-v1' | 'delta-v2';
+
 export interface SyncPullRequest {
-	since_ts?: number;
+	since_ts?: number; // delta-v1 cursor
+	protocol?: SyncProtocol; // absent = delta-v1
+	since_seq?: number; // delta-v2 cursor
+	limit?: number; // delta-v1 page size (max 1000); absent = single response
+	page_token?: string; // next_page_token from the previous page
 }
 
 export interface SyncDeletedTask {
@@ -41,12 +63,21 @@ export interface SyncDeletedTask {
 	deleted_ts: number;
 }
 
+export interface SyncDeletedList {
+	id: string;
+	deleted_ts: number;
+}
+
 export interface SyncPullResponse {
-	protocol: 'delta-v1';
+	protocol: SyncProtocol;
 	cursor_ts: number;
+	cursor_seq?: number; // delta-v2 only
 	lists: SyncList[];
 	tasks: SyncTask[];
 	deleted_tasks?: SyncDeletedTask[];
+	deleted_lists?: SyncDeletedList[];
+	next_page_token?: string; // delta-v1 paged pull: more rows below cursor_ts
+	full_resync_required?: boolean; // cursor predates pruned tombstones: this is a full snapshot, replace the cache
 }
 
 export interface SyncCreateTaskChange {
@@ -89,6 +120,7 @@ export interface SyncUpdateTaskChange {
 		completed_ts?: number;
 		assignee_user_id?: string;
 	};
+	base_updated_ts?: number; // opts into field-level merge; body is then a patch
 }
 
 export interface SyncUpdateTaskStatusChange {
@@ -98,21 +130,81 @@ export interface SyncUpdateTaskStatusChange {
 	status: TaskStatus;
 }
 
-export type SyncPushChange = SyncCreateTaskChange | SyncUpdateTaskChange | SyncUpdateTaskStatusChange;
+export interface SyncDeleteTaskChange {
+	kind: 'delete_task';
+	op_id: string;
+	task_id: string;
+}
+
+export interface SyncCreateListChange {
+	kind: 'create_list';
+	op_id: string;
+	body: {
+		id?: string;
+		name: string;
+		icon?: string;
+		color?: string;
+		order?: string;
+	};
+}
+
+export interface SyncUpdateListChange {
+	kind: 'update_list';
+	op_id: string;
+	list_id: string;
+	body: {
+		name?: string;
+		icon?: string;
+		color?: string;
+		order?: string;
+	};
+}
+
+export interface SyncDeleteListChange {
+	kind: 'delete_list';
+	op_id: string;
+	list_id: string;
+}
+
+export type SyncPushChange =
+	| SyncCreateTaskChange
+	| SyncUpdateTaskChange
+	| SyncUpdateTaskStatusChange
+	| SyncDeleteTaskChange
+	| SyncCreateListChange
+	| SyncUpdateListChange
+	| SyncDeleteListChange;
 
 export interface SyncPushRequest {
 	changes: SyncPushChange[];
+	atomic?: boolean; // all-or-nothing: any rejection rolls back the whole batch
 }
 
 export interface SyncPushRejected {
 	op_id: string;
 	status: number;
 	error: string;
+	conflicting_fields?: string[]; // 409 only: fields both sides changed since the base
+	current?: SyncTask; // 409 only: the server's row to reconcile against
 }
 
 export interface SyncPushResponse {
 	protocol: 'delta-v1';
 	cursor_ts: number;
 	applied: SyncTask[];
+	applied_lists?: SyncList[]; // list create/update results, in push order
 	rejected: SyncPushRejected[];
 }
+
+export interface SyncChangedEvent {
+	cursor_seq: number; // `event: changed` on GET /sync/events; pull when ahead of the last seen cursor_seq
+}
+
+export type SyncSocketClientMessage =
+	| ({ type: 'push' } & SyncPushRequest)
+	| { type: 'pull'; since_seq?: number }; // starts or rewinds the live delta feed
+
+export type SyncSocketServerMessage =
+	| ({ type: 'push_result' } & SyncPushResponse)
+	| ({ type: 'delta' } & SyncPullResponse)
+	| { type: 'error'; status: number; error: string };
@@ -1,11 +1,17 @@
 import { afterEach, beforeEach, describe, expect, it, vi } from 'vitest';
 
 const mocks = vi.hoisted(() => ({
-	buildHeaders: vi.fn()
+	buildHeaders: vi.fn(),
+	getRefreshToken: vi.fn(),
+	setAuthToken: vi.fn(),
+	setRefreshToken: vi.fn()
 }));
 
 vi.mock('./headers', () => ({
-	buildHeaders: mocks.buildHeaders
+	buildHeaders: mocks.buildHeaders,
+	getRefreshToken: mocks.getRefreshToken,
+	setAuthToken: mocks.setAuthToken,
+	setRefreshToken: mocks.setRefreshToken
 }));
 
 const jsonResponse = (status: number, body = '{}', statusText?: string) => ({
@@ -32,6 +38,7 @@ describe('api client', () => {
 		vi.resetModules();
 		vi.clearAllMocks();
 		mocks.buildHeaders.mockReturnValue({ 'x-test-auth': '1' });
+		mocks.getRefreshToken.mockReturnValue(null);
 		vi.stubGlobal('fetch', vi.fn());
 		window.__TASKSYNC_RUNTIME_CONFIG__ = { apiUrl: 'https://runtime.example' };
 	});
@@ -207,4 +214,99 @@ describe('api client', () => {
 			])
 		);
 	});
+
+	it('refreshes an expired session once and retries the request', async () => {
+		mocks.getRefreshToken.mockReturnValue('rt_old');
+		(fetch as unknown as ReturnType<typeof vi.fn>)
+			.mockResolvedValueOnce(jsonResponse(401, '', 'Unauthorized'))
+			.mockResolvedValueOnce(jsonResponse(200, '{"token":"access-new","refresh_token":"rt_new"}'))
+			.mockResolvedValueOnce(jsonResponse(200, '{"user_id":"u-admin"}'));
+		const { api } = await import('./client');
+
+		await expect(api.me()).resolves.toEqual({ user_id: 'u-admin' });
+
+		const calls = (fetch as unknown as ReturnType<typeof vi.fn>).mock.calls;
+		expect(calls.map(([url]) => String(url))).toEqual([
+			'https://runtime.example/auth/me',
+			'https://runtime.example/auth/refresh',
+			'https://runtime.example/auth/me'
+		]);
+		expect(JSON.parse(String(calls[1][1].body))).toEqual({ refresh_token: 'rt_old' });
+		expect(mocks.setAuthToken).toHaveBeenCalledWith('access-new');
+		expect(mocks.setRefreshToken).toHaveBeenCalledWith('rt_new');
+	});
+
+	it('adopts the pair another tab stored instead of spending a stale refresh token', async () => {
+		// Another tab rotated the token while this one was waiting its turn.
+		mocks.getRefreshToken.mockReturnValueOnce('rt_old').mockReturnValue('rt_from_other_tab');
+		(fetch as unknown as ReturnType<typeof vi.fn>)
+			.mockResolvedValueOnce(jsonResponse(401, '', 'Unauthorized'))
+			.mockResolvedValueOnce(jsonResponse(200, '{"user_id":"u-admin"}'));
+		const { api } = await import('./client');
+
+		await expect(api.me()).resolves.toEqual({ user_id: 'u-admin' });
+
+		const calls = (fetch as unknown as ReturnType<typeof vi.fn>).mock.calls;
+		expect(calls.map(([url]) => String(url))).toEqual([
+			'https://runtime.example/auth/me',
+			'https://runtime.example/auth/me'
+		]);
+		expect(mocks.setRefreshToken).not.toHaveBeenCalled();
+	});
+
+	it('runs the refresh under the cross-tab lock when available', async () => {
+		mocks.getRefreshToken.mockReturnValue('rt_old');
+		const request = vi.fn((_name: string, run: () => Promise<unknown>) => run());
+		vi.stubGlobal('navigator', { locks: { request } });
+		(fetch as unknown as ReturnType<typeof vi.fn>)
+			.mockResolvedValueOnce(jsonResponse(401, '', 'Unauthorized'))
+			.mockResolvedValueOnce(jsonResponse(200, '{"token":"access-new","refresh_token":"rt_new"}'))
+			.mockResolvedValueOnce(jsonResponse(200, '{"user_id":"u-admin"}'));
+		const { api } = await import('./client');
+
+		await expect(api.me()).resolves.toEqual({ user_id: 'u-admin' });
+
+		expect(request).toHaveBeenCalledTimes(1);
+		expect(request.mock.calls[0][0]).toBe('tasksync:refresh');
+		expect(mocks.setRefreshToken).toHaveBeenCalledWith('rt_new');
+	});
+
+	it('sends the refresh token to the server on logout', async () => {
+		(fetch as unknown as ReturnType<typeof vi.fn>).mockResolvedValueOnce(jsonResponse(204, ''));
+		const { api } = await import('./client');
+
+		await api.logout({ refresh_token: 'rt_current' });
+
+		const [url, options] = (fetch as unknown as ReturnType<typeof vi.fn>).mock.calls[0];
+		expect(String(url)).toBe('https://runtime.example/auth/logout');
+		expect(options.method).toBe('POST');
+		expect(JSON.parse(String(options.body))).toEqual({ refresh_token: 'rt_current' });
+	});
+
+	it('drops a rejected refresh token and surfaces the original 401', async () => {
+		mocks.getRefreshToken.mockReturnValue('rt_revoked');
+		(fetch as unknown as ReturnType<typeof vi.fn>)
+			.mockResolvedValueOnce(jsonResponse(401, '', 'Unauthorized'))
+			.mockResolvedValueOnce(jsonResponse(401, '', 'Unauthorized'));
+		const { api, apiErrorStatus } = await import('./client');
+
+		const err = await api.me().catch((caught: unknown) => caught);
+
+		expect(apiErrorStatus(err)).toBe(401);
+		expect(fetch).toHaveBeenCalledTimes(2);
+		expect(mocks.setRefreshToken).toHaveBeenCalledWith(null);
+	});
+
+	it('does not try to refresh when login itself is rejected', async () => {
+		mocks.getRefreshToken.mockReturnValue('rt_old');
+		(fetch as unknown as ReturnType<typeof vi.fn>).mockResolvedValueOnce(
+			jsonResponse(401, '', 'Unauthorized')
+		);
+		const { api } = await import('./client');
+
+		await expect(
+			api.login({ email: 'admin@example.com', password: 'wrong' })
+		).rejects.toThrow(/401/);
+		expect(fetch).toHaveBeenCalledTimes(1);
+	});
 });
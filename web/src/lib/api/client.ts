@@ -1,6 +1,11 @@
-import { buildHeaders } from './headers';
+import { buildHeaders, getRefreshToken, setAuthToken, setRefreshToken } from './headers';
 import type {
+	ApiToken,
+	AuditLogEntry,
+	AuditLogQuery,
 	AuthCreateMemberRequest,
+	AuthRefreshRequest,
+	AuthResetPasswordRequest,
 	AuthChangePasswordRequest,
 	AuthLoginRequest,
 	AuthLoginResponse,
@@ -10,9 +15,29 @@ import type {
 	AuthTokenResponse,
 	AuthUpdateProfileRequest,
 	AuthUser,
+	CreateApiTokenRequest,
+	CreatedApiToken,
+	CreatedWebhook,
+	CreateWebhookRequest,
+	DeviceSession,
 	ListGrant,
+	OidcCallbackRequest,
+	OidcStartResponse,
+	Passkey,
+	PasskeyCreationOptions,
+	PasskeyRegisterFinishRequest,
+	PasskeyRequestOptions,
+	ResetTokenResponse,
 	SetListGrantRequest,
-	SpaceMember
+	SpaceMember,
+	TwoFactorPasswordRequest,
+	TwoFactorPolicy,
+	TwoFactorRecoveryCodes,
+	TwoFactorSetupResponse,
+	TwoFactorStatus,
+	TwoFactorVerifyRequest,
+	Webhook,
+	WebhookDelivery
 } from '$shared/types/auth';
 import type {
 	SyncList,
@@ -20,7 +45,9 @@ import type {
 	SyncPullRequest,
 	SyncPullResponse,
 	SyncPushRequest,
-	SyncPushResponse
+	SyncPushResponse,
+	TaskEvent,
+	TrashedTask
 } from '$shared/types/sync';
 import type { SoundSettings, UiPreferencesWire } from '$shared/types/settings';
 import type { SpaceBackupBundle, SpaceBackupRestoreResponse } from '$shared/types/backup';
@@ -44,14 +71,17 @@ export class ApiError extends Error {
 	status: number;
 	statusText: string;
 	detail?: string;
+	// Machine-readable `error.code` from coded error bodies.
+	code?: string;
 
-	constructor(status: number, statusText: string, detail?: string) {
+	constructor(status: number, statusText: string, detail?: string, code?: string) {
 		const base = `API ${status} ${statusText}`;
 		super(detail ? `${base}: ${detail}` : base);
 		this.name = 'ApiError';
 		this.status = status;
 		this.statusText = statusText;
 		this.detail = detail;
+		this.code = code;
 	}
 }
 
@@ -73,6 +103,12 @@ const parseErrorDetail = (raw: string): string | undefined => {
 			if (typeof error === 'string' && error.trim()) {
 				return error.trim();
 			}
+			if (error && typeof error === 'object') {
+				const nested = (error as Record<string, unknown>).message;
+				if (typeof nested === 'string' && nested.trim()) {
+					return nested.trim();
+				}
+			}
 		}
 	} catch {
 		// Fall back to short plain-text payloads when server sends non-JSON errors.
@@ -80,6 +116,20 @@ const parseErrorDetail = (raw: string): string | undefined => {
 	return trimmed.length <= 160 ? trimmed : undefined;
 };
 
+// `{ "error": { "code": "..." } }` bodies (see the server's `ErrorBody`).
+const parseErrorCode = (raw: string): string | undefined => {
+	try {
+		const parsed = JSON.parse(raw) as { error?: { code?: unknown } } | null;
+		const code = parsed?.error?.code;
+		return typeof code === 'string' ? code : undefined;
+	} catch {
+		return undefined;
+	}
+};
+
+export const apiErrorCode = (err: unknown): string | null =>
+	err instanceof ApiError ? (err.code ?? null) : null;
+
 export const apiErrorStatus = (err: unknown): number | null => {
 	if (err instanceof ApiError) return err.status;
 	const message = err instanceof Error ? err.message : String(err);
@@ -91,19 +141,79 @@ export const apiErrorStatus = (err: unknown): number | null => {
 
 export type { SyncList as SyncList, SyncTask as SyncTask } from '$shared/types/sync';
 
-const fetchJson = async <T>(path: string, opts: RequestInit = {}): Promise<T> => {
-	const res = await fetch(`${getBaseUrl()}${path}`, {
-		...opts,
-		headers: {
-			'content-type': 'application/json',
-			...buildHeaders(),
-			...(opts.headers ?? {})
+// Paths whose 401 means "bad credentials", not "access token expired".
+const noRefreshPaths = new Set([
+	'/auth/login',
+	'/auth/setup',
+	'/auth/refresh',
+	'/auth/reset',
+	'/auth/oidc/callback'
+]);
+
+let refreshInFlight: Promise<boolean> | null = null;
+
+const refreshLockName = 'tasksync:refresh';
+
+// Runs `run` under a lock shared by every tab of this origin, or directly
+// where the Web Locks API is missing.
+const withRefreshLock = <T>(run: () => Promise<T>): Promise<T> =>
+	typeof navigator !== 'undefined' && navigator.locks
+		? navigator.locks.request(refreshLockName, run)
+		: run();
+
+// Trades the stored refresh token for a new token pair. The server treats a
+// second use of the same refresh token as theft and revokes the session, so
+// concurrent callers in this tab share one request and tabs take turns under
+// a cross-tab lock. A tab that finds a different refresh token stored once it
+// holds the lock adopts the pair another tab just stored instead of spending
+// the stale one.
+const refreshSession = (): Promise<boolean> => {
+	const staleToken = getRefreshToken();
+	refreshInFlight ??= withRefreshLock(async () => {
+		const refreshToken = getRefreshToken();
+		if (!refreshToken) return false;
+		if (refreshToken !== staleToken) return true;
+		try {
+			const res = await fetch(`${getBaseUrl()}/auth/refresh`, {
+				method: 'POST',
+				headers: { 'content-type': 'application/json' },
+				body: JSON.stringify({ refresh_token: refreshToken } satisfies AuthRefreshRequest)
+			});
+			if (!res.ok) {
+				if (res.status === 401) setRefreshToken(null);
+				return false;
+			}
+			const tokens = JSON.parse(await res.text()) as AuthTokenResponse;
+			setAuthToken(tokens.token);
+			setRefreshToken(tokens.refresh_token);
+			return true;
+		} catch {
+			return false;
 		}
+	}).finally(() => {
+		refreshInFlight = null;
 	});
+	return refreshInFlight;
+};
+
+const fetchJson = async <T>(path: string, opts: RequestInit = {}): Promise<T> => {
+	const send = () =>
+		fetch(`${getBaseUrl()}${path}`, {
+			...opts,
+			headers: {
+				'content-type': 'application/json',
+				...buildHeaders(),
+				...(opts.headers ?? {})
+			}
+		});
+	let res = await send();
+	if (res.status === 401 && !noRefreshPaths.has(path) && (await refreshSession())) {
+		res = await send();
+	}
 	const raw = await res.text();
 	if (!res.ok) {
 		const detail = parseErrorDetail(raw);
-		throw new ApiError(res.status, res.statusText, detail);
+		throw new ApiError(res.status, res.statusText, detail, parseErrorCode(raw));
 	}
 	if (res.status === 204) {
 		return undefined as T;
@@ -120,7 +230,46 @@ export const api = {
 	authStatus: () => fetchJson<AuthStatusResponse>('/auth/status'),
 	setupOwner: (body: AuthSetupRequest) =>
 		fetchJson<AuthLoginResponse>('/auth/setup', { method: 'POST', body: JSON.stringify(body) }),
+	logout: (body: AuthRefreshRequest) =>
+		fetchJson<void>('/auth/logout', { method: 'POST', body: JSON.stringify(body) }),
+	oidcStart: (spaceId?: string) =>
+		fetchJson<OidcStartResponse>(
+			spaceId ? `/auth/oidc/start?space_id=${encodeURIComponent(spaceId)}` : '/auth/oidc/start'
+		),
+	oidcCallback: (body: OidcCallbackRequest) =>
+		fetchJson<AuthLoginResponse>('/auth/oidc/callback', { method: 'POST', body: JSON.stringify(body) }),
+	resetPassword: (body: AuthResetPasswordRequest) =>
+		fetchJson<void>('/auth/reset', { method: 'POST', body: JSON.stringify(body) }),
+	passkeyLoginStart: () =>
+		fetchJson<PasskeyRequestOptions>('/auth/webauthn/login/start', {
+			method: 'POST',
+			body: JSON.stringify({})
+		}),
+	passkeyRegisterStart: () =>
+		fetchJson<PasskeyCreationOptions>('/auth/webauthn/register/start', { method: 'POST' }),
+	passkeyRegisterFinish: (body: PasskeyRegisterFinishRequest) =>
+		fetchJson<Passkey>('/auth/webauthn/register/finish', {
+			method: 'POST',
+			body: JSON.stringify(body)
+		}),
+	getPasskeys: () => fetchJson<Passkey[]>('/auth/webauthn/credentials'),
+	deletePasskey: (credentialId: string) =>
+		fetchJson<void>(`/auth/webauthn/credentials/${encodeURIComponent(credentialId)}`, {
+			method: 'DELETE'
+		}),
 	revokeSessions: () => fetchJson<AuthTokenResponse>('/auth/revoke-sessions', { method: 'POST' }),
+	getTwoFactorStatus: () => fetchJson<TwoFactorStatus>('/auth/2fa'),
+	setupTwoFactor: (body: TwoFactorPasswordRequest) =>
+		fetchJson<TwoFactorSetupResponse>('/auth/2fa/setup', { method: 'POST', body: JSON.stringify(body) }),
+	verifyTwoFactor: (body: TwoFactorVerifyRequest) =>
+		fetchJson<TwoFactorRecoveryCodes>('/auth/2fa/verify', { method: 'POST', body: JSON.stringify(body) }),
+	disableTwoFactor: (body: TwoFactorPasswordRequest) =>
+		fetchJson<void>('/auth/2fa/disable', { method: 'POST', body: JSON.stringify(body) }),
+	setTwoFactorPolicy: (body: TwoFactorPolicy) =>
+		fetchJson<TwoFactorPolicy>('/auth/2fa/policy', { method: 'PUT', body: JSON.stringify(body) }),
+	getSessions: () => fetchJson<DeviceSession[]>('/auth/sessions'),
+	revokeSession: (sessionId: string) =>
+		fetchJson<void>(`/auth/sessions/${sessionId}`, { method: 'DELETE' }),
 	me: () => fetchJson<AuthUser>('/auth/me'),
 	updateMe: (body: AuthUpdateProfileRequest) =>
 		fetchJson<AuthUser>('/auth/me', { method: 'PATCH', body: JSON.stringify(body) }),
@@ -153,9 +302,41 @@ export const api = {
 			method: 'PATCH',
 			body: JSON.stringify(body)
 		}),
+	issueResetToken: (userId: string) =>
+		fetchJson<ResetTokenResponse>(`/auth/members/${userId}/reset-token`, { method: 'POST' }),
 	getListGrants: () => fetchJson<ListGrant[]>('/auth/grants'),
 	setListGrant: (body: SetListGrantRequest) =>
 		fetchJson<ListGrant>('/auth/grants', { method: 'PUT', body: JSON.stringify(body) }),
+	clearMemberLockout: (userId: string) =>
+		fetchJson<void>(`/auth/members/${userId}/lockout`, { method: 'DELETE' }),
+	resetMemberTwoFactor: (userId: string) =>
+		fetchJson<void>(`/auth/members/${userId}/2fa`, { method: 'DELETE' }),
+	getMemberSessions: (userId: string) =>
+		fetchJson<DeviceSession[]>(`/auth/members/${userId}/sessions`),
+	revokeMemberSession: (userId: string, sessionId: string) =>
+		fetchJson<void>(`/auth/members/${userId}/sessions/${sessionId}`, { method: 'DELETE' }),
+	getApiTokens: () => fetchJson<ApiToken[]>('/auth/api-tokens'),
+	createApiToken: (body: CreateApiTokenRequest) =>
+		fetchJson<CreatedApiToken>('/auth/api-tokens', { method: 'POST', body: JSON.stringify(body) }),
+	revokeApiToken: (tokenId: string) =>
+		fetchJson<void>(`/auth/api-tokens/${encodeURIComponent(tokenId)}`, { method: 'DELETE' }),
+	getWebhooks: () => fetchJson<Webhook[]>('/auth/webhooks'),
+	createWebhook: (body: CreateWebhookRequest) =>
+		fetchJson<CreatedWebhook>('/auth/webhooks', { method: 'POST', body: JSON.stringify(body) }),
+	deleteWebhook: (webhookId: string) =>
+		fetchJson<void>(`/auth/webhooks/${encodeURIComponent(webhookId)}`, { method: 'DELETE' }),
+	getWebhookDeliveries: (webhookId: string, limit?: number) =>
+		fetchJson<WebhookDelivery[]>(
+			`/auth/webhooks/${encodeURIComponent(webhookId)}/deliveries${limit ? `?limit=${limit}` : ''}`
+		),
+	getAuditLog: (query: AuditLogQuery = {}) => {
+		const params = new URLSearchParams();
+		for (const [key, value] of Object.entries(query)) {
+			if (value !== undefined) params.set(key, String(value));
+		}
+		const search = params.toString();
+		return fetchJson<AuditLogEntry[]>(search ? `/auth/audit?${search}` : '/auth/audit');
+	},
 	getLists: () => fetchJson<SyncList[]>('/lists'),
 	createList: (body: { name: string; icon?: string; color?: string; order?: string }) =>
 		fetchJson<SyncList>('/lists', { method: 'POST', body: JSON.stringify(body) }),
@@ -166,6 +347,12 @@ export const api = {
 			method: 'DELETE'
 		}),
 	getTasks: () => fetchJson<SyncTask[]>('/tasks'),
+	getTrash: () => fetchJson<TrashedTask[]>('/tasks/trash'),
+	restoreTask: (id: string) =>
+		fetchJson<SyncTask>(`/tasks/${id}/restore`, {
+			method: 'POST'
+		}),
+	getTaskHistory: (id: string) => fetchJson<TaskEvent[]>(`/tasks/${id}/history`),
 	syncPull: (body: SyncPullRequest = {}) =>
 		fetchJson<SyncPullResponse>('/sync/pull', {
 			method: 'POST',
@@ -1,5 +1,11 @@
 import { afterEach, describe, expect, it } from 'vitest';
-import { buildHeaders, getAuthToken, setAuthToken } from './headers';
+import {
+	buildHeaders,
+	getAuthToken,
+	getRefreshToken,
+	setAuthToken,
+	setRefreshToken
+} from './headers';
 
 describe('auth headers', () => {
 	afterEach(() => {
@@ -29,4 +35,14 @@ describe('auth headers', () => {
 		expect(localStorage.getItem('tasksync:auth-token')).toBeNull();
 		expect(getAuthToken()).toBeNull();
 	});
+
+	it('stores the refresh token separately from the access token', () => {
+		setAuthToken('access');
+		setRefreshToken('rt_abc');
+		expect(getRefreshToken()).toBe('rt_abc');
+		expect(buildHeaders()).toEqual({ authorization: 'Bearer access' });
+		setRefreshToken(null);
+		expect(getRefreshToken()).toBeNull();
+		expect(getAuthToken()).toBe('access');
+	});
 });
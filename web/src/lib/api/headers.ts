@@ -1,4 +1,5 @@
 const authTokenKey = 'tasksync:auth-token';
+const refreshTokenKey = 'tasksync:refresh-token';
 
 export const getAuthToken = () => {
 	if (typeof localStorage === 'undefined') return null;
@@ -15,6 +16,21 @@ export const setAuthToken = (token: string | null) => {
 	localStorage.setItem(authTokenKey, token);
 };
 
+export const getRefreshToken = () => {
+	if (typeof localStorage === 'undefined') return null;
+	const token = localStorage.getItem(refreshTokenKey)?.trim();
+	return token ? token : null;
+};
+
+export const setRefreshToken = (token: string | null) => {
+	if (typeof localStorage === 'undefined') return;
+	if (!token) {
+		localStorage.removeItem(refreshTokenKey);
+		return;
+	}
+	localStorage.setItem(refreshTokenKey, token);
+};
+
 export const buildHeaders = (): Record<string, string> => {
 	const token = getAuthToken();
 	if (token) {
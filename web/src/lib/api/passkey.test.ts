@@ -0,0 +1,46 @@
+import { afterEach, describe, expect, it, vi } from 'vitest';
+import { fromBase64Url, getPasskeyAssertion, toBase64Url } from './passkey';
+
+const bytes = (...values: number[]) => new Uint8Array(values).buffer;
+
+describe('passkey helpers', () => {
+	afterEach(() => {
+		vi.unstubAllGlobals();
+	});
+
+	it('round-trips base64url without padding', () => {
+		const encoded = toBase64Url(bytes(0xfb, 0xff, 0xfe, 0x01));
+		expect(encoded).toBe('-__-AQ');
+		expect(new Uint8Array(fromBase64Url(encoded))).toEqual(new Uint8Array([0xfb, 0xff, 0xfe, 0x01]));
+		expect(new Uint8Array(fromBase64Url('AQ=='))).toEqual(new Uint8Array([1]));
+	});
+
+	it('encodes an assertion in the JSON form the server expects', async () => {
+		const get = vi.fn().mockResolvedValue({
+			id: 'cred-1',
+			response: {
+				clientDataJSON: bytes(1),
+				authenticatorData: bytes(2),
+				signature: bytes(3),
+				userHandle: null
+			}
+		});
+		vi.stubGlobal('navigator', { credentials: { get } });
+
+		const assertion = await getPasskeyAssertion({
+			challenge: 'AAE',
+			rpId: 'localhost',
+			timeout: 300000,
+			userVerification: 'preferred',
+			allowCredentials: [{ type: 'public-key', id: 'Ag' }]
+		});
+
+		expect(assertion).toEqual({
+			id: 'cred-1',
+			response: { clientDataJSON: 'AQ', authenticatorData: 'Ag', signature: 'Aw' }
+		});
+		const publicKey = get.mock.calls[0][0].publicKey;
+		expect(new Uint8Array(publicKey.challenge)).toEqual(new Uint8Array([0, 1]));
+		expect(new Uint8Array(publicKey.allowCredentials[0].id)).toEqual(new Uint8Array([2]));
+	});
+});
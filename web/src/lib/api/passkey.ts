@@ -0,0 +1,90 @@
+import type {
+	PasskeyAssertion,
+	PasskeyCreationOptions,
+	PasskeyRegistration,
+	PasskeyRequestOptions
+} from '$shared/types/auth';
+
+// The server speaks the WebAuthn JSON forms (base64url strings); the browser
+// API wants and returns ArrayBuffers. These convert between the two so the
+// app does not depend on `PublicKeyCredential.parseCreationOptionsFromJSON`,
+// which older WebKit builds lack.
+
+export const toBase64Url = (buffer: ArrayBuffer): string => {
+	let binary = '';
+	for (const byte of new Uint8Array(buffer)) binary += String.fromCharCode(byte);
+	return btoa(binary).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+};
+
+export const fromBase64Url = (value: string): ArrayBuffer => {
+	const base64 = value.replace(/-/g, '+').replace(/_/g, '/');
+	const binary = atob(base64.padEnd(Math.ceil(base64.length / 4) * 4, '='));
+	const bytes = new Uint8Array(binary.length);
+	for (let i = 0; i < binary.length; i += 1) bytes[i] = binary.charCodeAt(i);
+	return bytes.buffer;
+};
+
+export const passkeysSupported = (): boolean =>
+	typeof window !== 'undefined' &&
+	typeof window.PublicKeyCredential === 'function' &&
+	typeof navigator.credentials?.get === 'function';
+
+const descriptors = (list: { type: 'public-key'; id: string }[]) =>
+	list.map((descriptor) => ({ type: descriptor.type, id: fromBase64Url(descriptor.id) }));
+
+/** Asks the authenticator for a new passkey; rejects if the user cancels. */
+export const createPasskey = async (
+	options: PasskeyCreationOptions
+): Promise<PasskeyRegistration> => {
+	const credential = (await navigator.credentials.create({
+		publicKey: {
+			challenge: fromBase64Url(options.challenge),
+			rp: options.rp,
+			user: {
+				id: fromBase64Url(options.user.id),
+				name: options.user.name,
+				displayName: options.user.displayName
+			},
+			pubKeyCredParams: options.pubKeyCredParams,
+			timeout: options.timeout,
+			attestation: options.attestation,
+			excludeCredentials: descriptors(options.excludeCredentials),
+			authenticatorSelection: options.authenticatorSelection as AuthenticatorSelectionCriteria
+		}
+	})) as PublicKeyCredential | null;
+	if (!credential) throw new Error('No passkey was created');
+	const response = credential.response as AuthenticatorAttestationResponse;
+	return {
+		id: credential.id,
+		response: {
+			clientDataJSON: toBase64Url(response.clientDataJSON),
+			attestationObject: toBase64Url(response.attestationObject)
+		}
+	};
+};
+
+/** Asks the authenticator to sign the server's challenge with a passkey. */
+export const getPasskeyAssertion = async (
+	options: PasskeyRequestOptions
+): Promise<PasskeyAssertion> => {
+	const credential = (await navigator.credentials.get({
+		publicKey: {
+			challenge: fromBase64Url(options.challenge),
+			rpId: options.rpId,
+			timeout: options.timeout,
+			userVerification: options.userVerification,
+			allowCredentials: descriptors(options.allowCredentials)
+		}
+	})) as PublicKeyCredential | null;
+	if (!credential) throw new Error('No passkey was selected');
+	const response = credential.response as AuthenticatorAssertionResponse;
+	return {
+		id: credential.id,
+		response: {
+			clientDataJSON: toBase64Url(response.clientDataJSON),
+			authenticatorData: toBase64Url(response.authenticatorData),
+			signature: toBase64Url(response.signature),
+			...(response.userHandle ? { userHandle: toBase64Url(response.userHandle) } : {})
+		}
+	};
+};
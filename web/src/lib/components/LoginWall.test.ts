@@ -3,8 +3,9 @@ import { render, fireEvent } from '@testing-library/svelte';
 import { tick } from 'svelte';
 import { writable } from 'svelte/store';
 import type { AuthState } from '$lib/stores/auth';
+import type { AuthStatusResponse } from '$shared/types/auth';
 
-// Mock the auth store so tests can control fetchOwnerStatus/login/setupOwner
+// Mock the auth store so tests can control fetchStatus/login/setupOwner
 // independently, while still exposing a real writable for $auth reactive
 // bindings (error display).
 const mockAuthState = writable<AuthState>({
@@ -14,24 +15,60 @@ const mockAuthState = writable<AuthState>({
 	error: null
 });
 
-const mockFetchOwnerStatus = vi.fn();
+const mockFetchStatus = vi.fn();
 const mockLogin = vi.fn();
 const mockSetupOwner = vi.fn();
+const mockResetPassword = vi.fn();
+const mockStartSso = vi.fn();
+const mockCompleteSso = vi.fn();
+const mockSsoStarted = vi.fn();
+const mockLoginWithPasskey = vi.fn();
+const mockReplaceState = vi.fn();
+
+const status = (
+	ownerExists: boolean,
+	oidcEnabled = false,
+	webauthnEnabled = false
+): AuthStatusResponse => ({
+	owner_exists: ownerExists,
+	oidc_enabled: oidcEnabled,
+	webauthn_enabled: webauthnEnabled
+});
+
+vi.mock('$lib/api/passkey', () => ({
+	passkeysSupported: () => true
+}));
+
+vi.mock('$app/navigation', () => ({
+	replaceState: (...args: unknown[]) => mockReplaceState(...args)
+}));
 
 vi.mock('$lib/stores/auth', () => ({
 	auth: {
 		subscribe: (run: (state: AuthState) => void) => mockAuthState.subscribe(run),
-		fetchOwnerStatus: (...args: unknown[]) => mockFetchOwnerStatus(...args),
+		fetchStatus: (...args: unknown[]) => mockFetchStatus(...args),
 		login: (...args: unknown[]) => mockLogin(...args),
-		setupOwner: (...args: unknown[]) => mockSetupOwner(...args)
+		setupOwner: (...args: unknown[]) => mockSetupOwner(...args),
+		resetPassword: (...args: unknown[]) => mockResetPassword(...args),
+		startSso: (...args: unknown[]) => mockStartSso(...args),
+		completeSso: (...args: unknown[]) => mockCompleteSso(...args),
+		ssoStarted: (...args: unknown[]) => mockSsoStarted(...args),
+		loginWithPasskey: (...args: unknown[]) => mockLoginWithPasskey(...args),
+		clearError: () => mockAuthState.update((state) => ({ ...state, error: null }))
 	}
 }));
 
 beforeEach(() => {
 	mockAuthState.set({ status: 'anonymous', source: null, user: null, error: null });
-	mockFetchOwnerStatus.mockReset();
+	mockFetchStatus.mockReset();
 	mockLogin.mockReset();
 	mockSetupOwner.mockReset();
+	mockResetPassword.mockReset();
+	mockStartSso.mockReset();
+	mockCompleteSso.mockReset();
+	mockSsoStarted.mockReset();
+	mockLoginWithPasskey.mockReset();
+	mockReplaceState.mockReset();
 });
 
 async function renderWall() {
@@ -43,7 +80,7 @@ async function renderWall() {
 
 describe('LoginWall', () => {
 	it('renders the setup form when owner_exists is false', async () => {
-		mockFetchOwnerStatus.mockResolvedValue(false);
+		mockFetchStatus.mockResolvedValue(status(false));
 
 		const { getByTestId, queryByTestId } = await renderWall();
 		await tick();
@@ -59,7 +96,7 @@ describe('LoginWall', () => {
 	});
 
 	it('renders the login form when owner_exists is true', async () => {
-		mockFetchOwnerStatus.mockResolvedValue(true);
+		mockFetchStatus.mockResolvedValue(status(true));
 
 		const { getByTestId, queryByTestId } = await renderWall();
 		await tick();
@@ -76,7 +113,7 @@ describe('LoginWall', () => {
 
 	it('defaults to the login form when the status fetch fails (fail toward login)', async () => {
 		const warnSpy = vi.spyOn(console, 'warn').mockImplementation(() => undefined);
-		mockFetchOwnerStatus.mockRejectedValue(new Error('offline'));
+		mockFetchStatus.mockRejectedValue(new Error('offline'));
 
 		const { getByTestId, queryByTestId } = await renderWall();
 		await tick();
@@ -90,9 +127,9 @@ describe('LoginWall', () => {
 	});
 
 	it('renders a minimal loading state while the status probe is in flight', async () => {
-		let resolveStatus: (value: boolean) => void = () => undefined;
-		mockFetchOwnerStatus.mockReturnValue(
-			new Promise<boolean>((resolve) => {
+		let resolveStatus: (value: AuthStatusResponse) => void = () => undefined;
+		mockFetchStatus.mockReturnValue(
+			new Promise<AuthStatusResponse>((resolve) => {
 				resolveStatus = resolve;
 			})
 		);
@@ -103,7 +140,7 @@ describe('LoginWall', () => {
 		expect(queryByTestId('loginwall-setup')).toBeNull();
 		expect(queryByTestId('loginwall-login')).toBeNull();
 
-		resolveStatus(true);
+		resolveStatus(status(true));
 		await tick();
 		await tick();
 
@@ -111,7 +148,7 @@ describe('LoginWall', () => {
 	});
 
 	it('disables the sign-in button until email and password are filled, then calls auth.login', async () => {
-		mockFetchOwnerStatus.mockResolvedValue(true);
+		mockFetchStatus.mockResolvedValue(status(true));
 		mockLogin.mockResolvedValue(undefined);
 
 		const { getByTestId } = await renderWall();
@@ -137,8 +174,164 @@ describe('LoginWall', () => {
 		expect(mockLogin).toHaveBeenCalledWith('owner@example.com', 'hunter2', 's1');
 	});
 
+	it('asks for an authentication code when the account has two-factor enabled', async () => {
+		const { ApiError } = await import('$lib/api/client');
+		mockFetchStatus.mockResolvedValue(status(true));
+		mockLogin
+			.mockRejectedValueOnce(new ApiError(401, 'Unauthorized', undefined, 'two_factor_required'))
+			.mockResolvedValueOnce(undefined);
+
+		const { getByTestId, queryByTestId } = await renderWall();
+		await tick();
+		await tick();
+
+		await fireEvent.input(getByTestId('auth-email'), { target: { value: 'owner@example.com' } });
+		await fireEvent.input(getByTestId('auth-password'), { target: { value: 'hunter2' } });
+		expect(queryByTestId('auth-two-factor-code')).toBeNull();
+		await fireEvent.click(getByTestId('auth-signin'));
+		await tick();
+		await tick();
+
+		const code = getByTestId('auth-two-factor-code') as HTMLInputElement;
+		await fireEvent.input(code, { target: { value: ' 123456 ' } });
+		await fireEvent.click(getByTestId('auth-signin'));
+		await tick();
+
+		expect(mockLogin).toHaveBeenLastCalledWith('owner@example.com', 'hunter2', 's1', '123456');
+	});
+
+	it('redeems a reset code from the login form and returns to sign in', async () => {
+		mockFetchStatus.mockResolvedValue(status(true));
+		mockResetPassword.mockResolvedValue(undefined);
+
+		const { getByTestId, queryByTestId } = await renderWall();
+		await tick();
+		await tick();
+
+		await fireEvent.click(getByTestId('auth-forgot'));
+		await tick();
+		expect(getByTestId('loginwall-reset')).toBeTruthy();
+		const submit = getByTestId('reset-submit') as HTMLButtonElement;
+		expect(submit.disabled).toBe(true);
+
+		await fireEvent.input(getByTestId('reset-token'), { target: { value: ' pr_abc ' } });
+		await fireEvent.input(getByTestId('reset-password'), { target: { value: 'new-secret' } });
+		await tick();
+		expect(submit.disabled).toBe(false);
+		await fireEvent.click(submit);
+		await tick();
+		await tick();
+
+		expect(mockResetPassword).toHaveBeenCalledWith('pr_abc', 'new-secret');
+		expect(getByTestId('loginwall-login')).toBeTruthy();
+		expect(getByTestId('reset-done')).toBeTruthy();
+		expect(queryByTestId('loginwall-reset')).toBeNull();
+	});
+
+	it('offers single sign-on only when the server has it configured', async () => {
+		mockFetchStatus.mockResolvedValue(status(true));
+		const first = await renderWall();
+		await tick();
+		await tick();
+		expect(first.queryByTestId('auth-sso')).toBeNull();
+		first.unmount();
+
+		mockFetchStatus.mockResolvedValue(status(true, true));
+		mockStartSso.mockRejectedValue(new Error('API 502 Bad Gateway'));
+		const { getByTestId } = await renderWall();
+		await tick();
+		await tick();
+
+		await fireEvent.click(getByTestId('auth-sso'));
+		await tick();
+		expect(mockStartSso).toHaveBeenCalledWith('s1');
+	});
+
+	it('offers passkey sign-in only when the server accepts passkeys', async () => {
+		mockFetchStatus.mockResolvedValue(status(true));
+		const first = await renderWall();
+		await tick();
+		await tick();
+		expect(first.queryByTestId('auth-passkey')).toBeNull();
+		first.unmount();
+
+		mockFetchStatus.mockResolvedValue(status(true, false, true));
+		mockLoginWithPasskey.mockResolvedValue(undefined);
+		const { getByTestId } = await renderWall();
+		await tick();
+		await tick();
+
+		// No password needed; the email is optional.
+		await fireEvent.click(getByTestId('auth-passkey'));
+		await tick();
+		expect(mockLoginWithPasskey).toHaveBeenLastCalledWith(undefined, 's1');
+
+		await fireEvent.input(getByTestId('auth-email'), { target: { value: ' owner@example.com ' } });
+		await fireEvent.click(getByTestId('auth-passkey'));
+		await tick();
+		expect(mockLoginWithPasskey).toHaveBeenLastCalledWith('owner@example.com', 's1');
+	});
+
+	it('finishes single sign-on when the provider redirects back with a code', async () => {
+		window.history.pushState({}, '', '/?code=abc&state=xyz&iss=https%3A%2F%2Fid.example.com');
+		mockFetchStatus.mockResolvedValue(status(true, true));
+		mockSsoStarted.mockReturnValue(true);
+		mockCompleteSso.mockResolvedValue(undefined);
+
+		await renderWall();
+		await tick();
+		await tick();
+		await tick();
+
+		expect(mockSsoStarted).toHaveBeenCalledWith('xyz');
+		expect(mockCompleteSso).toHaveBeenCalledWith('abc', 'xyz');
+		const cleaned = mockReplaceState.mock.calls[0][0] as URL;
+		expect(cleaned.search).toBe('');
+		window.history.pushState({}, '', '/');
+	});
+
+	it('ignores a single sign-on response this browser did not start', async () => {
+		window.history.pushState({}, '', '/?code=planted&state=theirs');
+		mockFetchStatus.mockResolvedValue(status(true, true));
+		mockSsoStarted.mockReturnValue(false);
+
+		await renderWall();
+		await tick();
+		await tick();
+		await tick();
+
+		expect(mockCompleteSso).not.toHaveBeenCalled();
+		const cleaned = mockReplaceState.mock.calls[0][0] as URL;
+		expect(cleaned.search).toBe('');
+		window.history.pushState({}, '', '/');
+	});
+
+	it('finishes single sign-on with an authentication code when two-factor is enabled', async () => {
+		const { ApiError } = await import('$lib/api/client');
+		window.history.pushState({}, '', '/?code=abc&state=xyz');
+		mockFetchStatus.mockResolvedValue(status(true, true));
+		mockSsoStarted.mockReturnValue(true);
+		mockCompleteSso
+			.mockRejectedValueOnce(new ApiError(401, 'Unauthorized', undefined, 'two_factor_required'))
+			.mockResolvedValueOnce(undefined);
+
+		const { getByTestId, queryByTestId } = await renderWall();
+		await tick();
+		await tick();
+		await tick();
+
+		await fireEvent.input(getByTestId('auth-two-factor-code'), { target: { value: ' 123456 ' } });
+		await fireEvent.click(getByTestId('auth-sso-continue'));
+		await tick();
+		await tick();
+
+		expect(mockCompleteSso).toHaveBeenLastCalledWith(null, 'xyz', '123456');
+		expect(queryByTestId('auth-sso-continue')).toBeNull();
+		window.history.pushState({}, '', '/');
+	});
+
 	it('disables the create-owner button until required fields are filled, then calls auth.setupOwner', async () => {
-		mockFetchOwnerStatus.mockResolvedValue(false);
+		mockFetchStatus.mockResolvedValue(status(false));
 		mockSetupOwner.mockResolvedValue(undefined);
 
 		const { getByTestId } = await renderWall();
@@ -167,7 +360,7 @@ describe('LoginWall', () => {
 	});
 
 	it('shows the friendly $auth.error message inline on the login form', async () => {
-		mockFetchOwnerStatus.mockResolvedValue(true);
+		mockFetchStatus.mockResolvedValue(status(true));
 
 		const { getByTestId, queryByText } = await renderWall();
 		await tick();
@@ -8,8 +8,15 @@ vi.mock('$lib/api/client', () => ({
 		setupOwner: vi.fn(),
 		authStatus: vi.fn(),
 		revokeSessions: vi.fn(),
-		changePassword: vi.fn()
+		logout: vi.fn(),
+		changePassword: vi.fn(),
+		resetPassword: vi.fn(),
+		oidcStart: vi.fn(),
+		oidcCallback: vi.fn(),
+		passkeyLoginStart: vi.fn()
 	},
+	apiErrorCode: (err: unknown) =>
+		err && typeof err === 'object' && 'code' in err ? String((err as { code: unknown }).code) : null,
 	apiErrorStatus: (err: unknown) => {
 		const message = err instanceof Error ? err.message : String(err);
 		const match = /^API\s+(\d{3})\b/.exec(message);
@@ -19,8 +26,14 @@ vi.mock('$lib/api/client', () => ({
 	}
 }));
 
+vi.mock('$lib/api/passkey', () => ({
+	createPasskey: vi.fn(),
+	getPasskeyAssertion: vi.fn()
+}));
+
 import { api } from '$lib/api/client';
-import { getAuthToken } from '$lib/api/headers';
+import { getAuthToken, getRefreshToken } from '$lib/api/headers';
+import { getPasskeyAssertion } from '$lib/api/passkey';
 import { auth } from './auth';
 
 const mockedApi = vi.mocked(api);
@@ -36,6 +49,7 @@ const meUser = {
 describe('auth store', () => {
 	beforeEach(() => {
 		localStorage.clear();
+		sessionStorage.clear();
 		vi.clearAllMocks();
 	});
 
@@ -121,6 +135,177 @@ describe('auth store', () => {
 		expect(auth.get().error).toBe('Sign in failed. Check your email, password, and space ID.');
 	});
 
+	it('maps login 429 to lockout guidance', async () => {
+		mockedApi.login.mockRejectedValue(new Error('API 429 Too Many Requests'));
+
+		await expect(auth.login('admin@example.com', 'tasksync', 's1')).rejects.toThrow();
+
+		expect(auth.get().status).toBe('anonymous');
+		expect(auth.get().error).toBe(
+			'Too many failed sign-in attempts. Wait a few minutes and try again.'
+		);
+	});
+
+	it('maps two_factor_required to a code prompt and forwards the code', async () => {
+		mockedApi.login.mockRejectedValue(
+			Object.assign(new Error('API 401 Unauthorized'), { code: 'two_factor_required' })
+		);
+
+		await expect(auth.login('admin@example.com', 'tasksync', 's1')).rejects.toThrow();
+
+		expect(auth.get().error).toBe('Enter the code from your authenticator app, or a recovery code.');
+
+		mockedApi.login.mockResolvedValue({ token: 'jwt-token', refresh_token: 'rt', ...meUser });
+		await auth.login('admin@example.com', 'tasksync', 's1', '123456');
+
+		expect(mockedApi.login).toHaveBeenLastCalledWith({
+			email: 'admin@example.com',
+			password: 'tasksync',
+			space_id: 's1',
+			two_factor_code: '123456'
+		});
+	});
+
+	it('resetPassword sends the code and maps a spent code to guidance', async () => {
+		mockedApi.resetPassword.mockResolvedValue(undefined);
+		await auth.resetPassword('pr_abc', 'new-secret');
+		expect(mockedApi.resetPassword).toHaveBeenCalledWith({
+			token: 'pr_abc',
+			new_password: 'new-secret'
+		});
+		expect(auth.get().error).toBeNull();
+
+		mockedApi.resetPassword.mockRejectedValue(new Error('API 401 Unauthorized'));
+		await expect(auth.resetPassword('pr_abc', 'new-secret')).rejects.toThrow();
+		expect(auth.get().error).toBe(
+			'That reset code is invalid, already used, or expired. Ask an admin for a new one.'
+		);
+	});
+
+	const startSso = async () => {
+		mockedApi.oidcStart.mockResolvedValue({
+			authorization_url: 'https://id.example.com/authorize?state=xyz',
+			state: 'xyz',
+			binding: 'bind-1'
+		});
+		await auth.startSso();
+	};
+
+	it('completeSso stores the token like a password login', async () => {
+		await startSso();
+		expect(auth.ssoStarted('xyz')).toBe(true);
+		mockedApi.oidcCallback.mockResolvedValue({ token: 'sso-token', refresh_token: 'rt', ...meUser });
+
+		await auth.completeSso('abc', 'xyz');
+
+		expect(mockedApi.oidcCallback).toHaveBeenCalledWith({
+			code: 'abc',
+			state: 'xyz',
+			binding: 'bind-1'
+		});
+		expect(getAuthToken()).toBe('sso-token');
+		expect(auth.get().status).toBe('authenticated');
+		expect(auth.get().user?.email).toBe('admin@example.com');
+		expect(auth.ssoStarted('xyz')).toBe(false);
+	});
+
+	it('completeSso refuses a response to a sign-in this browser did not start', async () => {
+		await startSso();
+
+		await expect(auth.completeSso('planted', 'theirs')).rejects.toThrow();
+
+		expect(mockedApi.oidcCallback).not.toHaveBeenCalled();
+		expect(auth.get().status).toBe('anonymous');
+		expect(auth.get().error).toBe(
+			'Single sign-on was not started in this browser. Please try again.'
+		);
+	});
+
+	it('keeps the sign-in for the authentication code after two_factor_required', async () => {
+		await startSso();
+		mockedApi.oidcCallback
+			.mockRejectedValueOnce(
+				Object.assign(new Error('API 401 Unauthorized'), { code: 'two_factor_required' })
+			)
+			.mockResolvedValueOnce({ token: 'sso-token', refresh_token: 'rt', ...meUser });
+
+		await expect(auth.completeSso('abc', 'xyz')).rejects.toThrow();
+		expect(auth.get().error).toBe(
+			'Enter the code from your authenticator app, or a recovery code, to finish signing in.'
+		);
+		await auth.completeSso(null, 'xyz', '123456');
+
+		expect(mockedApi.oidcCallback).toHaveBeenLastCalledWith({
+			state: 'xyz',
+			binding: 'bind-1',
+			two_factor_code: '123456'
+		});
+		expect(auth.get().status).toBe('authenticated');
+	});
+
+	it('maps a completeSso 403 to membership guidance', async () => {
+		await startSso();
+		mockedApi.oidcCallback.mockRejectedValue(new Error('API 403 Forbidden'));
+
+		await expect(auth.completeSso('abc', 'xyz')).rejects.toThrow();
+
+		expect(auth.get().status).toBe('anonymous');
+		expect(auth.get().error).toBe(
+			'Your single sign-on account has no verified email that is a member of this space.'
+		);
+	});
+
+	it('loginWithPasskey sends the assertion in place of a password', async () => {
+		const options = {
+			challenge: 'c',
+			rpId: 'localhost',
+			timeout: 300000,
+			userVerification: 'preferred' as const,
+			allowCredentials: []
+		};
+		const passkey = {
+			id: 'cred-1',
+			response: { clientDataJSON: 'a', authenticatorData: 'b', signature: 'c' }
+		};
+		mockedApi.passkeyLoginStart.mockResolvedValue(options);
+		vi.mocked(getPasskeyAssertion).mockResolvedValue(passkey);
+		mockedApi.login.mockResolvedValue({ token: 'passkey-token', refresh_token: 'rt', ...meUser });
+
+		await auth.loginWithPasskey(undefined, 's1');
+
+		expect(getPasskeyAssertion).toHaveBeenCalledWith(options);
+		expect(mockedApi.login).toHaveBeenCalledWith({
+			email: '',
+			password: '',
+			space_id: 's1',
+			two_factor_code: undefined,
+			passkey
+		});
+		expect(getAuthToken()).toBe('passkey-token');
+		expect(auth.get().status).toBe('authenticated');
+	});
+
+	it('maps a cancelled passkey prompt to a gentle message', async () => {
+		mockedApi.passkeyLoginStart.mockResolvedValue({
+			challenge: 'c',
+			rpId: 'localhost',
+			timeout: 300000,
+			userVerification: 'preferred',
+			allowCredentials: []
+		});
+		vi.mocked(getPasskeyAssertion).mockRejectedValue(
+			new DOMException('The operation was aborted.', 'NotAllowedError')
+		);
+
+		await expect(auth.loginWithPasskey('admin@example.com')).rejects.toThrow();
+
+		// The email is not sent to the unauthenticated start.
+		expect(mockedApi.passkeyLoginStart).toHaveBeenCalledWith();
+		expect(mockedApi.login).not.toHaveBeenCalled();
+		expect(auth.get().status).toBe('anonymous');
+		expect(auth.get().error).toBe('Passkey sign in was cancelled.');
+	});
+
 	it('clears token on logout', async () => {
 		mockedApi.login.mockResolvedValue({
 			token: 'jwt-token',
@@ -134,6 +319,22 @@ describe('auth store', () => {
 		expect(auth.get().status).toBe('anonymous');
 	});
 
+	it('asks the server to revoke the session on logout', async () => {
+		mockedApi.login.mockResolvedValue({
+			token: 'jwt-token',
+			refresh_token: 'rt_current',
+			...meUser
+		});
+		mockedApi.logout.mockRejectedValue(new Error('offline'));
+		await auth.login('admin@example.com', 'tasksync', 's1');
+
+		auth.logout();
+
+		expect(mockedApi.logout).toHaveBeenCalledWith({ refresh_token: 'rt_current' });
+		expect(getRefreshToken()).toBeNull();
+		expect(auth.get().status).toBe('anonymous');
+	});
+
 	it('isAuthenticated returns true when authenticated and false otherwise', async () => {
 		mockedApi.me.mockResolvedValue(meUser);
 		await auth.hydrate();
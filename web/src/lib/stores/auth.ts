@@ -1,15 +1,23 @@
 import { get, writable } from 'svelte/store';
-import { api, apiErrorStatus } from '$lib/api/client';
-import { getAuthToken, setAuthToken } from '$lib/api/headers';
+import { api, apiErrorCode, apiErrorStatus } from '$lib/api/client';
+import { getAuthToken, getRefreshToken, setAuthToken, setRefreshToken } from '$lib/api/headers';
+import { createPasskey, getPasskeyAssertion } from '$lib/api/passkey';
 import type {
 	AuthChangePasswordRequest,
+	AuthLoginResponse,
 	AuthSetupRequest,
+	AuthStatusResponse,
 	AuthUpdateProfileRequest,
-	AuthUser
+	AuthUser,
+	Passkey
 } from '$shared/types/auth';
 
 const authUserKey = 'tasksync:auth-user';
 const staleAuthModeKey = 'tasksync:auth-mode';
+// The `state` and `binding` of the single sign-on this tab started. Session
+// storage keeps them out of other tabs and browsers, so a provider response
+// this browser did not ask for is refused.
+const ssoPendingKey = 'tasksync:sso-pending';
 
 type AuthStatus = 'loading' | 'authenticated' | 'anonymous';
 type AuthOrigin = 'token' | null;
@@ -93,6 +101,9 @@ const formatHydrateError = (err: unknown): string => {
 };
 
 const formatLoginError = (err: unknown): string => {
+	if (apiErrorCode(err) === 'two_factor_required') {
+		return 'Enter the code from your authenticator app, or a recovery code.';
+	}
 	const code = apiErrorStatus(err);
 	if (code === 400) {
 		return 'Sign in request is invalid. Check email, password, and space ID.';
@@ -100,6 +111,9 @@ const formatLoginError = (err: unknown): string => {
 	if (code === 401 || code === 403) {
 		return 'Sign in failed. Check your email, password, and space ID.';
 	}
+	if (code === 429) {
+		return 'Too many failed sign-in attempts. Wait a few minutes and try again.';
+	}
 	if (code === 404) {
 		return 'Sign in endpoint was not found (404). Check the API URL and server version.';
 	}
@@ -120,6 +134,9 @@ const formatSetupError = (err: unknown): string => {
 	if (code === 409) {
 		return 'An owner account already exists. Please sign in instead.';
 	}
+	if (code === 429) {
+		return 'Too many failed attempts from this device. Wait a few minutes and try again.';
+	}
 	if (code === 404) {
 		return 'Setup endpoint was not found (404). Check the API URL and server version.';
 	}
@@ -132,6 +149,124 @@ const formatSetupError = (err: unknown): string => {
 	return `Owner setup failed: ${authError(err)}`;
 };
 
+const formatResetError = (err: unknown): string => {
+	const code = apiErrorStatus(err);
+	if (code === 400) {
+		return 'Enter the reset code and a new password of at least 8 characters.';
+	}
+	if (code === 401) {
+		return 'That reset code is invalid, already used, or expired. Ask an admin for a new one.';
+	}
+	if (code === 429) {
+		return 'Too many failed attempts from this device. Wait a few minutes and try again.';
+	}
+	if (code && code >= 500) {
+		return `Password reset is temporarily unavailable (${code}). Please try again shortly.`;
+	}
+	if (isLikelyNetworkError(err)) {
+		return 'Cannot reach the server. Check your connection and API URL.';
+	}
+	return `Password reset failed: ${authError(err)}`;
+};
+
+const formatSsoError = (err: unknown): string => {
+	if (apiErrorCode(err) === 'two_factor_required') {
+		return 'Enter the code from your authenticator app, or a recovery code, to finish signing in.';
+	}
+	const code = apiErrorStatus(err);
+	if (code === 401) {
+		return 'Single sign-on did not complete. Please try again.';
+	}
+	if (code === 403) {
+		return 'Your single sign-on account has no verified email that is a member of this space.';
+	}
+	if (code === 404) {
+		return 'Single sign-on is not configured on this server.';
+	}
+	if (code === 429) {
+		return 'Too many failed attempts from this device. Wait a few minutes and try again.';
+	}
+	if (code === 502) {
+		return 'The identity provider could not be reached. Please try again shortly.';
+	}
+	if (code && code >= 500) {
+		return `Single sign-on is temporarily unavailable (${code}). Please try again shortly.`;
+	}
+	if (isLikelyNetworkError(err)) {
+		return 'Cannot reach the server. Check your connection and API URL.';
+	}
+	return `Single sign-on failed: ${authError(err)}`;
+};
+
+const formatPasskeyError = (err: unknown): string => {
+	if (err instanceof DOMException && err.name === 'NotAllowedError') {
+		return 'Passkey sign in was cancelled.';
+	}
+	if (apiErrorCode(err) === 'two_factor_required') {
+		return 'This passkey did not verify you. Enter your authentication code, then try again.';
+	}
+	const code = apiErrorStatus(err);
+	if (code === 401 || code === 403) {
+		return 'That passkey is not registered for this account and space.';
+	}
+	if (code === 404) {
+		return 'Passkeys are not configured on this server.';
+	}
+	if (code === 429) {
+		return 'Too many failed sign-in attempts. Wait a few minutes and try again.';
+	}
+	if (code && code >= 500) {
+		return `Passkey sign in is temporarily unavailable (${code}). Please try again shortly.`;
+	}
+	if (isLikelyNetworkError(err)) {
+		return 'Cannot reach the server. Check your connection and API URL.';
+	}
+	return `Passkey sign in failed: ${authError(err)}`;
+};
+
+interface PendingSso {
+	state: string;
+	binding: string;
+}
+
+const readPendingSso = (): PendingSso | null => {
+	if (typeof sessionStorage === 'undefined') return null;
+	try {
+		const parsed = JSON.parse(sessionStorage.getItem(ssoPendingKey) ?? 'null') as unknown;
+		if (!parsed || typeof parsed !== 'object') return null;
+		const { state, binding } = parsed as Record<string, unknown>;
+		return typeof state === 'string' && typeof binding === 'string' ? { state, binding } : null;
+	} catch {
+		return null;
+	}
+};
+
+const clearPendingSso = () => {
+	if (typeof sessionStorage !== 'undefined') sessionStorage.removeItem(ssoPendingKey);
+};
+
+// Stores the tokens and user of a login, setup, SSO or passkey response.
+const completeSignIn = (response: AuthLoginResponse): AuthUser => {
+	const user: AuthUser = {
+		user_id: response.user_id,
+		email: response.email,
+		display: response.display,
+		avatar_icon: response.avatar_icon,
+		space_id: response.space_id,
+		role: response.role
+	};
+	setAuthToken(response.token);
+	setRefreshToken(response.refresh_token);
+	persistUser(user);
+	authStore.set({
+		status: 'authenticated',
+		source: 'token',
+		user,
+		error: null
+	});
+	return user;
+};
+
 const isAuthFailure = (err: unknown) => {
 	const code = apiErrorStatus(err);
 	return code === 401 || code === 403;
@@ -182,6 +317,7 @@ export const auth = {
 		} catch (err) {
 			if (isAuthFailure(err)) {
 				setAuthToken(null);
+				setRefreshToken(null);
 				persistUser(null);
 				authStore.set({
 					status: 'anonymous',
@@ -211,7 +347,7 @@ export const auth = {
 			});
 		}
 	},
-	async login(email: string, password: string, spaceId?: string) {
+	async login(email: string, password: string, spaceId?: string, twoFactorCode?: string) {
 		authStore.update((current) => ({
 			...current,
 			status: 'loading',
@@ -221,35 +357,54 @@ export const auth = {
 			const response = await api.login({
 				email,
 				password,
-				space_id: spaceId || undefined
+				space_id: spaceId || undefined,
+				two_factor_code: twoFactorCode || undefined
 			});
-			const user: AuthUser = {
-				user_id: response.user_id,
-				email: response.email,
-				display: response.display,
-				avatar_icon: response.avatar_icon,
-				space_id: response.space_id,
-				role: response.role
-			};
-			setAuthToken(response.token);
-			persistUser(user);
+			return completeSignIn(response);
+		} catch (err) {
 			authStore.set({
-				status: 'authenticated',
-				source: 'token',
-				user,
-				error: null
+				status: 'anonymous',
+				source: null,
+				user: null,
+				error: formatLoginError(err)
+			});
+			throw err;
+		}
+	},
+	/** Signs in with a passkey instead of a password; a given `email` must match its account. */
+	async loginWithPasskey(email?: string, spaceId?: string, twoFactorCode?: string) {
+		authStore.update((current) => ({
+			...current,
+			status: 'loading',
+			error: null
+		}));
+		try {
+			const options = await api.passkeyLoginStart();
+			const passkey = await getPasskeyAssertion(options);
+			const response = await api.login({
+				email: email ?? '',
+				password: '',
+				space_id: spaceId || undefined,
+				two_factor_code: twoFactorCode || undefined,
+				passkey
 			});
-			return user;
+			return completeSignIn(response);
 		} catch (err) {
 			authStore.set({
 				status: 'anonymous',
 				source: null,
 				user: null,
-				error: formatLoginError(err)
+				error: formatPasskeyError(err)
 			});
 			throw err;
 		}
 	},
+	/** Registers a passkey on this device for the signed-in user. */
+	async addPasskey(label?: string): Promise<Passkey> {
+		const options = await api.passkeyRegisterStart();
+		const credential = await createPasskey(options);
+		return api.passkeyRegisterFinish({ label: label || undefined, credential });
+	},
 	async setupOwner(body: AuthSetupRequest) {
 		authStore.update((current) => ({
 			...current,
@@ -258,23 +413,7 @@ export const auth = {
 		}));
 		try {
 			const response = await api.setupOwner(body);
-			const user: AuthUser = {
-				user_id: response.user_id,
-				email: response.email,
-				display: response.display,
-				avatar_icon: response.avatar_icon,
-				space_id: response.space_id,
-				role: response.role
-			};
-			setAuthToken(response.token);
-			persistUser(user);
-			authStore.set({
-				status: 'authenticated',
-				source: 'token',
-				user,
-				error: null
-			});
-			return user;
+			return completeSignIn(response);
 		} catch (err) {
 			authStore.set({
 				status: 'anonymous',
@@ -285,10 +424,87 @@ export const auth = {
 			throw err;
 		}
 	},
+	clearError() {
+		authStore.update((current) => ({ ...current, error: null }));
+	},
+	/** Redeems a reset code; the member signs in with the new password afterwards. */
+	async resetPassword(token: string, newPassword: string): Promise<void> {
+		authStore.update((current) => ({ ...current, error: null }));
+		try {
+			await api.resetPassword({ token, new_password: newPassword });
+		} catch (err) {
+			authStore.update((current) => ({ ...current, error: formatResetError(err) }));
+			throw err;
+		}
+	},
+	async fetchStatus(): Promise<AuthStatusResponse> {
+		return api.authStatus();
+	},
 	async fetchOwnerStatus(): Promise<boolean> {
 		const response = await api.authStatus();
 		return response.owner_exists;
 	},
+	/** Returns the identity provider URL to send the browser to. */
+	async startSso(spaceId?: string): Promise<string> {
+		authStore.update((current) => ({ ...current, error: null }));
+		try {
+			const response = await api.oidcStart(spaceId);
+			sessionStorage.setItem(
+				ssoPendingKey,
+				JSON.stringify({ state: response.state, binding: response.binding })
+			);
+			return response.authorization_url;
+		} catch (err) {
+			authStore.update((current) => ({ ...current, error: formatSsoError(err) }));
+			throw err;
+		}
+	},
+	/** Whether this browser started the single sign-on that `state` answers. */
+	ssoStarted(state: string): boolean {
+		return readPendingSso()?.state === state;
+	},
+	/**
+	 * Finishes SSO with the `code` and `state` the provider redirected back
+	 * with. After `two_factor_required`, call again with no `code` and the
+	 * authentication code.
+	 */
+	async completeSso(code: string | null, state: string, twoFactorCode?: string) {
+		const pending = readPendingSso();
+		if (!pending || pending.state !== state) {
+			authStore.set({
+				status: 'anonymous',
+				source: null,
+				user: null,
+				error: 'Single sign-on was not started in this browser. Please try again.'
+			});
+			throw new Error('Single sign-on was not started in this browser');
+		}
+		authStore.update((current) => ({
+			...current,
+			status: 'loading',
+			error: null
+		}));
+		try {
+			const response = await api.oidcCallback({
+				code: code || undefined,
+				state,
+				binding: pending.binding,
+				two_factor_code: twoFactorCode || undefined
+			});
+			clearPendingSso();
+			return completeSignIn(response);
+		} catch (err) {
+			// The server keeps the sign-in while it waits for the code.
+			if (apiErrorCode(err) !== 'two_factor_required') clearPendingSso();
+			authStore.set({
+				status: 'anonymous',
+				source: null,
+				user: null,
+				error: formatSsoError(err)
+			});
+			throw err;
+		}
+	},
 	async revokeAllSessions(): Promise<void> {
 		const current = get(authStore);
 		if (current.status !== 'authenticated') {
@@ -296,10 +512,12 @@ export const auth = {
 		}
 		const response = await api.revokeSessions();
 		setAuthToken(response.token);
+		setRefreshToken(response.refresh_token);
 	},
 	async changePassword(body: AuthChangePasswordRequest): Promise<void> {
 		const response = await api.changePassword(body);
 		setAuthToken(response.token);
+		setRefreshToken(response.refresh_token);
 	},
 	async updateProfile(body: AuthUpdateProfileRequest) {
 		const current = get(authStore);
@@ -315,8 +533,15 @@ export const auth = {
 		});
 		return updated;
 	},
+	// Signs out locally at once and asks the server to revoke this device's
+	// session; an offline or failed request leaves only the local sign-out.
 	logout() {
+		const refreshToken = getRefreshToken();
+		if (refreshToken) {
+			void api.logout({ refresh_token: refreshToken }).catch(() => undefined);
+		}
 		setAuthToken(null);
+		setRefreshToken(null);
 		persistUser(null);
 		authStore.set({
 			status: 'anonymous',
@@ -164,6 +164,24 @@ describe('tasks store helpers', () => {
 		expect(typeof updated?.completed_ts).toBe('number');
 	});
 
+	it('leaves RRULE roll-forward to the server when toggled complete', () => {
+		tasks.setAll([
+			baseTask({
+				id: 'rrule-1',
+				recurrence_id: 'FREQ=WEEKLY;BYDAY=TU',
+				due_date: '2026-02-03',
+				status: 'pending'
+			})
+		]);
+
+		tasks.toggle('rrule-1');
+		const updated = tasks.getAll().find((t) => t.id === 'rrule-1');
+		expect(updated?.status).toBe('done');
+		expect(updated?.due_date).toBe('2026-02-03');
+		expect(updated?.occurrences_completed ?? 0).toBe(0);
+		expect(updated?.dirty).toBe(true);
+	});
+
 	it('shows recurring tasks completed today in My Day completed after next due is scheduled', () => {
 		tasks.setAll([
 			baseTask({
@@ -8,6 +8,7 @@ import { auth } from '$lib/stores/auth';
 import { api } from '$lib/api/client';
 import { streak } from '$lib/stores/streak';
 import {
+	isRecurrenceRule,
 	nextDueForRecurrence,
 	prevDueForRecurrence,
 	toLocalIsoDate
@@ -332,7 +333,9 @@ export const tasks = {
 				task.id === id
 					? (() => {
 							const now = Date.now();
-							if (task.recurrence_id && task.status !== 'done') {
+							// Only preset keywords roll forward locally. RRULE series are marked
+							// done and the server rolls them to the next occurrence on push.
+							if (isRecurrenceRule(task.recurrence_id) && task.status !== 'done') {
 								shouldPlayCompletion = true;
 								didComplete = true;
 								isRecurring = true;
@@ -245,6 +245,37 @@ describe('syncFromServer', () => {
 		expect(tasks.getAll().find((task) => task.id === 'srv-deleted')).toBeUndefined();
 	});
 
+	it('drops clean tasks missing from a full-resync snapshot but keeps dirty ones', async () => {
+		const base: Task = {
+			id: 'srv-pruned',
+			title: 'deleted while away',
+			status: 'pending',
+			list_id: 'goal-management',
+			my_day: false,
+			priority: 0,
+			tags: [],
+			checklist: [],
+			order: 'a',
+			created_ts: 1,
+			updated_ts: 5,
+			dirty: false,
+			local: false
+		};
+		tasks.setAll([base, { ...base, id: 'srv-edited', dirty: true }]);
+		mockedApi.syncPull.mockResolvedValue({
+			protocol: 'delta-v1',
+			cursor_ts: 40,
+			lists: [],
+			tasks: [remoteTask({ id: 'srv-kept', updated_ts: 40 })],
+			full_resync_required: true
+		});
+
+		await syncFromServer();
+
+		const ids = tasks.getAll().map((task) => task.id).sort();
+		expect(ids).toEqual(['srv-edited', 'srv-kept']);
+	});
+
 	it('applies tombstone deletes even when local copy is dirty', async () => {
 		const dirty: Task = {
 			id: 'srv-dirty',
@@ -647,7 +678,7 @@ describe('pushPendingToServer', () => {
 			tasks.setAll([orphan, local]);
 		}
 
-		mockedApi.syncPush.mockResolvedValue({
+		mockedApi.syncPush.mockImplementation(async ({ changes }) => ({
 			protocol: 'delta-v1',
 			cursor_ts: 3,
 			applied: [
@@ -663,8 +694,10 @@ describe('pushPendingToServer', () => {
 					updated_ts: 3
 				}
 			],
-			rejected: [{ op_id: 'update-0', status: 404, error: 'Not Found' }]
-		});
+			rejected: changes
+				.filter((change) => change.kind === 'update_task')
+				.map((change) => ({ op_id: change.op_id, status: 404, error: 'Not Found' }))
+		}));
 
 		const result = await pushPendingToServer();
 
@@ -692,12 +725,12 @@ describe('pushPendingToServer', () => {
 			dirty: true
 		};
 		tasks.setAll([task]);
-		mockedApi.syncPush.mockResolvedValue({
+		mockedApi.syncPush.mockImplementation(async ({ changes }) => ({
 			protocol: 'delta-v1',
 			cursor_ts: 8,
 			applied: [],
-			rejected: [{ op_id: 'update-0', status: 403, error: 'Forbidden' }]
-		});
+			rejected: [{ op_id: changes[0].op_id, status: 403, error: 'Forbidden' }]
+		}));
 
 		const result = await pushPendingToServer();
 
@@ -725,6 +758,35 @@ describe('pushPendingToServer', () => {
 		expect(tasks.getAll().find((task) => task.id === local?.id)?.dirty).toBe(true);
 	});
 
+	it('reuses op ids for retried edits and issues fresh ones for new edits', async () => {
+		const task: Task = {
+			id: '123e4567-e89b-12d3-a456-426614174222',
+			title: 'first edit',
+			status: 'pending',
+			list_id: 'goal-management',
+			my_day: false,
+			priority: 0,
+			tags: [],
+			checklist: [],
+			order: 'a',
+			created_ts: 1,
+			updated_ts: 1,
+			dirty: true
+		};
+		tasks.setAll([task]);
+		mockedApi.syncPush.mockRejectedValueOnce(new Error('TypeError: Failed to fetch'));
+
+		await pushPendingToServer();
+		await pushPendingToServer();
+		const firstOpId = mockedApi.syncPush.mock.calls[0]?.[0]?.changes?.[0]?.op_id;
+		expect(firstOpId).toBeTruthy();
+		expect(mockedApi.syncPush.mock.calls[1]?.[0]?.changes?.[0]?.op_id).toBe(firstOpId);
+
+		tasks.setAll([{ ...task, title: 'second edit', updated_ts: 2, dirty: true }]);
+		await pushPendingToServer();
+		expect(mockedApi.syncPush.mock.calls[2]?.[0]?.changes?.[0]?.op_id).not.toBe(firstOpId);
+	});
+
 	it('uses sync push cursor as next pull boundary', async () => {
 		mockedApi.syncPull
 			.mockResolvedValueOnce({
@@ -78,14 +78,33 @@ interface PendingPushOp {
 	sent: Task;
 }
 
+// One op id per unsettled edit. A retry of the same edit (same task, kind and
+// updated_ts) reuses its id so the server replays the recorded outcome; a
+// later edit of the task gets a fresh id. Entries are dropped once settled.
+const pendingOpIds = new Map<string, { kind: SyncPushChange['kind']; updated_ts: number; op_id: string }>();
+
+const newOpId = () =>
+	typeof crypto !== 'undefined' && typeof crypto.randomUUID === 'function'
+		? crypto.randomUUID()
+		: `op-${Date.now().toString(36)}-${Math.random().toString(36).slice(2)}`;
+
+const opIdForTask = (task: Task, kind: SyncPushChange['kind']) => {
+	const existing = pendingOpIds.get(task.id);
+	if (existing && existing.kind === kind && existing.updated_ts === task.updated_ts) {
+		return existing.op_id;
+	}
+	const op_id = newOpId();
+	pendingOpIds.set(task.id, { kind, updated_ts: task.updated_ts, op_id });
+	return op_id;
+};
+
 const toPushChange = (
-	task: Task,
-	index: number
+	task: Task
 ): {
 	change: SyncPushChange;
 	op: PendingPushOp;
 } => {
-	const op_id = `${task.local ? 'create' : 'update'}-${index}`;
+	const op_id = opIdForTask(task, task.local ? 'create_task' : 'update_task');
 	if (task.local) {
 		return {
 			change: {
@@ -211,6 +230,12 @@ export const syncFromServer = async () => {
 				: [];
 
 		lists.setAll(toLists);
+		if (pull.full_resync_required) {
+			// Our cursor predates pruned tombstones, so this pull is a full snapshot:
+			// drop clean local tasks the server no longer has.
+			const remoteIds = new Set(toTasks.map((t) => t.id));
+			tasks.setAll(tasks.getAll().filter((t) => t.dirty || t.local || remoteIds.has(t.id)));
+		}
 		tasks.mergeRemote(toTasks);
 		tasks.applyRemoteDeletes(deletedTasks);
 		bumpSyncCursor(pull.cursor_ts);
@@ -288,7 +313,7 @@ export const pushPendingToServer = async () => {
 		return { pushed: 0, created: 0, rejected: 0 };
 	}
 
-	const pendingOps = syncable.map((task, index) => toPushChange(task, index));
+	const pendingOps = syncable.map((task) => toPushChange(task));
 	const opById = new Map<string, PendingPushOp>(pendingOps.map((entry) => [entry.op.op_id, entry.op]));
 
 	let pushed = 0;
@@ -315,6 +340,9 @@ export const pushPendingToServer = async () => {
 		}
 
 		const firstUnhandledError = applyRejections(response.rejected, opById);
+		for (const entry of pendingOps) {
+			pendingOpIds.delete(entry.op.localTaskId);
+		}
 
 		await repo.saveTasks(tasks.getAll());
 		const remainingDirty = tasks.getAll().filter((task) => task.dirty).length;